@@ -0,0 +1,306 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use todo_lib::{todo, todotxt};
+
+use crate::conf;
+use crate::wildcard;
+
+// How completed todos are split between archive files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    // All todos go to the same `done.txt`
+    #[default]
+    None,
+    // A file per month: `done-2026-10.txt`
+    Monthly,
+    // A file per year: `done-2026.txt`
+    Yearly,
+}
+
+pub fn str_to_rotation(s: &str) -> Option<Rotation> {
+    match s.to_lowercase().as_str() {
+        "none" | "" => Some(Rotation::None),
+        "monthly" | "month" | "m" => Some(Rotation::Monthly),
+        "yearly" | "year" | "y" => Some(Rotation::Yearly),
+        _ => None,
+    }
+}
+
+// Splits a file name into a stem and an extension with a leading dot
+fn split_name(done: &Path) -> (String, String) {
+    let stem = done.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = done.extension().map(|s| format!(".{}", s.to_string_lossy())).unwrap_or_default();
+    (stem, ext)
+}
+
+// Returns the archive file for a todo completed on `date`
+pub fn rotated_path(done: &Path, date: Option<chrono::NaiveDate>, rot: Rotation) -> PathBuf {
+    let suffix = match (rot, date) {
+        (Rotation::Monthly, Some(dt)) => dt.format("%Y-%m").to_string(),
+        (Rotation::Yearly, Some(dt)) => dt.format("%Y").to_string(),
+        _ => return done.to_path_buf(),
+    };
+    let (stem, ext) = split_name(done);
+    done.with_file_name(format!("{stem}-{suffix}{ext}"))
+}
+
+// Returns the list of files to read archived todos from.
+// If the path contains wildcards, all matching files are returned.
+// If the rotation is enabled, the main archive file is returned along with all rotated ones.
+pub fn archive_paths(done: &Path, rot: Rotation) -> Vec<PathBuf> {
+    if wildcard::is_wildcard(done) {
+        return wildcard::expand(done);
+    }
+    let mut paths = vec![done.to_path_buf()];
+    if rot != Rotation::None {
+        let (stem, ext) = split_name(done);
+        paths.extend(wildcard::expand(&done.with_file_name(format!("{stem}-*{ext}"))));
+    }
+    paths
+}
+
+// Loads archived todos from all archive files
+pub fn load(done: &Path, rot: Rotation) -> Result<todo::TaskVec, String> {
+    let mut tasks = Vec::new();
+    for path in archive_paths(done, rot) {
+        match todo::load(&path) {
+            Ok(mut l) => tasks.append(&mut l),
+            Err(e) => return Err(format!("Failed to load done list from '{0}': {e:?}", path.display())),
+        }
+    }
+    Ok(tasks)
+}
+
+// Groups todos by archive files they must be saved to.
+// If `all_files` is true, the result includes existing archive files that get no todos.
+pub fn split_by_file(
+    tasks: &todo::TaskSlice,
+    done: &Path,
+    rot: Rotation,
+    all_files: bool,
+) -> Result<Vec<(PathBuf, todo::TaskVec)>, String> {
+    if wildcard::is_wildcard(done) {
+        return Err(format!("Cannot save archived todos to '{0}': the path contains wildcards", done.display()));
+    }
+    let mut files: Vec<(PathBuf, todo::TaskVec)> = Vec::new();
+    if all_files {
+        for path in archive_paths(done, rot) {
+            files.push((path, Vec::new()));
+        }
+    }
+    for task in tasks {
+        let path = rotated_path(done, task.finish_date, rot);
+        match files.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, l)) => l.push(task.clone()),
+            None => files.push((path, vec![task.clone()])),
+        }
+    }
+    Ok(files)
+}
+
+// Builds the new content of files changed by unarchiving: `restored` todos are moved back to their
+// active lists, and todos with IDs `ids` are removed from `done_tasks`. The result contains both
+// active and archived files of all affected task lists.
+pub fn unarchive_lists(
+    tasks: &todo::TaskSlice,
+    done_tasks: &todo::TaskSlice,
+    ids: &todo::IDSlice,
+    restored: &todo::TaskSlice,
+    conf: &conf::Conf,
+) -> Result<Vec<(todo::TaskVec, PathBuf)>, String> {
+    let single = conf.is_single_file_mode();
+    let list_id = |task: &todotxt::Task| if single { Some(0) } else { task.source.as_ref().map(|src| src.id) };
+    let mut list_ids: Vec<usize> = restored.iter().filter_map(list_id).collect();
+    list_ids.sort_unstable();
+    list_ids.dedup();
+
+    let mut lists: Vec<(todo::TaskVec, PathBuf)> = Vec::new();
+    for lid in list_ids {
+        let tlist = &conf.task_lists[lid];
+        let active: todo::TaskVec =
+            tasks.iter().chain(restored.iter()).filter(|t| list_id(t) == Some(lid)).cloned().collect();
+        let archived: todo::TaskVec = done_tasks
+            .iter()
+            .enumerate()
+            .filter(|(idx, t)| list_id(t) == Some(lid) && !ids.contains(idx))
+            .map(|(_, t)| t.clone())
+            .collect();
+        lists.push((active, tlist.todo_file.clone()));
+        for (path, archived) in split_by_file(&archived, &tlist.done_file, conf.archive_rotation, true)? {
+            lists.push((archived, path));
+        }
+    }
+    Ok(lists)
+}
+
+fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Writes all lists to temporary files first, and replaces the original files only when all
+// temporary files are written successfully. So, a failed write does not leave a half-moved todo.
+// The original files are kept as backups until all files are replaced: if a file cannot be
+// replaced, the already replaced files are restored from their backups.
+pub fn save_together(lists: &[(todo::TaskVec, PathBuf)]) -> Result<(), String> {
+    let mut tmp_paths = Vec::new();
+    for (tlist, path) in lists {
+        let tmpname = path.with_extension("unarc.tmp");
+        let res = File::create(&tmpname).and_then(|mut f| {
+            for t in tlist {
+                writeln!(f, "{t}")?;
+            }
+            f.sync_all()
+        });
+        tmp_paths.push(tmpname);
+        if let Err(e) = res {
+            remove_files(&tmp_paths);
+            return Err(format!("Failed to save task list to '{0}': {e:?}", path.display()));
+        }
+    }
+
+    let mut replaced: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+    for (tmp, (_, path)) in tmp_paths.iter().zip(lists.iter()) {
+        let backup = path.with_extension("unarc.bak");
+        let res = if path.exists() { fs::rename(path, &backup).map(|_| Some(backup)) } else { Ok(None) };
+        let res = res.and_then(|backup| match fs::rename(tmp, path) {
+            Ok(_) => Ok(backup),
+            Err(e) => {
+                if let Some(b) = &backup {
+                    let _ = fs::rename(b, path);
+                }
+                Err(e)
+            }
+        });
+        match res {
+            Ok(backup) => replaced.push((path, backup)),
+            Err(e) => {
+                remove_files(&tmp_paths);
+                let mut msg = format!("Failed to save task list to '{0}': {e:?}", path.display());
+                for (path, backup) in replaced.iter().rev() {
+                    let res = match backup {
+                        Some(b) => fs::rename(b, path),
+                        None => fs::remove_file(path),
+                    };
+                    if let Err(e) = res {
+                        msg += &format!("\nFailed to restore '{0}': {e:?}", path.display());
+                    }
+                }
+                return Err(msg);
+            }
+        }
+    }
+    for (_, backup) in replaced {
+        if let Some(b) = backup {
+            let _ = fs::remove_file(b);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn rotated_path_test() {
+        struct Test {
+            done: &'static str,
+            date: Option<NaiveDate>,
+            rot: Rotation,
+            res: &'static str,
+        }
+        let dt = NaiveDate::from_ymd_opt(2026, 10, 5);
+        let tests: Vec<Test> = vec![
+            Test { done: "/tmp/done.txt", date: dt, rot: Rotation::None, res: "/tmp/done.txt" },
+            Test { done: "/tmp/done.txt", date: dt, rot: Rotation::Monthly, res: "/tmp/done-2026-10.txt" },
+            Test { done: "/tmp/done.txt", date: dt, rot: Rotation::Yearly, res: "/tmp/done-2026.txt" },
+            Test { done: "/tmp/done.txt", date: None, rot: Rotation::Yearly, res: "/tmp/done.txt" },
+            Test { done: "/tmp/archive", date: dt, rot: Rotation::Monthly, res: "/tmp/archive-2026-10" },
+        ];
+        for test in tests.iter() {
+            let res = rotated_path(Path::new(test.done), test.date, test.rot);
+            assert_eq!(PathBuf::from(test.res), res, "{} - {:?}", test.done, test.rot);
+        }
+    }
+
+    fn list_files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn unarchive_lists_test() {
+        let dt = NaiveDate::from_ymd_opt(2026, 10, 5).unwrap();
+        let mut conf = conf::Conf::default();
+        for name in ["home", "work"] {
+            conf.task_lists.push(conf::Source {
+                name: name.to_string(),
+                todo_file: PathBuf::from(format!("/tmp/{name}/todo.txt")),
+                done_file: PathBuf::from(format!("/tmp/{name}/done.txt")),
+                ..Default::default()
+            });
+        }
+        let parse = |s: &str, id: usize| {
+            let mut t = todotxt::Task::parse(s, dt);
+            t.source = Some(todotxt::Source { name: conf.task_lists[id].name.clone(), id });
+            t
+        };
+        let tasks = vec![parse("home active", 0), parse("work active", 1)];
+        let done_tasks = vec![parse("x home done", 0), parse("x work first", 1), parse("x work second", 1)];
+        let restored = vec![parse("work first", 1)];
+        let lists = unarchive_lists(&tasks, &done_tasks, &[1], &restored, &conf).unwrap();
+        let res: Vec<(Vec<String>, &str)> =
+            lists.iter().map(|(l, p)| (l.iter().map(|t| t.to_string()).collect(), p.to_str().unwrap())).collect();
+        let expected = vec![
+            (vec!["work active".to_string(), "work first".to_string()], "/tmp/work/todo.txt"),
+            (vec!["x work second".to_string()], "/tmp/work/done.txt"),
+        ];
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn save_together_test() {
+        let dt = NaiveDate::from_ymd_opt(2026, 10, 5).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let todo_path = dir.path().join("todo.txt");
+        let done_path = dir.path().join("done.txt");
+        fs::write(&todo_path, "old todo\n").unwrap();
+        fs::write(&done_path, "x old done\n").unwrap();
+        let lists = vec![
+            (vec![todotxt::Task::parse("new todo", dt)], todo_path.clone()),
+            (vec![todotxt::Task::parse("x new done", dt)], done_path.clone()),
+        ];
+        save_together(&lists).unwrap();
+        assert_eq!(fs::read_to_string(&todo_path).unwrap(), "new todo\n");
+        assert_eq!(fs::read_to_string(&done_path).unwrap(), "x new done\n");
+        assert_eq!(list_files(dir.path()), vec!["done.txt", "todo.txt"]);
+
+        // The archive cannot be backed up: the first file must be restored
+        fs::create_dir_all(dir.path().join("done.unarc.bak/keep")).unwrap();
+        let lists = vec![
+            (vec![todotxt::Task::parse("newer todo", dt)], todo_path.clone()),
+            (vec![todotxt::Task::parse("x newer done", dt)], done_path.clone()),
+        ];
+        let err = save_together(&lists).unwrap_err();
+        assert!(err.contains("done.txt"), "{err}");
+        assert_eq!(fs::read_to_string(&todo_path).unwrap(), "new todo\n");
+        assert_eq!(fs::read_to_string(&done_path).unwrap(), "x new done\n");
+        assert_eq!(list_files(dir.path()), vec!["done.txt", "done.unarc.bak", "todo.txt"]);
+
+        // A temporary file cannot be created: nothing is changed
+        let lists = vec![
+            (vec![todotxt::Task::parse("newer todo", dt)], todo_path.clone()),
+            (vec![todotxt::Task::parse("x newer done", dt)], dir.path().join("missing/done.txt")),
+        ];
+        assert!(save_together(&lists).is_err());
+        assert_eq!(fs::read_to_string(&todo_path).unwrap(), "new todo\n");
+        assert_eq!(list_files(dir.path()), vec!["done.txt", "done.unarc.bak", "todo.txt"]);
+    }
+}
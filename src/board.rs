@@ -0,0 +1,396 @@
+use std::io::{self, Write};
+
+use termcolor::{Color, StandardStream, WriteColor};
+use todo_lib::{todo, todotxt};
+use unicode_width::UnicodeWidthStr;
+
+use crate::conf;
+use crate::fmt;
+
+// A column narrower than this value is hard to read. If all columns do not fit the screen,
+// the board is split into a few rows of columns
+const MIN_COLUMN_WIDTH: usize = 16;
+const COLUMN_GAP: &str = " | ";
+const EMPTY_COLUMN: &str = "[Empty]";
+// The value of the command `move` that removes the field value from a todo
+pub const NO_COLUMN: &str = "-";
+
+pub struct Column {
+    pub name: String,
+    pub ids: todo::IDVec,
+}
+
+// Returns true if the field is a tag and a todo can be moved between columns by changing the tag value
+fn is_tag_field(field: &str) -> bool {
+    !matches!(field, "pri" | "priority" | "prj" | "project" | "ctx" | "context" | "hash" | "hashtag" | "src" | "src_id")
+}
+
+// Returns the values of the field to put a todo into columns. Unlike grouping, priority is
+// treated as a field with a single value here.
+fn column_values(task: &todotxt::Task, field: &str) -> Vec<String> {
+    match field {
+        "pri" | "priority" if task.priority < todotxt::NO_PRIORITY => {
+            vec![todotxt::priority_to_char(task.priority).to_string()]
+        }
+        "pri" | "priority" => vec![String::new()],
+        _ => fmt::values_of_field(task, field),
+    }
+}
+
+// Distributes todos between columns by values of the field. A todo that has a few values(e.g,
+// a few contexts) is displayed in every column. Predefined columns go first in the given order,
+// even if they are empty. They are followed by the rest values sorted alphabetically. The last
+// column contains todos without any value.
+pub fn build_columns(
+    tasks: &todo::TaskSlice,
+    selected: &todo::IDSlice,
+    field: &str,
+    predefined: &[String],
+) -> Vec<Column> {
+    let mut columns: Vec<Column> =
+        predefined.iter().map(|name| Column { name: name.clone(), ids: Vec::new() }).collect();
+    let mut extra: Vec<Column> = Vec::new();
+    let mut empty = Column { name: String::new(), ids: Vec::new() };
+    for id in selected {
+        for value in column_values(&tasks[*id], field) {
+            if value.is_empty() {
+                empty.ids.push(*id);
+            } else if let Some(col) = columns.iter_mut().find(|c| c.name == value) {
+                col.ids.push(*id);
+            } else if let Some(col) = extra.iter_mut().find(|c| c.name == value) {
+                col.ids.push(*id);
+            } else {
+                extra.push(Column { name: value, ids: vec![*id] });
+            }
+        }
+    }
+    extra.sort_by(|a, b| a.name.cmp(&b.name));
+    columns.append(&mut extra);
+    if !empty.ids.is_empty() {
+        columns.push(empty);
+    }
+    columns
+}
+
+// The text of a todo card. The tag used for grouping is removed from the subject because
+// the column title shows its value.
+fn card_text(task: &todotxt::Task, id: usize, field: &str) -> String {
+    let mut subj = task.subject.clone();
+    if is_tag_field(field)
+        && let Some(value) = task.tags.get(field)
+    {
+        todotxt::replace_word(&mut subj, &format!("{field}:{value}"), "");
+    }
+    let subj = subj.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if task.priority < todotxt::NO_PRIORITY && !matches!(field, "pri" | "priority") {
+        format!("{} ({}) {subj}", id + 1, todotxt::priority_to_char(task.priority))
+    } else {
+        format!("{} {subj}", id + 1)
+    }
+}
+
+// Wraps every card of a column to fit the column width. The next lines of a card are indented
+fn column_lines(tasks: &todo::TaskSlice, col: &Column, field: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let indent = " ".repeat(fmt::number_of_digits(tasks.len()) + 1);
+    for id in &col.ids {
+        let text = card_text(&tasks[*id], *id, field);
+        let opts = textwrap::Options::new(width).subsequent_indent(&indent);
+        for line in textwrap::wrap(&text, opts) {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+fn print_padded(stdout: &mut StandardStream, s: &str, width: usize) -> io::Result<()> {
+    let w = s.width();
+    if w >= width { write!(stdout, "{s}") } else { write!(stdout, "{s}{}", " ".repeat(width - w)) }
+}
+
+fn print_columns(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    columns: &[Column],
+    field: &str,
+    width: usize,
+    conf: &conf::Conf,
+) -> io::Result<()> {
+    let mut clr = conf.fmt.colors.default_fg.clone();
+    clr.set_fg(Some(Color::Green));
+    stdout.set_color(&clr)?;
+    for (idx, col) in columns.iter().enumerate() {
+        if idx != 0 {
+            write!(stdout, "{COLUMN_GAP}")?;
+        }
+        let name = if col.name.is_empty() { EMPTY_COLUMN } else { col.name.as_str() };
+        let title = format!("{name} ({})", col.ids.len());
+        let title = fmt::cut_string(&title, width);
+        print_padded(stdout, title, width)?;
+    }
+    writeln!(stdout)?;
+    let sep = vec!["-".repeat(width); columns.len()].join("-+-");
+    writeln!(stdout, "{sep}")?;
+    stdout.set_color(&conf.fmt.colors.default_fg)?;
+
+    let cells: Vec<Vec<String>> = columns.iter().map(|col| column_lines(tasks, col, field, width)).collect();
+    let rows = cells.iter().map(|c| c.len()).max().unwrap_or(0);
+    for row in 0..rows {
+        let mut line = String::new();
+        for (idx, cell) in cells.iter().enumerate() {
+            if idx != 0 {
+                line += COLUMN_GAP;
+            }
+            let s = if row < cell.len() { cell[row].as_str() } else { "" };
+            line += s;
+            let w = s.width();
+            if idx != cells.len() - 1 && w < width {
+                line += &" ".repeat(width - w);
+            }
+        }
+        writeln!(stdout, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+// Displays todos as a board: every column contains todos with the same value of a field.
+pub fn print_board(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    columns: &[Column],
+    conf: &conf::Conf,
+) -> io::Result<()> {
+    if columns.is_empty() {
+        return writeln!(stdout, "No todo found");
+    }
+    let total_width = if conf.fmt.width == 0 { 80 } else { usize::from(conf.fmt.width) };
+    let gap = COLUMN_GAP.len();
+    let per_row = ((total_width + gap) / (MIN_COLUMN_WIDTH + gap)).clamp(1, columns.len());
+    let width = ((total_width + gap) / per_row).saturating_sub(gap).max(MIN_COLUMN_WIDTH);
+    for (idx, chunk) in columns.chunks(per_row).enumerate() {
+        if idx != 0 {
+            writeln!(stdout)?;
+        }
+        print_columns(stdout, tasks, chunk, &conf.board_field, width, conf)?;
+    }
+    Ok(())
+}
+
+// Moves a todo to a column by changing the value of the board field. For projects and
+// contexts, the values that are other board columns are removed from the todo. If the list of
+// columns is not set, only the todo with a single value is moved: its value is replaced. A todo with
+// a few values is just added to the column because it is unclear what column it is moved from.
+// Moving to the "no value" column removes all values in both cases.
+// Returns true if the todo was changed.
+pub fn move_task(task: &mut todotxt::Task, field: &str, column: &str, columns: &[String]) -> Result<bool, String> {
+    let column = if column == NO_COLUMN { "" } else { column };
+    match field {
+        "pri" | "priority" => {
+            let pri = if column.is_empty() {
+                todotxt::NO_PRIORITY
+            } else {
+                let mut chars = column.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_alphabetic() => todotxt::char_to_priority(c.to_ascii_uppercase()),
+                    _ => return Err(format!("Invalid priority '{column}'")),
+                }
+            };
+            if task.priority == pri {
+                return Ok(false);
+            }
+            task.priority = pri;
+            Ok(true)
+        }
+        "prj" | "project" | "ctx" | "context" => {
+            let is_prj = matches!(field, "prj" | "project");
+            let old_subj = task.subject.clone();
+            let old_values = if is_prj { task.projects.clone() } else { task.contexts.clone() };
+            let single = old_values.len() == 1;
+            for value in old_values {
+                let remove = if !columns.is_empty() { columns.contains(&value) } else { column.is_empty() || single };
+                if value != column && remove {
+                    if is_prj {
+                        task.replace_project(&value, "");
+                    } else {
+                        task.replace_context(&value, "");
+                    }
+                }
+            }
+            if is_prj {
+                task.replace_project("", column);
+            } else {
+                task.replace_context("", column);
+            }
+            Ok(old_subj != task.subject)
+        }
+        "hash" | "hashtag" | "src" | "src_id" => {
+            Err(format!("Moving todos between columns by '{field}' is not supported"))
+        }
+        tag => {
+            if todo::is_tag_special(tag) {
+                return Err(format!("Moving todos between columns by '{field}' is not supported"));
+            }
+            Ok(task.update_tag_with_value(tag, column))
+        }
+    }
+}
+
+// Moves selected todos to a column. Returns the list of flags: what todos were changed
+pub fn move_tasks(
+    tasks: &mut todo::TaskVec,
+    ids: &todo::IDSlice,
+    field: &str,
+    column: &str,
+    columns: &[String],
+) -> Result<todo::ChangedVec, String> {
+    let mut changed = Vec::new();
+    for id in ids {
+        changed.push(move_task(&mut tasks[*id], field, column, columns)?);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn build_columns_test() {
+        struct Test {
+            predefined: Vec<String>,
+            field: &'static str,
+            res: Vec<(&'static str, Vec<usize>)>,
+        }
+        let today = NaiveDate::from_ymd_opt(2020, 3, 10).unwrap();
+        let tasks: todo::TaskVec = vec![
+            todotxt::Task::parse("(A) first status:doing @home", today),
+            todotxt::Task::parse("second status:todo @home @work", today),
+            todotxt::Task::parse("(B) third", today),
+            todotxt::Task::parse("fourth status:doing", today),
+        ];
+        let selected: todo::IDVec = vec![0, 1, 2, 3];
+        let tests: Vec<Test> = vec![
+            Test {
+                predefined: Vec::new(),
+                field: "status",
+                res: vec![("doing", vec![0, 3]), ("todo", vec![1]), ("", vec![2])],
+            },
+            Test {
+                predefined: vec!["todo".to_string(), "doing".to_string(), "done".to_string()],
+                field: "status",
+                res: vec![("todo", vec![1]), ("doing", vec![0, 3]), ("done", vec![]), ("", vec![2])],
+            },
+            Test {
+                predefined: Vec::new(),
+                field: "ctx",
+                res: vec![("home", vec![0, 1]), ("work", vec![1]), ("", vec![2, 3])],
+            },
+            Test { predefined: Vec::new(), field: "pri", res: vec![("A", vec![0]), ("B", vec![2]), ("", vec![1, 3])] },
+        ];
+        for test in tests.iter() {
+            let cols = build_columns(&tasks, &selected, test.field, &test.predefined);
+            let res: Vec<(&str, Vec<usize>)> = cols.iter().map(|c| (c.name.as_str(), c.ids.clone())).collect();
+            assert_eq!(test.res, res, "{} --> {:?} != {:?}", test.field, test.res, res);
+        }
+    }
+
+    #[test]
+    fn move_task_test() {
+        struct Test {
+            subj: &'static str,
+            field: &'static str,
+            column: &'static str,
+            columns: Vec<String>,
+            res: &'static str,
+            changed: bool,
+        }
+        let today = NaiveDate::from_ymd_opt(2020, 3, 10).unwrap();
+        let tests: Vec<Test> = vec![
+            Test {
+                subj: "task status:todo",
+                field: "status",
+                column: "doing",
+                columns: Vec::new(),
+                res: "task status:doing",
+                changed: true,
+            },
+            Test {
+                subj: "task",
+                field: "status",
+                column: "doing",
+                columns: Vec::new(),
+                res: "task status:doing",
+                changed: true,
+            },
+            Test {
+                subj: "task status:todo extra",
+                field: "status",
+                column: "-",
+                columns: Vec::new(),
+                res: "task extra",
+                changed: true,
+            },
+            Test {
+                subj: "task status:done",
+                field: "status",
+                column: "done",
+                columns: Vec::new(),
+                res: "task status:done",
+                changed: false,
+            },
+            Test { subj: "(B) task", field: "pri", column: "a", columns: Vec::new(), res: "(A) task", changed: true },
+            Test { subj: "(B) task", field: "pri", column: "-", columns: Vec::new(), res: "task", changed: true },
+            Test {
+                subj: "task @home @phone",
+                field: "ctx",
+                column: "work",
+                columns: vec!["home".to_string(), "work".to_string()],
+                res: "task @phone @work",
+                changed: true,
+            },
+            Test {
+                subj: "task  with  spaces @home",
+                field: "ctx",
+                column: "work",
+                columns: Vec::new(),
+                res: "task  with  spaces @work",
+                changed: true,
+            },
+            Test {
+                subj: "task @home @phone",
+                field: "ctx",
+                column: "work",
+                columns: Vec::new(),
+                res: "task @home @phone @work",
+                changed: true,
+            },
+            Test {
+                subj: "task @home @phone",
+                field: "ctx",
+                column: "-",
+                columns: Vec::new(),
+                res: "task",
+                changed: true,
+            },
+            Test {
+                subj: "task +old",
+                field: "prj",
+                column: "new",
+                columns: Vec::new(),
+                res: "task +new",
+                changed: true,
+            },
+        ];
+        for test in tests.iter() {
+            let mut task = todotxt::Task::parse(test.subj, today);
+            let changed = move_task(&mut task, test.field, test.column, &test.columns).unwrap();
+            let res = format!("{task}");
+            assert_eq!(test.res, res, "{} --> {} != {}", test.subj, test.res, res);
+            assert_eq!(test.changed, changed, "{} changed: {} != {}", test.subj, test.changed, changed);
+        }
+        let mut task = todotxt::Task::parse("task #tag", today);
+        assert!(move_task(&mut task, "hash", "other", &[]).is_err());
+        assert!(move_task(&mut task, "pri", "AB", &[]).is_err());
+    }
+}
@@ -3,9 +3,14 @@ use std::io::{self, Write};
 
 use chrono::{Datelike, NaiveDate, Weekday};
 use termcolor::{Color, StandardStream, WriteColor};
+use todo_lib::{conv, todotxt};
 
 use crate::conf;
 
+// For every date, the list of all matched task dates: the todo ID and the field index(in the order
+// of calendar fields). A todo can be in the list a few times if a few its fields have the same date.
+pub type DayCounter = HashMap<NaiveDate, Vec<(usize, usize)>>;
+
 pub struct CalPrinter {
     start_date: NaiveDate,
     end_date: NaiveDate,
@@ -15,11 +20,15 @@ pub struct CalPrinter {
     // first column
     started: bool, // false = the struct intialized but there was no call to print_next_line
     // yet
+    // Marks for every date field. Empty if the calendar shows only one field
+    marks: Vec<char>,
     cols: Vec<Option<NaiveDate>>, // The current dates for every month column displayed. If
                                   // it is None, the month is finished
 }
 
 const MONTH_WIDTH: u16 = 3 + 7 * 3; // week # + 7 days and a space in-between
+// The mark for a date that has todos matched by different kinds of fields
+const MARK_MIXED: char = '*';
 const MONTH_NAMES: [&str; 12] = [
     "January",
     "February",
@@ -35,8 +44,17 @@ const MONTH_NAMES: [&str; 12] = [
     "December",
 ];
 
+// The number of different todos among matched task dates
+fn todo_count(fields: &[(usize, usize)]) -> usize {
+    let mut ids: Vec<usize> = fields.iter().map(|(id, _)| *id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.len()
+}
+
 impl CalPrinter {
-    pub fn new(s: NaiveDate, e: NaiveDate, max_width: u16) -> CalPrinter {
+    pub fn new(s: NaiveDate, e: NaiveDate, max_width: u16, fields: &[String]) -> CalPrinter {
+        let marks = if fields.len() > 1 { fields.iter().map(|f| field_mark(f)).collect() } else { Vec::new() };
         let mut cp = CalPrinter {
             start_date: s,
             end_date: e,
@@ -45,6 +63,7 @@ impl CalPrinter {
             first_idx: 0,
             total_months: months_between(s, e),
             started: false,
+            marks,
         };
 
         let mc = cp.total_months;
@@ -168,6 +187,26 @@ impl CalPrinter {
     fn is_first_day_of_week(&self, dt: NaiveDate, conf: &conf::Conf) -> bool {
         (dt.weekday() == Weekday::Sun && conf.first_sunday) || (dt.weekday() == Weekday::Mon && !conf.first_sunday)
     }
+    // The character to display before a day number. If all todos of the date are matched by the
+    // same kind of field, the field mark is used. Otherwise, the date is marked as mixed.
+    fn day_mark(&self, fields: Option<&Vec<(usize, usize)>>) -> char {
+        if self.marks.is_empty() {
+            return ' ';
+        }
+        let Some(fields) = fields else {
+            return ' ';
+        };
+        let mut mark = ' ';
+        for (_, idx) in fields {
+            let m = self.marks[*idx];
+            if mark == ' ' {
+                mark = m;
+            } else if mark != m {
+                return MARK_MIXED;
+            }
+        }
+        mark
+    }
     // The main loop function that does everything:
     // - print the current line of month columns
     // - if all months are completed, it prints the next header and intializes
@@ -175,7 +214,7 @@ impl CalPrinter {
     pub fn print_next_line(
         &mut self,
         stdout: &mut StandardStream,
-        counter: &HashMap<NaiveDate, u32>,
+        counter: &DayCounter,
         today: NaiveDate,
         conf: &conf::Conf,
     ) -> io::Result<bool> {
@@ -223,13 +262,14 @@ impl CalPrinter {
                         if dt == today {
                             clr.set_bg(Some(Color::Blue));
                         }
-                        if let Some(n) = counter.get(&dt) {
-                            let fg = if n > &1 { Color::Red } else { Color::Magenta };
+                        let fields = counter.get(&dt);
+                        if let Some(n) = fields {
+                            let fg = if todo_count(n) > 1 { Color::Red } else { Color::Magenta };
                             clr.set_fg(Some(fg));
                         }
                         stdout.set_color(&clr)?;
 
-                        write!(stdout, "{:>3}", dt.day())?;
+                        write!(stdout, "{}{:>2}", self.day_mark(fields), dt.day())?;
                         dt = dt.succ_opt().unwrap_or_else(|| panic!("the next date must exist for {dt}"));
                         if dt > self.end_date {
                             break;
@@ -278,6 +318,40 @@ impl CalPrinter {
         writeln!(stdout)?;
         Ok(())
     }
+    // Prints what field every mark means. Nothing is printed if the calendar shows only one field
+    pub fn print_legend(&self, stdout: &mut StandardStream, fields: &[String]) -> io::Result<()> {
+        if self.marks.is_empty() {
+            return Ok(());
+        }
+        let mut legend: Vec<String> = Vec::new();
+        for (field, mark) in fields.iter().zip(self.marks.iter()) {
+            legend.push(format!("{mark} {field}"));
+        }
+        legend.push(format!("{MARK_MIXED} mixed"));
+        writeln!(stdout, "{}", legend.join(", "))
+    }
+}
+
+// Returns a mark for a date field: `due`, `threshold`, `created`, `finished`, or any custom tag.
+pub fn field_mark(field: &str) -> char {
+    match field {
+        "due" => '!',
+        "t" | "thr" | "threshold" => '^',
+        "created" => '+',
+        "finished" | "completed" => 'x',
+        _ => '~',
+    }
+}
+
+// Returns the date of a task field. Any unknown field name is treated as a tag with a date value
+pub fn task_field_date(task: &todotxt::Task, field: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match field {
+        "due" => task.due_date,
+        "t" | "thr" | "threshold" => task.threshold_date,
+        "created" => task.create_date,
+        "finished" | "completed" => task.finish_date,
+        fname => task.tags.get(fname).and_then(|fval| conv::str_to_date(fval, today)),
+    }
 }
 
 // Returns the first day of a month advancing `m_delta` months ahead.
@@ -387,4 +461,63 @@ mod tests {
             assert_eq!(test.e, e, "{0} --> {1} = {2} != {3}", test.b, test.m, test.e, e);
         }
     }
+
+    #[test]
+    fn day_mark_test() {
+        struct Test {
+            fields: Option<Vec<(usize, usize)>>,
+            m: char,
+        }
+        let d = NaiveDate::from_ymd_opt(2000, 1, 6).unwrap();
+        let fields = vec!["due".to_string(), "t".to_string(), "start".to_string(), "end".to_string()];
+        let cp = CalPrinter::new(d, d, 80, &fields);
+        let tests: Vec<Test> = vec![
+            Test { fields: None, m: ' ' },
+            Test { fields: Some(vec![(0, 0)]), m: '!' },
+            Test { fields: Some(vec![(0, 1), (1, 1)]), m: '^' },
+            Test { fields: Some(vec![(0, 0), (1, 1)]), m: MARK_MIXED },
+            Test { fields: Some(vec![(0, 0), (0, 1)]), m: MARK_MIXED },
+            Test { fields: Some(vec![(2, 2), (3, 3)]), m: '~' },
+        ];
+        for test in tests.iter() {
+            let m = cp.day_mark(test.fields.as_ref());
+            assert_eq!(test.m, m, "{:?} --> {} != {}", test.fields, test.m, m);
+        }
+
+        let cp = CalPrinter::new(d, d, 80, &fields[..1]);
+        assert_eq!(cp.day_mark(Some(&vec![(0, 0)])), ' ');
+    }
+
+    #[test]
+    fn todo_count_test() {
+        assert_eq!(todo_count(&[]), 0);
+        assert_eq!(todo_count(&[(3, 0)]), 1);
+        // The same todo with due and threshold dates on the same day
+        assert_eq!(todo_count(&[(3, 0), (3, 1)]), 1);
+        assert_eq!(todo_count(&[(3, 0), (5, 0), (3, 1)]), 2);
+    }
+
+    #[test]
+    fn task_field_date_test() {
+        struct Test {
+            field: &'static str,
+            d: Option<NaiveDate>,
+        }
+        let today = NaiveDate::from_ymd_opt(2020, 3, 10).unwrap();
+        let task =
+            todotxt::Task::parse("x 2020-03-05 2020-01-01 task due:2020-04-01 t:2020-03-15 start:2020-03-20", today);
+        let tests: Vec<Test> = vec![
+            Test { field: "due", d: NaiveDate::from_ymd_opt(2020, 4, 1) },
+            Test { field: "t", d: NaiveDate::from_ymd_opt(2020, 3, 15) },
+            Test { field: "threshold", d: NaiveDate::from_ymd_opt(2020, 3, 15) },
+            Test { field: "created", d: NaiveDate::from_ymd_opt(2020, 1, 1) },
+            Test { field: "finished", d: NaiveDate::from_ymd_opt(2020, 3, 5) },
+            Test { field: "start", d: NaiveDate::from_ymd_opt(2020, 3, 20) },
+            Test { field: "end", d: None },
+        ];
+        for test in tests.iter() {
+            let d = task_field_date(&task, test.field, today);
+            assert_eq!(test.d, d, "{0} --> {1:?} != {2:?}", test.field, test.d, d);
+        }
+    }
 }
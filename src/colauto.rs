@@ -64,7 +64,7 @@ fn is_field_empty(tasks: &todo::TaskSlice, ids: &todo::IDSlice, field: &str) ->
 
 fn header_width(field: &str) -> usize {
     match field {
-        "done" | "pri" => 1,
+        "done" | "arc" | "pri" => 1,
         "thr" => "threshold".width(),
         "prj" => "project".width(),
         "ctx" => "context".width(),
@@ -132,7 +132,7 @@ pub fn filter_non_empty(tasks: &todo::TaskSlice, ids: &todo::IDSlice, fields: &[
 pub fn cleanup_description(desc: &mut String, fields: &[&str], c: &Conf) {
     for f in fields.iter() {
         match *f {
-            "id" | "done" | "pri" | "created" | "finished" => continue,
+            "id" | "done" | "arc" | "pri" | "created" | "finished" => continue,
             "thr" => hide_tags(desc, "t", c),
             "spent" => {
                 hide_tags(desc, "tmr", c);
@@ -0,0 +1,155 @@
+use crate::help::{self, Arg};
+
+// Options which values are completed with live data of task lists, and the kind of the data
+// passed to the hidden command `__complete`
+const VALUE_KINDS: &[(&str, &str)] = &[
+    ("project", "projects"),
+    ("set-proj", "projects"),
+    ("del-proj", "projects"),
+    ("context", "contexts"),
+    ("set-ctx", "contexts"),
+    ("del-ctx", "contexts"),
+    ("hashtag", "hashtags"),
+    ("set-hashtag", "hashtags"),
+    ("del-hashtag", "hashtags"),
+    ("tag", "tags"),
+    ("del-tag", "tags"),
+    ("src", "sources"),
+    ("to", "sources"),
+];
+
+// Prefixes of filter words and the kinds of their values
+const PREFIX_KINDS: &[(char, &str)] = &[('+', "projects"), ('@', "contexts"), ('#', "hashtags")];
+
+fn option_names() -> Vec<String> {
+    let mut names = Vec::new();
+    for opt in help::OPTIONS {
+        if !opt.short.is_empty() {
+            names.push(format!("-{0}", opt.short));
+        }
+        if !opt.long.is_empty() {
+            names.push(format!("--{0}", opt.long));
+        }
+    }
+    names
+}
+
+// Returns options grouped by the kind of their values
+fn options_by_kind() -> Vec<(&'static str, Vec<&'static str>)> {
+    let mut kinds: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (opt, kind) in VALUE_KINDS {
+        match kinds.iter_mut().find(|(k, _)| k == kind) {
+            Some((_, l)) => l.push(opt),
+            None => kinds.push((kind, vec![opt])),
+        }
+    }
+    kinds
+}
+
+fn bash() -> String {
+    let mut s = String::from("_ttdl() {\n    local cur prev\n");
+    s += "    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n";
+    s += "    if [[ \"$prev\" == \"=\" ]]; then\n        prev=\"${COMP_WORDS[COMP_CWORD-2]}\"\n    fi\n";
+    s += "    case \"$prev\" in\n";
+    for (kind, list) in options_by_kind() {
+        let names: Vec<String> = list.iter().map(|o| format!("--{o}")).collect();
+        s += &format!(
+            "        {0})\n            COMPREPLY=($(compgen -W \"$(ttdl __complete {kind} 2>/dev/null)\" -- \"$cur\"))\n            return\n            ;;\n",
+            names.join("|")
+        );
+    }
+    s += "    esac\n    case \"$cur\" in\n";
+    for (prefix, kind) in PREFIX_KINDS {
+        s += &format!(
+            "        \\{prefix}*)\n            COMPREPLY=($(compgen -P '{prefix}' -W \"$(ttdl __complete {kind} 2>/dev/null)\" -- \"${{cur:1}}\"))\n            ;;\n"
+        );
+    }
+    s += &format!(
+        "        -*)\n            COMPREPLY=($(compgen -W \"{0}\" -- \"$cur\"))\n            ;;\n",
+        option_names().join(" ")
+    );
+    s += "        *)\n            if [[ $COMP_CWORD -eq 1 ]]; then\n";
+    s += &format!("                COMPREPLY=($(compgen -W \"{0}\" -- \"$cur\"))\n", help::command_names().join(" "));
+    s += "            else\n                COMPREPLY=($(compgen -W \"$(ttdl __complete ids 2>/dev/null)\" -- \"$cur\"))\n";
+    s += "            fi\n            ;;\n    esac\n}\n\ncomplete -F _ttdl ttdl\n";
+    s
+}
+
+fn zsh() -> String {
+    let mut s = String::from("#compdef ttdl\n\n_ttdl() {\n    local opt\n    local -a values\n");
+    s += "    if [[ $PREFIX == --*=* ]]; then\n        opt=${PREFIX%%=*}\n        compset -P '*='\n";
+    s += "    else\n        opt=${words[CURRENT-1]}\n    fi\n    case $opt in\n";
+    for (kind, list) in options_by_kind() {
+        let names: Vec<String> = list.iter().map(|o| format!("--{o}")).collect();
+        s += &format!(
+            "        {0})\n            values=(${{(f)\"$(ttdl __complete {kind} 2>/dev/null)\"}})\n            compadd -a values\n            return\n            ;;\n",
+            names.join("|")
+        );
+    }
+    s += "    esac\n    case $PREFIX in\n";
+    for (prefix, kind) in PREFIX_KINDS {
+        s += &format!(
+            "        \\{prefix}*)\n            compset -P '{prefix}'\n            values=(${{(f)\"$(ttdl __complete {kind} 2>/dev/null)\"}})\n            compadd -a values\n            ;;\n"
+        );
+    }
+    s += &format!("        -*)\n            compadd -- {0}\n            ;;\n", option_names().join(" "));
+    s += "        *)\n            if (( CURRENT == 2 )); then\n";
+    s += &format!("                compadd -- {0}\n", help::command_names().join(" "));
+    s += "            else\n                values=(${(f)\"$(ttdl __complete ids 2>/dev/null)\"})\n                compadd -a values\n";
+    s += "            fi\n            ;;\n    esac\n}\n\n";
+    // Works both from a file in `fpath` and when the script is sourced
+    s += "if [ \"$funcstack[1]\" = \"_ttdl\" ]; then\n    _ttdl \"$@\"\nelse\n    compdef _ttdl ttdl\nfi\n";
+    s
+}
+
+fn fish() -> String {
+    let mut s = String::from("function __ttdl_needs_command\n    test (count (commandline -opc)) -eq 1\nend\n\n");
+    s += "complete -c ttdl -f\n";
+    s += &format!("complete -c ttdl -n __ttdl_needs_command -a \"{0}\"\n", help::command_names().join(" "));
+    s += "complete -c ttdl -n 'not __ttdl_needs_command' -a '(ttdl __complete ids 2>/dev/null)'\n";
+    for (prefix, kind) in PREFIX_KINDS {
+        s += &format!(
+            "complete -c ttdl -n 'not __ttdl_needs_command' -a '(ttdl __complete {kind} 2>/dev/null | string replace -r \"^\" \"{prefix}\")'\n"
+        );
+    }
+    for opt in help::OPTIONS {
+        let mut line = String::from("complete -c ttdl");
+        if !opt.short.is_empty() {
+            line += &format!(" -s {0}", opt.short);
+        }
+        if !opt.long.is_empty() {
+            line += &format!(" -l {0}", opt.long);
+        }
+        match VALUE_KINDS.iter().find(|(o, _)| *o == opt.long) {
+            Some((_, kind)) => line += &format!(" -x -a '(ttdl __complete {kind} 2>/dev/null)'"),
+            None if opt.arg != Arg::No => line += " -r",
+            None => {}
+        }
+        s += &line;
+        s.push('\n');
+    }
+    s
+}
+
+// Returns a completion script for the shell, or None if the shell is not supported
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_kinds_test() {
+        for (name, _) in VALUE_KINDS {
+            let opt = help::OPTIONS.iter().find(|o| o.long == *name);
+            assert!(opt.is_some_and(|o| o.arg == Arg::Required), "{name}");
+        }
+    }
+}
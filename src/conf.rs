@@ -8,15 +8,20 @@ use std::{env, io};
 
 use anyhow::{Result, anyhow};
 use chrono::Local;
-use getopts::{Matches, Options};
+use getopts::Matches;
 use termcolor::{Color, ColorSpec};
 use unicode_width::UnicodeWidthStr;
 
 use crate::agenda::DAY_END;
 use crate::agenda::{MIN_IN_HOUR, MIN_SLOT_SIZE, SLOT_NONE};
+use crate::archive;
+use crate::expr;
 use crate::fmt;
+use crate::help;
+use crate::status;
 use crate::subj_clean::Hide;
 use crate::tml;
+use crate::wildcard;
 use todo_lib::{conv, human_date, terr, tfilter, todo, todotxt, tsort};
 
 const TODOFILE_VAR: &str = "TTDL_FILENAME";
@@ -53,26 +58,137 @@ pub enum RunMode {
     ListContexts,
     ListHashtags,
     Agenda,
+    Board,
+    Move,
+    Search,
+    Unarchive,
+    Transfer,
+    Sync,
+    Merge,
+    Completions,
+    Help,
+    Man,
+    Serve,
+    Lsp,
+    Status,
+    Pomodoro,
+    // Hidden command used by completion scripts
+    Complete,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Source {
     pub name: String,
     pub todo_file: PathBuf,
     pub done_file: PathBuf,
     pub default: bool,
+    // The settings below override global ones for todos of this task list
+    pub creation_date_auto: Option<bool>,
+    pub priority_on_done: Option<todotxt::CompletionMode>,
+    // Projects and contexts that are added to every new todo of this task list
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    // Color of the `src` column
+    pub color: Option<ColorSpec>,
+    pub readonly: bool,
 }
 impl Source {
-    fn from_tml(src: &tml::Source) -> Self {
-        Source {
+    fn from_tml(src: &tml::Source) -> Result<Self> {
+        let priority_on_done = match &src.priority_on_done {
+            None => None,
+            Some(l) => {
+                let mode = str_to_pri_mode(l);
+                if mode.is_none() {
+                    eprintln!("Invalid value '{l}' for sources.priority_on_done");
+                }
+                mode
+            }
+        };
+        let color = match &src.color {
+            None => None,
+            Some(c) => Some(color_from_str(c)?),
+        };
+        Ok(Source {
             name: if let Some(s) = &src.name { s.clone() } else { String::new() },
             todo_file: PathBuf::from(&src.path),
             done_file: if let Some(p) = &src.archive_path { PathBuf::from(p) } else { PathBuf::from("") },
             default: src.default.unwrap_or(false),
+            creation_date_auto: src.creation_date_auto,
+            priority_on_done,
+            projects: split_source_tags(&src.projects, '+'),
+            contexts: split_source_tags(&src.contexts, '@'),
+            color,
+            readonly: src.readonly.unwrap_or(false),
+        })
+    }
+}
+
+// Splits a comma-separated list of projects or contexts. Leading `+` or `@` is optional
+fn split_source_tags(list: &Option<String>, prefix: char) -> Vec<String> {
+    match list {
+        None => Vec::new(),
+        Some(l) => {
+            l.split(',').map(|s| s.trim().trim_start_matches(prefix).to_string()).filter(|s| !s.is_empty()).collect()
         }
     }
 }
 
+// Returns the name of the directory that contains the file
+fn parent_dir_name(path: &Path) -> String {
+    path.parent().and_then(|p| p.file_name()).map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+// Turns a source which path is a directory or a wildcard into a list of sources, a source per found file.
+// A directory with `todo.txt` is the same as the path to its `todo.txt`. Otherwise, every subdirectory with
+// `todo.txt` becomes a separate task list. Found task lists are named after directories they are in.
+// If the source has a name, it is used as a prefix: `name/dir`.
+fn expand_source(mut src: Source) -> Vec<Source> {
+    resolve_home_directory(&mut src.todo_file);
+    resolve_home_directory(&mut src.done_file);
+    if src.todo_file.is_dir() {
+        let todo_file = src.todo_file.join(TODO_FILE);
+        if todo_file.is_file() {
+            src.todo_file = todo_file;
+            return vec![src];
+        }
+        src.todo_file = src.todo_file.join("*").join(TODO_FILE);
+    } else if !wildcard::is_wildcard(&src.todo_file) {
+        return vec![src];
+    }
+
+    let files = wildcard::expand(&src.todo_file);
+    if files.is_empty() {
+        eprintln!("No task list found for '{0}'", src.todo_file.display());
+    }
+    // Every found task list gets its own archive in the same directory
+    let done_name = src.done_file.file_name().map(|n| n.to_os_string());
+    files
+        .into_iter()
+        .map(|todo_file| Source {
+            name: if src.name.is_empty() {
+                parent_dir_name(&todo_file)
+            } else {
+                format!("{0}/{1}", src.name, parent_dir_name(&todo_file))
+            },
+            done_file: match &done_name {
+                Some(n) => todo_file.with_file_name(n),
+                None => PathBuf::new(),
+            },
+            todo_file,
+            ..src.clone()
+        })
+        .collect()
+}
+
+// Returns the first name of a found task list that is used by another task list too. Configured
+// task lists may share a name to be merged in the output, but found ones must be distinguishable.
+fn duplicated_source_name<'a>(sources: &'a [Source], found: &[String]) -> Option<&'a str> {
+    sources
+        .iter()
+        .find(|src| found.contains(&src.name) && sources.iter().filter(|s| s.name == src.name).count() > 1)
+        .map(|src| src.name.as_str())
+}
+
 #[derive(Debug, Clone)]
 pub struct Conf {
     pub mode: RunMode,
@@ -81,6 +197,12 @@ pub struct Conf {
     pub dry: bool,
     pub wipe: bool,
     pub use_done: bool,
+    // Load both active and archived todos: archived ones go after all active ones
+    pub with_archive: bool,
+    // For unarchive: mark restored todos incomplete
+    pub reopen: bool,
+    // Index of the first archived todo when active and archived todos are merged
+    pub archived_from: Option<usize>,
     pub first_sunday: bool,
     pub strict_mode: bool,
     pub show_hidden: bool,
@@ -90,6 +212,7 @@ pub struct Conf {
     pub keep_tags: bool,
     editor_path: Option<String>,
     pub use_editor: bool,
+    pub watch: bool,
     pub max_items: Option<usize>,
     pub task_lists: Vec<Source>,
 
@@ -97,18 +220,58 @@ pub struct Conf {
     pub auto_show_columns: bool,
     pub always_hide_columns: Vec<String>,
     pub priority_on_done: todotxt::CompletionMode,
+    // How `clean` splits completed todos between archive files
+    pub archive_rotation: archive::Rotation,
+    // Move todos to the archive right after they are completed
+    pub archive_on_done: bool,
+    // Archive todos completed more than this number of days ago on every run. Zero disables it
+    pub auto_clean_days: u32,
+    // Commit changed task lists to their git repositories
+    pub git_commit: bool,
+    // The remote used by `sync`. None means `origin`
+    pub git_remote: Option<String>,
+    // Ask before removing or changing todos
+    pub confirm: bool,
+    // `edit` and `done` ask only if they change more todos than this number
+    pub confirm_count: usize,
     pub add_completion_date_always: bool,
     pub list_sources: bool,
+    // BASE, OURS, and THEIRS files of the command `merge`
+    pub merge_files: Vec<PathBuf>,
+    // The shell of the command `completions`, or the kind of candidates of `__complete`
+    pub completion: String,
+    // The address the command `serve` listens on
+    pub listen: String,
+    // The template of the command `status`, and whether it prints JSON for waybar
+    pub status_format: String,
+    pub status_json: bool,
+    // The lengths of a pomodoro and a break after it in seconds, and the command to run when they end
+    pub pomodoro_length: i64,
+    pub pomodoro_break: i64,
+    pub pomodoro_notify: Option<String>,
 
     pub todo: todo::Conf,
     pub fmt: fmt::Conf,
     pub flt: tfilter::Conf,
+    // Compiled filter expression from the option `where`
+    pub where_expr: Option<expr::Expr>,
     pub sort: tsort::Conf,
     pub postpone_threshold: bool,
     pub use_regex: bool,
     pub source: String,
+    // For mv: the name of the task list to move todos to
+    pub move_to: String,
+    // For mv: move also archived todos that match the filter
+    pub move_archived: bool,
 
     pub calendar: Option<human_date::CalendarRange>,
+    // For calendar: what date fields to mark. Default is `due`.
+    // Overrided by field list passed in command-line option `calendar`
+    pub calendar_fields: Vec<String>,
+    // For board: the field which values are used as board columns. Default is `status` tag
+    pub board_field: String,
+    // For board: predefined list of columns. They are displayed even if they are empty
+    pub board_columns: Vec<String>,
     // For agenda: what fields to check.
     // Format: FIELD1[,FIELD2]
     // Overrided by command-line option that sets `on` field. Though if the command-line option
@@ -141,6 +304,9 @@ impl Default for Conf {
             verbose: false,
             wipe: false,
             use_done: false,
+            with_archive: false,
+            reopen: false,
+            archived_from: None,
             first_sunday: true,
             strict_mode: false,
             show_hidden: false,
@@ -150,6 +316,7 @@ impl Default for Conf {
             keep_tags: false,
             editor_path: None,
             use_editor: false,
+            watch: false,
             max_items: None,
             on: None,
             time_range: None,
@@ -164,17 +331,38 @@ impl Default for Conf {
             auto_show_columns: false,
             always_hide_columns: Vec::new(),
             priority_on_done: todotxt::CompletionMode::JustMark,
+            archive_rotation: archive::Rotation::None,
+            archive_on_done: false,
+            auto_clean_days: 0,
+            git_commit: false,
+            git_remote: None,
+            confirm: false,
+            confirm_count: 1,
             add_completion_date_always: false,
             list_sources: false,
+            merge_files: Vec::new(),
+            completion: String::new(),
+            listen: "127.0.0.1:8080".to_string(),
+            status_format: status::DEFAULT_FORMAT.to_string(),
+            status_json: false,
+            pomodoro_length: 25 * 60,
+            pomodoro_break: 5 * 60,
+            pomodoro_notify: None,
 
             fmt: Default::default(),
             todo: Default::default(),
             flt: Default::default(),
+            where_expr: None,
             sort: Default::default(),
             calendar: None,
+            calendar_fields: vec!["due".to_string()],
+            board_field: "status".to_string(),
+            board_columns: Vec::new(),
             postpone_threshold: false,
             use_regex: false,
             source: String::new(),
+            move_to: String::new(),
+            move_archived: false,
         }
     }
 }
@@ -231,6 +419,32 @@ impl Conf {
     pub fn todo_list_name_length(&self, idx: usize) -> usize {
         if idx < self.task_lists.len() { self.task_lists[idx].name.width() } else { 0 }
     }
+    // Returns what to do with priority when a todo is completed: a task list may override the global setting
+    pub fn priority_on_done(&self, task: &todotxt::Task) -> todotxt::CompletionMode {
+        task.source
+            .as_ref()
+            .and_then(|src| self.task_lists.get(src.id))
+            .and_then(|tl| tl.priority_on_done)
+            .unwrap_or(self.priority_on_done)
+    }
+    pub fn is_readonly(&self, task: &todotxt::Task) -> bool {
+        match &task.source {
+            Some(src) => self.task_lists.get(src.id).is_some_and(|tl| tl.readonly),
+            None => self.task_lists.len() == 1 && self.task_lists[0].readonly,
+        }
+    }
+    // Returns an error if any of the task lists is read-only. Commands skip todos of read-only
+    // task lists, so changing such list is a bug that must not end up in the file
+    pub fn check_writable<'a>(&self, list_ids: impl IntoIterator<Item = &'a usize>) -> Result<(), String> {
+        for id in list_ids {
+            let tl = &self.task_lists[*id];
+            if tl.readonly {
+                let name = if tl.name.is_empty() { format!("{0}", id + 1) } else { tl.name.clone() };
+                return Err(format!("Task list '{name}' is read-only"));
+            }
+        }
+        Ok(())
+    }
     pub fn default_todo_file(&self) -> &Path {
         if let Some(idx) = self.selected_task_list() {
             return &self.task_lists[idx].todo_file;
@@ -259,97 +473,6 @@ impl Conf {
     }
 }
 
-fn print_usage(program: &str, opts: &Options) {
-    let brief = format!("Usage: {program} command [ID or ID range] [subject] [filter] [new values] [extra options]");
-    print!("{}", opts.usage(&brief));
-
-    println!("\n\n[ID or ID range] - ID is the order number of a todo starting from 1. The range is inclusive. It is OK to use non-existing IDs - all invalid IDs are skipped while processing the command
-");
-
-    let filter = r#"Filter options include:
-    --all | -a, --complete | -A, --rec, --due, --pri, --regex, --context, --project, --tag | -e, --threshold, --hidden, --hashtag, --filter
-    +project - select todos which are related to project "project"; if more than one project name is defined in command line, they are combined with OR;
-    @context - select todos which have context "project"; if more than one context is set, they are combined with OR;
-    "#;
-
-    let newones = r#"Modifying options are:
-    --set-pri, --set-due, --set-time, --set-rec, --set-proj, --set-ctx, --del-proj, --del-ctx, --repl-proj, --repl-ctx, --set-threshold, --set-tag, --set-hashtag, --del-tag, --del-hashtag, --repl-hashtag, --update-threshold
-    "#;
-
-    let extras = r#"Extra options:
-    --stdin, --dry-run, --sort | -s, --sort-rev, --wrap, --short, --width, --local, --no-colors, --syntax, --no-syntax, --clean-subject, --auto-hide-cols, --auto-show-cols, --always-hide-cols
-    --interactive | -i, --init, --init-local, --group, --no-headers | -H, --hide-fields, --date-format
-    --hide-all-day, --no-hide-all-day --time, --slot, --on
-    "#;
-    let commands = r#"Available commands:
-    list | l - list todos
-        `ttdl l -s=proj,pri` - show all incomplete todos sorted by their project and by priority inside each project
-        `ttdl l "car*"` - list all todos which have substring `car*` in their subject, project or context
-        `ttdl l "car*" -e` - list all todos which have subject, project or context matched regular expression `car*`
-        `ttdl l "car"` - list all todos which have substring `car` in their subject, project or context
-        `ttdl l --max 10` - show only the first 10 incomplete todos
-        `ttdl l "car*" --max 5` - list at most 5 todos which have substring `car*` in their subject, project or context
-        `ttdl l -s=proj,pri --max 5` - show first 5 incomplete todos sorted by their project and by priority inside each project
-        `ttdl l --pri=a` - show all incomplete todos with the highest priority A
-        `ttdl l --pri=b+` - show all incomplete todos with priority B and higher (only A and B in this case)
-        `ttdl l +car +train` - show all incomplete todos which related either to `car` or to `train` projects
-        `ttdl l +my* @*tax` - show all incomplete todos that have a project tag starts with `my` and a context ends with `tax`
-        `ttdl l --due=tomorrow -a` - show all todos that are due tomorrow
-        `ttdl l --due=soon` - show all incomplete todos which are due are due in less a few days, including overdue ones (the range is configurable and default value is 7 days)
-        `ttdl l --due=overdue` - show all incomplete overdue todos
-        `ttdl l --due=today -a` - show all todos that are due today
-        `ttdl l --due=today..tomorrow -a` - show all todos that are overdue between today and tomorrow(inclusive)
-        `ttdl l --due=today:tomorrow -a` - the same as above
-        `ttdl l --completed=-1w..today -a` - show all todos that were done the last week(literally: from a week ago through today)
-        `ttdl l --created=-3d..` - show active todos that are created 3 days ago or earlier(3 days old and younger)
-        `ttdl l --due=..2d..` - show active todos that are either overdue or their due date within 2 days from the current date
-        `ttdl l -a +myproj @ui @rest` - show both incomplete and done todos related to project 'myproj' which contains either 'ui' or 'rest' context
-        `ttdl l --calendar=m` - show calendar for this month and mark dates that have one or more due todos
-        `ttdl l --calendar=2w` - show calendar for this and next week and mark dates that have one or more due todos
-        `ttdl l --calendar=+1m` - show calendar for 30 days(one month) starting with today
-        `ttdl l --calendar=+-10d` - show calendar for 10 days in the past(one month) ending with today
-    add | a - add a new todo
-        `ttdl a "send tax declaration +personal @finance @tax due:2018-04-01 rec:1y"` - add a new recurrent todo(yearly todo) with a due date first of April every year
-        `ttdl a "(A) send tax return docs due:2018-04-01" - add a new todo with the highest priority `A`
-    done | d - mark regular incomplete todos completed, pushes due date for recurrent todos to their next date
-        `ttdl d 2-5` - mark todos with IDs from 2 through 5 done
-    undone - remove finish date and completion mark from completed todos
-    clean | c | archive | arc - move all completed todos to `done.txt`. If option `--wipe` is set then completed todos are removed instead of moving
-    remove | rm - delete selected todos. Warning: by default completed todos are not selected, so be careful
-        `ttdl rm 2-5` - delete incomplete todos with IDs from 2 thorough 5
-        `ttdl rm 2-5 -a` - delete both done and incomplete todos with IDs from 2 through 5
-        `ttdl rm 2-5 -A` - delete all done todos with IDs from 2 through 5. The command does the same as `ttdl clean 2-5 --wipe` does
-    edit | e - modifies selected todos. Warninig: if you try to change a subject of a few todos, only the first todo would be changed. It is by design. Date-like tags `due` and `threshold` accept simple expressions
-        `ttdl e 2-5 "new subject"` - only the first incomplete todo with ID between 2 and 5 changes its subject
-        `ttdl e +proj --repl-ctx=bug1010@bug1020` - replace context `bug1010` with `bug1020` for all incomplete todos that related to project `proj`
-        `ttdl e @customer_acme --set-due=2018-12-31` - set due date 2018-12-31 for all incomplete todos that has `customer_acme` context
-        `ttdl e @customer_acme --set-due=none` - remove due date 2018-12-31 for all incomplete todos that has `customer_acme` context
-        `ttdl e --pri=none --set-pri=z` - set the lowest priority for all incomplete todos which do not have a priority set`
-        `ttdl e @bug1000 --set-pri=+` - increase priority for all incomplete todos which have context `bug1000`, todos which did not have priority set get the lowest priority `z`
-        `ttdl e 2 --set-due=t+1w` - set the due date a week later than the task threshold date
-        `ttdl e 2 --set-due=due+2d` - push the due date by 2 days
-        `ttdl e 2 --set-due=limit+1d` - takes task's tag `limit` as a date, adds 1 day and sets the result to the due date
-        `ttdl e 2 --set-due=t+1w+3d` - push the due date by a week and a half or more accurate by 10 days
-        `ttdl e 2-5 -i` - open an external editor of you choice the the incomplete todos with ID between 2 and 5 for interactive editing. After saving the changes and closing the editor, TTDL updates the task list
-    append | app - adds a text to the end of todos
-    prepend | prep - inserts a text at the beginning of todos
-    listprojects [FILTER] | listproj | lp - list all projects
-        `ttdl lp ` - show alphabetically sorted list of all projects
-        `ttdl lp +un*` - show projects starting with 'un'
-    listcontexts [FILTER] | listcon | lc - list all contexts
-        `ttdl lc ` - show alphabetically sorted list of all contexts
-        `ttdl lc @phon*` - show contexts starting with 'phon'
-    start TODO_ID - activates todo's timer
-    stop TODO_ID - stops the timer for a todo and updates time spent for it
-    stats [--short] - shows todo list summary
-        `ttdl stats --short` - displays only the number of total, active, done, overdue, and recurrent todos
-        `ttdl stats` - detailed view with additional grouping by project and displaying total time spent on each group
-    agenda | ag - show a list of tasks for a single day as an agenda (i.e, dispalys a timetable and tasks in each time slot)
-        `ttdl agenda --hide-all-day` - show agenda without footer with tasks that do not have any time slot defined
-    "#;
-    println!("{commands}\n\n{filter}\n\n{newones}\n\n{extras}");
-}
-
 fn str_to_mode(s: &str) -> RunMode {
     match s {
         "l" | "list" | "ls" => RunMode::List,
@@ -369,6 +492,21 @@ fn str_to_mode(s: &str) -> RunMode {
         "lc" | "listcon" | "listcontexts" => RunMode::ListContexts,
         "lh" | "listhash" | "listhashtags" => RunMode::ListHashtags,
         "ag" | "agenda" => RunMode::Agenda,
+        "board" | "kanban" => RunMode::Board,
+        "move" => RunMode::Move,
+        "search" | "find" => RunMode::Search,
+        "unarchive" | "unarc" => RunMode::Unarchive,
+        "mv" | "transfer" => RunMode::Transfer,
+        "sync" => RunMode::Sync,
+        "merge" => RunMode::Merge,
+        "completions" => RunMode::Completions,
+        "help" => RunMode::Help,
+        "man" => RunMode::Man,
+        "serve" => RunMode::Serve,
+        "lsp" => RunMode::Lsp,
+        "status" => RunMode::Status,
+        "pomodoro" => RunMode::Pomodoro,
+        "__complete" => RunMode::Complete,
         _ => RunMode::None,
     }
 }
@@ -497,6 +635,19 @@ fn parse_filter_completed(val: &str, c: &mut tfilter::Conf, soon_days: u8) -> Re
     Ok(())
 }
 
+// Selects todos completed at least `val` ago. E.g., `30d` means 30 or more days ago.
+fn parse_filter_older_than(val: &str, c: &mut tfilter::Conf, soon_days: u8) -> Result<(), terr::TodoError> {
+    let age = val.trim_start_matches(['-', '+']);
+    if age.is_empty() || !age.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(terr::TodoError::InvalidValue(val.to_string(), "age".to_string()));
+    }
+    c.finished = Some(parse_filter_date_range(&format!("..-{age}"), soon_days)?);
+    if c.all == tfilter::TodoStatus::Active {
+        c.all = tfilter::TodoStatus::Done;
+    }
+    Ok(())
+}
+
 fn parse_filter_threshold(val: &str, c: &mut tfilter::Conf, soon_days: u8) -> Result<(), terr::TodoError> {
     let rng = parse_filter_date_range(val, soon_days)?;
     c.thr = Some(rng);
@@ -551,6 +702,9 @@ fn parse_filter(matches: &Matches, c: &mut tfilter::Conf, soon_days: u8) -> Resu
         };
         parse_filter_completed(&dstr, c, soon_days)?;
     }
+    if let Some(age) = matches.opt_str("older-than") {
+        parse_filter_older_than(&age.to_lowercase(), c, soon_days)?;
+    }
     if matches.opt_present("threshold") {
         let dstr = match matches.opt_str("threshold") {
             None => String::new(),
@@ -583,6 +737,14 @@ fn parse_filter(matches: &Matches, c: &mut tfilter::Conf, soon_days: u8) -> Resu
     Ok(())
 }
 
+fn split_calendar_fields(list: &str) -> Vec<String> {
+    list.split(',').map(|f| f.trim().to_lowercase()).filter(|f| !f.is_empty()).collect()
+}
+
+fn split_board_columns(list: &str) -> Vec<String> {
+    list.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect()
+}
+
 fn comma_list_to_vec(list: &str) -> (Vec<String>, Vec<String>) {
     let mut incl = Vec::new();
     let mut excl = Vec::new();
@@ -601,7 +763,7 @@ fn comma_list_to_vec(list: &str) -> (Vec<String>, Vec<String>) {
     (incl, excl)
 }
 
-fn validate_time_range(s: &str) -> Result<(), terr::TodoError> {
+pub fn validate_time_range(s: &str) -> Result<(), terr::TodoError> {
     if s.is_empty() || s == "-" || s == "none" {
         return Ok(());
     }
@@ -997,8 +1159,15 @@ fn parse_fmt(matches: &Matches, c: &mut fmt::Conf) {
     }
 
     if let Some(s) = matches.opt_str("group") {
-        c.group = Some(s.clone());
+        c.group = s.split(',').map(|g| g.trim().to_string()).filter(|g| !g.is_empty()).collect();
+    }
+    if let Some(s) = matches.opt_str("group-sort")
+        && s != "name"
+    {
+        c.group_sort = Some(s);
     }
+    c.group_sort_rev = matches.opt_present("group-sort-rev");
+    c.group_totals = matches.opt_present("group-totals");
     c.hide_headers = matches.opt_present("no-headers");
     if let Some(s) = matches.opt_str("date-format") {
         c.date_format = s.clone();
@@ -1009,6 +1178,23 @@ fn detect_filenames(matches: &Matches, conf: &mut Conf) {
     let mut passed_in_cli: bool = false;
     let mut done_passed_in_cli: bool = false;
 
+    let sources = std::mem::take(&mut conf.task_lists);
+    let mut found = Vec::new();
+    for src in sources {
+        let name = src.name.clone();
+        for src in expand_source(src) {
+            // Task lists found in a directory or by a wildcard get new names
+            if src.name != name {
+                found.push(src.name.clone());
+            }
+            conf.task_lists.push(src);
+        }
+    }
+    if let Some(name) = duplicated_source_name(&conf.task_lists, &found) {
+        eprintln!("More than one task list is named '{name}'. Set different names for sources in the config");
+        exit(1);
+    }
+
     if let Ok(val) = env::var(TODOFILE_VAR)
         && !val.is_empty()
     {
@@ -1062,6 +1248,7 @@ fn detect_filenames(matches: &Matches, conf: &mut Conf) {
             todo_file: conf.todo_file.clone(),
             done_file: conf.done_file.clone(),
             default: true,
+            ..Default::default()
         };
         conf.task_lists.push(src);
     } else if conf.task_lists.len() == 1 {
@@ -1080,6 +1267,7 @@ fn detect_filenames(matches: &Matches, conf: &mut Conf) {
             todo_file: conf.todo_file.clone(),
             done_file: conf.done_file.clone(),
             default: true,
+            ..Default::default()
         };
         conf.task_lists.push(src);
     }
@@ -1253,6 +1441,28 @@ fn validate_agenda_config(conf: &Conf) -> Result<()> {
     Ok(())
 }
 
+fn update_calendar_from_config(tc: &tml::Conf, conf: &mut Conf) {
+    if let Some(cal_tml) = &tc.calendar
+        && let Some(fields) = &cal_tml.fields
+        && !fields.is_empty()
+    {
+        conf.calendar_fields = split_calendar_fields(fields);
+    }
+}
+
+fn update_board_from_config(tc: &tml::Conf, conf: &mut Conf) {
+    if let Some(board_tml) = &tc.board {
+        if let Some(field) = &board_tml.field
+            && !field.is_empty()
+        {
+            conf.board_field = field.to_lowercase();
+        }
+        if let Some(columns) = &board_tml.columns {
+            conf.board_columns = split_board_columns(columns);
+        }
+    }
+}
+
 fn update_agenda_from_config(tc: &tml::Conf, conf: &mut Conf) -> Result<()> {
     if let Some(agenda_tml) = &tc.agenda {
         if let Some(hide) = agenda_tml.hide_all_day {
@@ -1350,9 +1560,40 @@ fn update_global_from_conf(tc: &tml::Conf, conf: &mut Conf) {
             None => eprintln!("Invalid value '{l}' for global.priority_on_done"),
         }
     }
+    if let Some(l) = &tc.global.archive_rotation {
+        match archive::str_to_rotation(l) {
+            Some(r) => conf.archive_rotation = r,
+            None => eprintln!("Invalid value '{l}' for global.archive_rotation"),
+        }
+    }
+    if let Some(aod) = &tc.global.archive_on_done {
+        conf.archive_on_done = *aod;
+    }
+    if let Some(days) = &tc.global.auto_clean_days {
+        conf.auto_clean_days = *days;
+    }
+    if let Some(gc) = &tc.global.git_commit {
+        conf.git_commit = *gc;
+    }
+    if let Some(r) = &tc.global.git_remote
+        && !r.is_empty()
+    {
+        conf.git_remote = Some(r.clone());
+    }
+    if let Some(c) = &tc.global.confirm {
+        conf.confirm = *c;
+    }
+    if let Some(cnt) = &tc.global.confirm_count {
+        conf.confirm_count = *cnt;
+    }
     if let Some(acda) = &tc.global.add_completion_date_always {
         conf.add_completion_date_always = *acda;
     }
+    if let Some(cmd) = &tc.global.pomodoro_notify
+        && !cmd.is_empty()
+    {
+        conf.pomodoro_notify = Some(cmd.clone());
+    }
     if let Some(p) = &tc.global.editor {
         conf.editor_path = Some(p.clone());
     }
@@ -1436,10 +1677,12 @@ fn load_from_config(conf: &mut Conf, conf_path: Option<PathBuf>) -> Result<()> {
     update_markdown_from_config(&info_toml, conf)?;
     update_fields_from_config(&info_toml, conf)?;
     update_agenda_from_config(&info_toml, conf)?;
+    update_calendar_from_config(&info_toml, conf);
+    update_board_from_config(&info_toml, conf);
 
     if let Some(lists) = &info_toml.sources {
         for s in lists {
-            conf.task_lists.push(Source::from_tml(s));
+            conf.task_lists.push(Source::from_tml(s)?);
         }
     }
 
@@ -1465,238 +1708,13 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     let args = preprocess_args(args);
     let program = args[0].clone();
     let mut conf = Conf::new();
-
-    // Free short options: BCDEFGIJKLMNOPQRSTUVWXYZbdgjlmnopquxyz"
-
-    let mut opts = Options::new();
-    opts.optflag("h", "help", "Show this help");
-    opts.optflag("a", "all", "Select all todos including completed ones");
-    opts.optflag("A", "only-completed", "Select only completed todos");
-    opts.optflag("t", "active", "Only active records");
-    opts.optflag("", "dry-run", "Dry run: do not change todo list, only show which todos would be changed");
-    opts.optflag("v", "verbose", "Display extra information (used file names etc)");
-    opts.optflag(
-        "e",
-        "regex",
-        "Treat the search string as regular expression. By default simple case-insensitive substring search is done",
-    );
-    opts.optflag("", "wipe", "'Clean' command deletes todos instead of moving them to 'done.txt'");
-    opts.optflagopt(
-        "s",
-        "sort",
-        "Sort todos by the list of fields(if the list is empty todos are sorted by their priority)",
-        "FIELD1,FIELD2",
-    );
-    opts.optflag("", "sort-rev", "Reverse todo list after sorting. It works only if the option 'sort' is set");
-    opts.optopt("", "group", "a field name that is used to group the list of tasks", "FIELD");
-    opts.optopt("", "rec", "Select only recurrent(any) or non-recurrent(none) todos", "any | none");
-    opts.optopt("", "due", "Select records without due date(none), with any due date(any), overdue todos(overdue), today's todos(today), tomorrow's ones(tomorrow), or which are due in a few days(soon)", "any | none | today| tomorrow | yesterday | soon | 'range'");
-    opts.optopt(
-        "",
-        "created",
-        "Select records without creation date(none), with any creation date(any), created within a date range",
-        "any | none | today| tomorrow | yesterday | soon | 'range'",
-    );
-    opts.optopt(
-        "",
-        "completed",
-        "Select records without completion date(none), with any completion date(any), completed within a date range",
-        "any | none | today| tomorrow | yesterday | soon | 'range'",
-    );
-    opts.optopt(
-        "",
-        "threshold",
-        "Select records without threshold date(none), with any threshold date(any)",
-        "any | none | today| tomorrow | yesterday | soon | 'range'",
-    );
-    opts.optopt(
-        "",
-        "project",
-        "Comma-separated list of projects. Select records that have any of them. Special values: 'none' - select records with no project, and 'any' - select records that have at least one project. Basic pattern matching supported: '*ab' - project ends with 'ab', 'ab*' - project starts with 'ab', '*ab*' - project contains 'ab'",
-        "PROJECT1,PROJECT2",
-    );
-    opts.optopt(
-        "",
-        "context",
-        "Comma-separated list of contexts. Select records that have any of them. Special values: 'none' - select records with no context, and 'any' - select records that have at least one context. Basic pattern matching supported: '*ab' - context ends with 'ab', 'ab*' - context starts with 'ab', '*ab*' - context contains 'ab'",
-        "CONTEXT1,CONTEXT2",
-    );
-    opts.optopt(
-        "",
-        "tag",
-        "Comma-separated list of tags. Select records that have any of them. Special values: 'none' - select records with no tag, and 'any' - select records that have at least one tag. Basic pattern matching supported: '*ab' - tag ends with 'ab', 'ab*' - tag starts with 'ab', '*ab*' - tag contains 'ab'",
-        "TAG1,TAG2",
-    );
-    opts.optopt("", "pri", "Select todos without priority(none), with any priority(any), with a given priority, with a priority equal to or higher/lower than the given priority", "none | any | a | b+ | c-");
-    opts.optopt("", "hashtag", "Select only todos with any of hashtags", "HASHTAG1,HASHTAG2 | any | none");
-    opts.optopt(
-        "",
-        "set-pri",
-        "Change priority for selected todos: remove priority, set exact priority, increase or decrease it",
-        "none | A-Z | + | -",
-    );
-    opts.optopt(
-        "",
-        "set-rec",
-        "Change recurrence for selected todos: remove recurrence, or set a new one",
-        "none | 1m | 15d",
-    );
-    opts.optopt(
-        "",
-        "set-due",
-        "Change due date for selected todos: remove due date or set a new one",
-        "none | YYYY-MM-DD",
-    );
-    opts.optopt(
-        "",
-        "set-time",
-        "Change time tag selected todos: remove time tag or set a new value for it",
-        "none | TIME | TIME_RANGE",
-    );
-    opts.optopt(
-        "",
-        "set-threshold",
-        "Change threshold date for selected todos: remove threshold date or set a new one",
-        "none | YYYY-MM-DD",
-    );
-    opts.optopt("", "set-proj", "Add projects to selected todos", "PROJ1,PROJ2");
-    opts.optopt("", "set-ctx", "Add contexts to selected todos", "CTX1,CTX2");
-    opts.optopt("", "set-tag", "Add tags to selected todos", "TAG1:VALUE1,TAG2:VALUE2");
-    opts.optopt("", "set-hashtag", "Add hashtags to selected todos", "HASHTAG1,HASHTAG2");
-    opts.optopt("", "del-proj", "Remove projects from selected todos", "PROJ");
-    opts.optopt("", "del-ctx", "Remove contexts from selected todos", "CTX");
-    opts.optopt("", "del-tag", "Remove tags from selected todos", "TAG1,TAG2");
-    opts.optopt("", "del-hashtag", "Remove hashtags from selected todos", "HASHTAG1,HASHTAG2");
-    opts.optopt(
-        "",
-        "repl-proj",
-        "Replace projects for selected todos: a list of comma separated pairs of old and new project values. Old value and new value are separated with '+'",
-        "PROJ1+PROJECT1,PROJ2+PROJECT2",
-    );
-    opts.optopt(
-        "",
-        "repl-ctx",
-        "Replace contexts for selected todos: a list of comma separated pairs of old and new context values. Old value and new value are separated with '@'",
-        "CT1@CTX,CT2@ANOTHER",
-    );
-    opts.optopt("", "repl-hashtag", "Replace hashtags for selected todos", "HASHTAG1:NEW1,HASHTAG2:NEW2");
-    opts.optflag("", "short", "Show only ID, priority and subject for todos");
-    opts.optflag("", "wrap", "Word wrap a long subject within subject column");
-    opts.optopt("w", "width", "Set terminal width. The application detects terminal width automatically but it is possible to limit the output width manually", "WIDTH");
-    opts.optflagopt(
-        "",
-        "human",
-        "Show relative date(for due and threshold dates) instead of default YYYY-MM-DD. Examples: 'today', '4d overdue', or 'in 2m'",
-        "empty value or FIELD1,FIELD2",
-    );
-    opts.optflag("", "compact", "Show relative date in compact mode: without 'in' or 'overdue', overdue and future dates are distinguished by their colors");
-    opts.optopt(
-        "",
-        "fields",
-        "Set custom list of fields to display(ID is always visible). The list defines visibility of fields but not their order. The order cannot be changed",
-        "FIELD2,FIELD1",
-    );
-    opts.optflag("", "local", "Use todo from the current working directory. It is the default mode. But if you set environment variable or modified config, the option can be used to override those values and use todo.txt from current working directory");
-    opts.optflag("", "no-colors", "Disable all colors");
-    opts.optflag(
-        "",
-        "done",
-        "Use file of completed todos - done.txt. In this mode the only available command is 'list'",
-    );
-    opts.optflag("", "version", "Show TTDL version");
-    opts.optopt("c", "config", "Path to configuration file", "CONF FILE PATH");
-    opts.optopt(
-        "",
-        "todo-file",
-        "Path to file with todos (if it is directory 'todo.txt' is added automatically) ",
-        "TODO FILE PATH",
-    );
-    opts.optopt("", "done-file", "Path to file with archived todos (if it is directory 'done.txt' is added automatically, if it contains only file name then the directory is the same as for todo.txt) ", "DONE FILE PATH");
-    opts.optflag("", "strict", "Enable strict mode");
-    opts.optflag("", "hidden", "Include hidden tasks");
-    opts.optopt(
-        "",
-        "calendar",
-        "Display a calendar with dates highlighted if any todo is due on that date(foreground color). Today is highlighted with background color, Default values for `NUMBER` is `1` and for `TYPE` is `d`(days). Valid values for type are `d`(days), `w`(weeks), and `m`(months). Prepending plus sign shows the selected interval starting from today, not from Monday or first day of the month",
-        "[+][NUMBER][TYPE]",
-    );
-    opts.optflag("", "syntax", "Enable keyword highlights when printing subject");
-    opts.optflag("", "no-syntax", "Disable keyword highlights when printing subject");
-    opts.optflag("", "markdown", "Enable Markdown formatting in subject text");
-    opts.optflag("", "no-markdown", "Disable Markdown formatting in subject text");
-    opts.optflag("", "keep-empty", "do not remove empty todos when cleaning up(archiving) the list");
-    opts.optopt(
-        "",
-        "clean-subject",
-        "hide the given items in a subject column when printing a task list. Items are hidden only if their corresponding columns are visible",
-        "no|none|nothing|tags|all|yes. 'yes' is an alias for all, 'no|none|nothing' are the synonyms",
-    );
-    opts.optflag("", "auto-hide-cols", "Hide columns that do not have values");
-    opts.optflag("", "auto-show-cols", "Show all columns that have at least one value");
-    opts.optopt(
-        "",
-        "always-hide-cols",
-        "Comma-separated list of tags that TTDL never show in a separate column. E.g, 'prj,due' or 'pri,created,customtag'",
-        "FIELD1,FIELD2",
-    );
-    opts.optopt(
-        "",
-        "priority-on-done",
-        "what to do with priority on task completion: keep - no special action(default behavior), move - place priority after completion date, tag - convert priority to a tag 'pri:', erase - remove priority. Note that in all modes, except `erase`, the operation is reversible and on task uncompleting, the task gets its priority back",
-        "VALUE",
-    );
-    opts.optflag(
-        "",
-        "add-completion-date-always",
-        "When task is finished, always add completion date, regardless of whether or not creation date is present",
-    );
-    opts.optflag("k", "keep-tags", "in edit mode a new subject replaces regular text of the todo, everything else(tags, priority etc) is taken from the old and appended to the new subject. A convenient way to replace just text and keep all the tags without typing the tags again");
-    opts.optflag("i", "interactive", "Open an external edit to modify all filtered tasks. If the task list is modified inside an editor, the old tasks will be removed and new ones will be added to the end of the task list. If you do not change anything or save an empty file, the edit operation will be canceled. To set editor, change config.global.editor option or set EDITOR environment variable.");
-    opts.optflag("", "init", "create a default configuration file in user's configuration directory if the configuration file does not exist yet");
-    opts.optflag("", "init-local", "create a default configuration file in the current working directory if the configuration file does not exist yet");
-    opts.optflag("", "stdin", "Read new or replacement task content from standard input");
-    opts.optflag("H", "no-headers", "Do not show headers and footers");
-
-    opts.optopt("", "max", "Set maximum number of todos to display", "NUMBER");
-    opts.optopt(
-        "",
-        "filter-tag",
-        "Custom filter by user-defined tag values",
-        "TAG1=RANGE1;TAG2=RANGE2. Deprecated, use 'filter' instead",
-    );
-    opts.optopt("f", "filter", "Custom filter by user-defined tag values", "TAG1=RANGE1;TAG2=RANGE2");
-    opts.optflag("", "update-threshold", "Update threshold in addition to changing due date when a task is postponed");
-    opts.optopt(
-        "",
-        "hide-fields",
-        "Comma-separated list of fields to hide in both columns and subject",
-        "FIELD1,FIELD2",
-    );
-    opts.optopt(
-        "",
-        "date-format",
-        "Display format for dates. Format string includes %Y, %M, %D and more",
-        "default|human|short|FORMAT-STRING",
-    );
-    opts.optopt("", "on", "Set the date, default is today, for an agenda to show and optional field to use for checking date (default is 'due')", "FIELD1[,FIELD2][=][DATE]");
-    opts.optopt("", "time", "Show an agenda for this time interval. Time is defined without a separator between hours and minutes, e.g, '930' means '9:30'", "[START][-][END]");
-    opts.optopt("", "slot", "A slot size for an agenda in minutes. Default is 30 minutes", "[SLOT_SIZE]");
-    opts.optflag("", "hide-all-day", "Do not show section 'All day' in agenda");
-    opts.optflag("", "no-hide-all-day", "Show section 'All day' in agenda if it is off in the configuration file");
-    opts.optopt("r", "resolution", "A resolution is appended to every task that was completed", "[MESSAGE]");
-    opts.optopt(
-        "",
-        "src",
-        "Select a source task list for adding a task or for filtering (used only in multi-file mode",
-        "[MESSAGE]",
-    );
-    opts.optflag("", "list-sources", "Show info about all task lists");
+    let opts = help::options();
 
     let matches: Matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(e) => {
             println!("{e}");
-            print_usage(&program, &opts);
+            help::print_usage(&program, &opts);
             exit(1);
         }
     };
@@ -1707,9 +1725,26 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
         exit(0);
     }
     if matches.opt_present("h") {
-        print_usage(&program, &opts);
+        help::print_usage(&program, &opts);
         exit(0);
     }
+    match matches.free.first().map(|s| str_to_mode(s)) {
+        Some(RunMode::Help) => {
+            match matches.free.get(1) {
+                None => help::print_usage(&program, &opts),
+                Some(name) => match help::find_command(name) {
+                    Some(cmd) => help::print_command_usage(&program, cmd),
+                    None => return Err(anyhow!("Unknown command '{name}'")),
+                },
+            }
+            exit(0);
+        }
+        Some(RunMode::Man) => {
+            print!("{}", help::man_page());
+            exit(0);
+        }
+        _ => {}
+    }
     if matches.opt_present("init") || matches.opt_present("init-local") {
         match init_config_file(matches.opt_present("init-local")) {
             Ok(()) => exit(0),
@@ -1725,6 +1760,14 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     parse_todo(&matches, &mut conf.todo)?;
     parse_sort(&matches, &mut conf.sort);
     parse_fmt(&matches, &mut conf.fmt);
+    if let Some(key) = &conf.fmt.group_sort
+        && !fmt::is_group_sort_key(key, &conf.fmt)
+    {
+        eprintln!(
+            "Invalid value '{key}' for `--group-sort`. Use name, count, overdue, spent, or a name of a numeric custom field"
+        );
+        exit(1);
+    }
 
     conf.stdin = matches.opt_present("stdin");
     conf.dry = matches.opt_present("dry-run");
@@ -1735,11 +1778,29 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     if conf.use_done && conf.flt.all == tfilter::TodoStatus::Active {
         conf.flt.all = tfilter::TodoStatus::Done;
     }
+    conf.with_archive = matches.opt_present("with-archive");
+    conf.reopen = matches.opt_present("reopen");
+    if conf.with_archive && conf.use_done {
+        eprintln!("Options `--done` and `--with-archive` cannot be used together");
+        exit(1);
+    }
+    if conf.with_archive && conf.flt.all == tfilter::TodoStatus::Active {
+        conf.flt.all = tfilter::TodoStatus::All;
+    }
     if matches.opt_present("strict") || conf.stdin {
         conf.strict_mode = true;
     }
     if let Some(dstr) = matches.opt_str("calendar") {
-        let rng = human_date::CalendarRange::parse(&dstr)?;
+        let rng_str = match dstr.split_once('=') {
+            Some((fields, rng_str)) => {
+                if !fields.is_empty() {
+                    conf.calendar_fields = split_calendar_fields(fields);
+                }
+                rng_str
+            }
+            None => dstr.as_str(),
+        };
+        let rng = human_date::CalendarRange::parse(rng_str)?;
         conf.calendar = Some(rng);
     }
 
@@ -1767,6 +1828,13 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
         conf.fmt.hide = str_to_hide(&s);
     }
 
+    if matches.opt_present("confirm") {
+        conf.confirm = true;
+    }
+    if matches.opt_present("no-confirm") {
+        conf.confirm = false;
+    }
+
     if matches.opt_present("auto-show-cols") {
         conf.auto_show_columns = true;
     }
@@ -1780,6 +1848,14 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
         }
         conf.always_hide_columns = v;
     }
+    if let Some(s) = matches.opt_str("archive-rotation") {
+        match archive::str_to_rotation(&s) {
+            Some(r) => conf.archive_rotation = r,
+            None => {
+                return Err(anyhow!(terr::TodoError::InvalidValue(s.to_string(), "archive rotation".to_string())));
+            }
+        }
+    }
     if let Some(s) = matches.opt_str("priority-on-done") {
         match str_to_pri_mode(&s) {
             Some(m) => conf.priority_on_done = m,
@@ -1795,6 +1871,7 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
         conf.add_completion_date_always = true;
     }
     conf.use_editor = matches.opt_present("interactive");
+    conf.watch = matches.opt_present("watch");
     conf.resolution = matches.opt_str("resolution");
 
     if let Some(max_str) = matches.opt_str("max") {
@@ -1814,6 +1891,19 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     // Reason: filter needs to know if regex is used beforehand.
     parse_filter(&matches, &mut conf.flt, soon_days)?;
     conf.use_regex = conf.flt.use_regex;
+    if let Some(s) = matches.opt_str("where") {
+        conf.where_expr = Some(expr::parse(&s, &conf.fmt)?);
+    }
+    // Todos with threshold date in the future are hidden by default. It makes impossible to show
+    // threshold dates in calendar, so the filter is disabled unless a user sets it explicitly.
+    // Note that it shows such todos for all calendar fields, not only for the threshold one.
+    if conf.calendar.is_some()
+        && conf.flt.thr.is_none()
+        && conf.calendar_fields.iter().any(|f| matches!(f.as_str(), "t" | "thr" | "threshold"))
+    {
+        let days = tfilter::ValueRange { low: tfilter::INCLUDE_NONE, high: i64::MAX };
+        conf.flt.thr = Some(tfilter::DateRange { days, span: tfilter::ValueSpan::Range });
+    }
     if let Some(f_str) = matches.opt_str("filter-tag") {
         if !f_str.contains('=') {
             conf.flt.custom_filter = Some(format!("subj={f_str}"));
@@ -1835,6 +1925,10 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     if let Some(s) = matches.opt_str("src") {
         conf.source = s.clone();
     }
+    if let Some(s) = matches.opt_str("to") {
+        conf.move_to = s;
+    }
+    conf.move_archived = matches.opt_present("archived");
 
     conf.on = matches.opt_str("on");
     if matches.opt_present("time") {
@@ -1846,6 +1940,31 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     conf.hide_all_day = conf.hide_all_day || matches.opt_present("hide-all-day");
     conf.hide_all_day = conf.hide_all_day && !matches.opt_present("no-hide-all-day");
     conf.list_sources = matches.opt_present("list-sources");
+    if let Some(s) = matches.opt_str("listen") {
+        conf.listen = s;
+    }
+    if let Some(s) = matches.opt_str("format") {
+        conf.status_format = s;
+    }
+    conf.status_json = matches.opt_present("json");
+    if let Some(s) = matches.opt_str("length") {
+        match conv::str_to_duration(&s) {
+            Some(d) if d > 0 => conf.pomodoro_length = d,
+            _ => return Err(anyhow!(terr::TodoError::InvalidValue(s, "pomodoro length".to_string()))),
+        }
+    }
+    if let Some(s) = matches.opt_str("break") {
+        match conv::str_to_duration(&s) {
+            Some(d) if d >= 0 => conf.pomodoro_break = d,
+            _ => return Err(anyhow!(terr::TodoError::InvalidValue(s, "break length".to_string()))),
+        }
+    }
+    if let Some(s) = matches.opt_str("board-field") {
+        conf.board_field = s.to_lowercase();
+    }
+    if let Some(s) = matches.opt_str("board-columns") {
+        conf.board_columns = split_board_columns(&s);
+    }
 
     let mut idx: usize = 0;
     if idx >= matches.free.len() && !conf.stdin {
@@ -1862,6 +1981,18 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
     } else if conf.strict_mode {
         return Err(anyhow!(terr::TodoError::NotCommand));
     }
+    // `merge` takes paths to files instead of a filter
+    if conf.mode == RunMode::Merge {
+        conf.merge_files = matches.free[idx..].iter().map(PathBuf::from).collect();
+        if conf.merge_files.len() != 3 {
+            return Err(anyhow!("Three files required. Usage: ttdl merge BASE OURS THEIRS"));
+        }
+        return Ok(conf);
+    }
+    if conf.mode == RunMode::Completions || conf.mode == RunMode::Complete {
+        conf.completion = matches.free.get(idx).cloned().unwrap_or_default();
+        return Ok(conf);
+    }
     if idx >= matches.free.len() && !conf.stdin {
         // TODO: validity check
         return Ok(conf);
@@ -1872,19 +2003,34 @@ pub fn parse_args(args: &[String]) -> Result<Conf> {
         || conf.mode == RunMode::None
         || conf.mode == RunMode::Append
         || conf.mode == RunMode::Prepend
-        || conf.mode == RunMode::Postpone;
+        || conf.mode == RunMode::Postpone
+        || conf.mode == RunMode::Move
+        || conf.mode == RunMode::Search;
 
     if conf.use_editor && conf.mode != RunMode::Edit {
         eprintln!("Option '--interactive' can be used only with `edit` command");
         exit(1);
     }
 
+    if conf.watch && conf.mode != RunMode::List && conf.mode != RunMode::None {
+        eprintln!("Option '--watch' can be used only with `list` command");
+        exit(1);
+    }
+
     if conf.use_editor && conf.stdin {
         eprintln!("Option '--interactive' cannot be combined with --stdin");
         exit(1);
     }
 
-    if idx < matches.free.len() {
+    // Status counts done todos too
+    if conf.mode == RunMode::Status && conf.flt.all == tfilter::TodoStatus::Active {
+        conf.flt.all = tfilter::TodoStatus::All;
+    }
+    // Search looks through all todos, and a query is never an ID range
+    if conf.mode == RunMode::Search && conf.flt.all == tfilter::TodoStatus::Active {
+        conf.flt.all = tfilter::TodoStatus::All;
+    }
+    if idx < matches.free.len() && conf.mode != RunMode::Search {
         // second should be a range
         if matches.free[idx].find(|c: char| !c.is_ascii_digit()).is_none() {
             // a single ID
@@ -1985,6 +2131,35 @@ fn process_single_free_arg(conf: &mut Conf, soon_days: u8, edit_mode: bool, raw_
     }
 }
 
+// Returns a copy of the configuration with filter, sorting, and modifying options of a request
+// to `serve`. The options are the same as the command-line ones, e.g. `--due=soon` or `--set-pri=a`
+pub fn request_conf(conf: &Conf, args: &[String]) -> Result<Conf> {
+    let matches = help::options().parse(args)?;
+    let mut conf = conf.clone();
+    parse_filter(&matches, &mut conf.flt, conf.fmt.colors.soon_days)?;
+    parse_sort(&matches, &mut conf.sort);
+    parse_todo(&matches, &mut conf.todo)?;
+    conf.show_hidden = matches.opt_present("hidden");
+    if let Some(s) = matches.opt_str("where") {
+        conf.where_expr = Some(expr::parse(&s, &conf.fmt)?);
+    }
+    if let Some(f_str) = matches.opt_str("filter") {
+        conf.flt.custom_filter = if f_str.contains('=') { Some(f_str) } else { Some(format!("subj={f_str}")) };
+    }
+    if let Some(s) = matches.opt_str("src") {
+        conf.source = s;
+    }
+    if let Some(max_str) = matches.opt_str("max") {
+        match max_str.parse::<usize>() {
+            Ok(max) => conf.max_items = Some(max),
+            Err(_) => {
+                return Err(anyhow!(terr::TodoError::InvalidValue(max_str, "maximum number of items".to_string())));
+            }
+        }
+    }
+    Ok(conf)
+}
+
 // Parses a range in a form "ID1-ID2" or "ID1:ID2".
 // Returns range ends. RangeEnds.l is always less than or equal to RangeEnds.r
 fn parse_id_range(s: &str) -> Result<RangeEnds, terr::TodoError> {
@@ -2073,7 +2248,45 @@ pub fn parse_range(s: &str) -> Option<(&str, &str)> {
 /// Returns true if the given `mode` can be used for `done.txt`.
 /// Most of the modes are available exclusively for `todo.txt`.
 pub fn can_run_for_done(mode: RunMode) -> bool {
-    matches!(mode, RunMode::List | RunMode::Stats)
+    matches!(mode, RunMode::List | RunMode::Stats | RunMode::Board)
+}
+
+/// Returns the name of the change the given `mode` makes to task lists, or None if the mode does not change them.
+/// The name is used to describe a commit.
+pub fn change_name(mode: RunMode) -> Option<&'static str> {
+    match mode {
+        RunMode::Add => Some("add"),
+        RunMode::Done => Some("done"),
+        RunMode::Undone => Some("undone"),
+        RunMode::Clean => Some("clean"),
+        RunMode::Edit => Some("edit"),
+        RunMode::Remove => Some("remove"),
+        RunMode::Append => Some("append"),
+        RunMode::Prepend => Some("prepend"),
+        RunMode::Start => Some("start"),
+        RunMode::Stop => Some("stop"),
+        RunMode::Pomodoro => Some("pomodoro"),
+        RunMode::Postpone => Some("postpone"),
+        RunMode::Move => Some("move"),
+        RunMode::Unarchive => Some("unarchive"),
+        RunMode::Transfer => Some("mv"),
+        _ => None,
+    }
+}
+
+/// Returns true if the given `mode` can be used when active and archived todos are merged.
+/// The modes that add or delete todos are not available because they shift IDs of archived todos.
+pub fn can_run_with_archive(mode: RunMode) -> bool {
+    matches!(
+        mode,
+        RunMode::List
+            | RunMode::Stats
+            | RunMode::Board
+            | RunMode::Undone
+            | RunMode::ListProjects
+            | RunMode::ListContexts
+            | RunMode::ListHashtags
+    )
 }
 
 #[cfg(test)]
@@ -2098,4 +2311,138 @@ mod tests {
             assert_eq!(e, test.res[1], "{}. '{}' != '{}'", idx, e, test.res[1]);
         }
     }
+
+    #[test]
+    fn parse_older_than_test() {
+        struct Test {
+            input: &'static str,
+            low: Option<i64>,
+        }
+        let tests: Vec<Test> = vec![
+            Test { input: "30d", low: Some(-29) },
+            Test { input: "-1w", low: Some(-6) },
+            Test { input: "d", low: None },
+            Test { input: "", low: None },
+        ];
+        for test in tests.iter() {
+            let mut c = tfilter::Conf::default();
+            let res = parse_filter_older_than(test.input, &mut c, 7);
+            match test.low {
+                None => assert!(res.is_err(), "{}", test.input),
+                Some(low) => {
+                    let rng = c.finished.unwrap();
+                    assert_eq!(rng.span, tfilter::ValueSpan::Lower, "{}", test.input);
+                    assert_eq!(rng.days.low, low, "{}", test.input);
+                    assert_eq!(c.all, tfilter::TodoStatus::Done, "{}", test.input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_source_tags_test() {
+        struct Test {
+            input: Option<&'static str>,
+            res: Vec<&'static str>,
+        }
+        let tests: Vec<Test> = vec![
+            Test { input: None, res: vec![] },
+            Test { input: Some(""), res: vec![] },
+            Test { input: Some("work"), res: vec!["work"] },
+            Test { input: Some(" +work, home ,,+"), res: vec!["work", "home"] },
+        ];
+        for test in tests.iter() {
+            let res = split_source_tags(&test.input.map(|s| s.to_string()), '+');
+            assert_eq!(res, test.res, "{:?}", test.input);
+        }
+    }
+
+    #[test]
+    fn expand_source_test() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["one", "two", "three"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        fs::write(dir.path().join("one/todo.txt"), "").unwrap();
+        fs::write(dir.path().join("two/todo.txt"), "").unwrap();
+
+        let src = Source { name: "dir".to_string(), todo_file: dir.path().join("one"), ..Default::default() };
+        let res = expand_source(src);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].name, "dir");
+        assert_eq!(res[0].todo_file, dir.path().join("one/todo.txt"));
+
+        let src = Source { todo_file: dir.path().to_path_buf(), readonly: true, ..Default::default() };
+        let res = expand_source(src);
+        let names: Vec<&str> = res.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["one", "two"]);
+        assert!(res.iter().all(|s| s.readonly && s.done_file.as_os_str().is_empty()));
+
+        let src = Source {
+            todo_file: dir.path().join("*").join("todo.txt"),
+            done_file: PathBuf::from("archive.txt"),
+            ..Default::default()
+        };
+        let res = expand_source(src);
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[1].todo_file, dir.path().join("two/todo.txt"));
+        assert_eq!(res[1].done_file, dir.path().join("two/archive.txt"));
+
+        // Lists with the same directory names in different places
+        let other = tempfile::tempdir().unwrap();
+        fs::create_dir(other.path().join("one")).unwrap();
+        fs::write(other.path().join("one/todo.txt"), "").unwrap();
+        let first = Source { todo_file: dir.path().to_path_buf(), ..Default::default() };
+        let second = Source { todo_file: other.path().to_path_buf(), ..Default::default() };
+        let res: Vec<Source> = vec![first.clone(), second.clone()].into_iter().flat_map(expand_source).collect();
+        let found: Vec<String> = res.iter().map(|s| s.name.clone()).collect();
+        assert_eq!(duplicated_source_name(&res, &found), Some("one"));
+
+        let first = Source { name: "home".to_string(), ..first };
+        let second = Source { name: "work".to_string(), ..second };
+        let res: Vec<Source> = vec![first, second].into_iter().flat_map(expand_source).collect();
+        let names: Vec<&str> = res.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["home/one", "home/two", "work/one"]);
+        let found: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        assert_eq!(duplicated_source_name(&res, &found), None);
+
+        // Configured task lists can share a name
+        let shared = vec![
+            Source { name: "home".to_string(), ..Default::default() },
+            Source { name: "home".to_string(), ..Default::default() },
+        ];
+        assert_eq!(duplicated_source_name(&shared, &[]), None);
+    }
+
+    #[test]
+    fn readonly_test() {
+        let today = chrono::NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let mut conf = Conf::default();
+        conf.task_lists.push(Source { name: "home".to_string(), ..Default::default() });
+        conf.task_lists.push(Source { name: "shared".to_string(), readonly: true, ..Default::default() });
+        conf.task_lists.push(Source { readonly: true, ..Default::default() });
+        let mut task = todotxt::Task::parse("call mom", today);
+        assert!(!conf.is_readonly(&task));
+        task.set_source("home", 0);
+        assert!(!conf.is_readonly(&task));
+        task.set_source("shared", 1);
+        assert!(conf.is_readonly(&task));
+
+        assert_eq!(conf.check_writable(&[0]), Ok(()));
+        assert_eq!(conf.check_writable(&[0, 1]), Err("Task list 'shared' is read-only".to_string()));
+        assert_eq!(conf.check_writable(&[2]), Err("Task list '3' is read-only".to_string()));
+
+        conf.task_lists.truncate(1);
+        conf.task_lists[0].readonly = true;
+        task.source = None;
+        assert!(conf.is_readonly(&task));
+    }
+
+    #[test]
+    fn command_names_test() {
+        for name in help::command_names() {
+            assert_ne!(str_to_mode(name), RunMode::None, "{name}");
+            assert_ne!(str_to_mode(name), RunMode::Complete, "{name}");
+        }
+    }
 }
@@ -0,0 +1,106 @@
+// What happened to a line when `old` list turned into `new` one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    // The line `old[.0]` is the same as `new[.1]`
+    Same(usize, usize),
+    // The line `old[.0]` is replaced with `new[.1]`
+    Changed(usize, usize),
+    // The line `old[.0]` is removed
+    Removed(usize),
+    // The line `new[.0]` is added
+    Added(usize),
+}
+
+// Returns the longest common subsequence of two lists as pairs of indices
+fn common_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// Compares two lists line by line. Unchanged lines are found first. Between two unchanged lines,
+// removed and added lines are paired in order and become changed ones. The rest are removed or added.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut anchors = common_lines(old, new);
+    anchors.push((old.len(), new.len()));
+    for (oi, nj) in anchors {
+        while i < oi && j < nj {
+            edits.push(Edit::Changed(i, j));
+            i += 1;
+            j += 1;
+        }
+        while i < oi {
+            edits.push(Edit::Removed(i));
+            i += 1;
+        }
+        while j < nj {
+            edits.push(Edit::Added(j));
+            j += 1;
+        }
+        if oi < old.len() {
+            edits.push(Edit::Same(oi, nj));
+            i += 1;
+            j += 1;
+        }
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+        struct Test {
+            old: Vec<&'static str>,
+            new: Vec<&'static str>,
+            res: Vec<Edit>,
+        }
+        let tests: Vec<Test> = vec![
+            Test { old: vec![], new: vec![], res: vec![] },
+            Test { old: vec!["a", "b"], new: vec!["a", "b"], res: vec![Edit::Same(0, 0), Edit::Same(1, 1)] },
+            Test {
+                old: vec!["a", "b", "c"],
+                new: vec!["a", "B", "c"],
+                res: vec![Edit::Same(0, 0), Edit::Changed(1, 1), Edit::Same(2, 2)],
+            },
+            Test {
+                old: vec!["a", "b", "c"],
+                new: vec!["a", "c", "d"],
+                res: vec![Edit::Same(0, 0), Edit::Removed(1), Edit::Same(2, 1), Edit::Added(2)],
+            },
+            Test {
+                old: vec!["a", "b"],
+                new: vec!["x", "y", "b"],
+                res: vec![Edit::Changed(0, 0), Edit::Added(1), Edit::Same(1, 2)],
+            },
+            Test { old: vec!["a", "b"], new: vec![], res: vec![Edit::Removed(0), Edit::Removed(1)] },
+        ];
+        for (idx, test) in tests.iter().enumerate() {
+            let res = diff(&test.old, &test.new);
+            assert_eq!(res, test.res, "{idx}");
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::conf;
+
+// The header of a task list section in the temporary file: `# [name]`. A task list which name
+// is empty or shared with another task list is identified by its number
+pub fn list_header(conf: &conf::Conf, list_id: usize) -> String {
+    let name = &conf.task_lists[list_id].name;
+    if name.is_empty() || conf.list_name_to_index(name) != Some(list_id) {
+        format!("# [{0}]", list_id + 1)
+    } else {
+        format!("# [{name}]")
+    }
+}
+
+// Headers are written only in multi-file mode. In single-file mode, a line like `# [abc]` is a todo
+fn parse_list_header<'a>(line: &'a str, conf: &conf::Conf) -> Option<&'a str> {
+    if conf.is_single_file_mode() {
+        return None;
+    }
+    line.trim().strip_prefix("# [")?.strip_suffix(']')
+}
+
+// Returns true if the temporary file contains any todo. Task list headers do not count:
+// a file with only headers is empty
+pub fn has_todos(content: &str, conf: &conf::Conf) -> bool {
+    content.lines().any(|l| !l.trim().is_empty() && parse_list_header(l, conf).is_none())
+}
+
+// Parses the edited temporary file and returns its todos along with IDs of task lists they belong to.
+// A todo belongs to the task list of the closest header above it, or to the default task list.
+pub fn parse_temp_file(content: &str, conf: &conf::Conf) -> Result<Vec<(usize, String)>, String> {
+    let mut list_id = conf.selected_task_list();
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        if let Some(name) = parse_list_header(line, conf) {
+            match conf.list_name_to_index(name) {
+                None => return Err(format!("Task list '{name}' not found")),
+                Some(id) if conf.task_lists[id].readonly => {
+                    return Err(format!("Cannot add a todo to the read-only task list '{name}'"));
+                }
+                Some(id) => list_id = Some(id),
+            }
+            continue;
+        }
+        let subj = line.trim();
+        if subj.is_empty() {
+            continue;
+        }
+        match list_id {
+            Some(id) => lines.push((id, subj.to_string())),
+            None => return Err(format!("Failed to choose a task list for '{subj}'. Put it after a task list header")),
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_conf(names: &[&str]) -> conf::Conf {
+        let mut conf = conf::Conf::default();
+        for name in names {
+            conf.task_lists.push(conf::Source {
+                name: name.to_string(),
+                readonly: *name == "shared",
+                ..Default::default()
+            });
+        }
+        conf
+    }
+
+    #[test]
+    fn list_header_test() {
+        let conf = make_conf(&["home", "", "work", "work"]);
+        let res: Vec<String> = (0..4).map(|id| list_header(&conf, id)).collect();
+        assert_eq!(res, vec!["# [home]", "# [2]", "# [work]", "# [4]"]);
+    }
+
+    #[test]
+    fn parse_temp_file_test() {
+        struct Test {
+            names: Vec<&'static str>,
+            content: &'static str,
+            res: Result<Vec<(usize, &'static str)>, &'static str>,
+        }
+        let tests: Vec<Test> = vec![
+            Test {
+                names: vec![""],
+                content: "# [home]\nbuy milk\n\n",
+                res: Ok(vec![(0, "# [home]"), (0, "buy milk")]),
+            },
+            Test {
+                names: vec!["home", "work"],
+                content: "# [home]\nbuy milk\n\n# [2]\n  call boss  \n# [home]\nwash car",
+                res: Ok(vec![(0, "buy milk"), (1, "call boss"), (0, "wash car")]),
+            },
+            Test {
+                names: vec!["home", "work"],
+                content: "buy milk\n# [home]",
+                res: Err("Failed to choose a task list for 'buy milk'. Put it after a task list header"),
+            },
+            Test { names: vec!["home", "work"], content: "# [car]\nwash car", res: Err("Task list 'car' not found") },
+            Test {
+                names: vec!["home", "shared"],
+                content: "# [shared]\nwash car",
+                res: Err("Cannot add a todo to the read-only task list 'shared'"),
+            },
+        ];
+        for test in tests.iter() {
+            let conf = make_conf(&test.names);
+            let res = parse_temp_file(test.content, &conf);
+            let expected = test
+                .res
+                .clone()
+                .map(|l| l.into_iter().map(|(id, s)| (id, s.to_string())).collect::<Vec<(usize, String)>>())
+                .map_err(|e| e.to_string());
+            assert_eq!(expected, res, "{:?}: {}", test.names, test.content);
+        }
+    }
+
+    #[test]
+    fn has_todos_test() {
+        assert!(!has_todos("# [home]\n\n# [work]\n", &make_conf(&["home", "work"])));
+        assert!(has_todos("# [home]\nbuy milk\n", &make_conf(&["home", "work"])));
+        assert!(has_todos("# [home]\n", &make_conf(&[""])));
+    }
+}
@@ -0,0 +1,445 @@
+use chrono::{Local, NaiveDate};
+use termcolor::ColorSpec;
+use todo_lib::{conv, terr, timer, todotxt};
+
+use crate::fmt;
+
+// Boolean filter expression for the option `--where`.
+//
+// Grammar (keywords are case-insensitive):
+//   expr    := and ( ("or" | "||") and )*
+//   and     := not ( ("and" | "&&") not )*
+//   not     := ("not" | "!") not | primary
+//   primary := "(" expr ")" | FIELD [OP VALUE] | +PROJECT | @CONTEXT | #HASHTAG
+//   OP      := "=" | "==" | "!=" | "<" | "<=" | ">" | ">=" | "~"
+// A bare FIELD checks that a todo has the field. VALUE can be a list `v1,v2` or a range `v1..v2`
+// (only for `=` and `!=`), and can be quoted to include spaces.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Contains,
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    // A value matches the rule
+    Rule(fmt::FmtRule),
+    // A value matches the range rule but it is not equal to the range end. Used for `<` and `>`
+    Strict(fmt::FmtRule, fmt::FmtRule),
+    // A value contains a substring (case-insensitive)
+    Contains(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Has(String),
+    Cmp { field: String, kind: String, check: Check },
+}
+
+fn expr_err(expr: &str, msg: &str) -> terr::TodoError {
+    terr::TodoError::InvalidValue(expr.to_string(), msg.to_string())
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, terr::TodoError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = s.chars().collect();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let next = chars.get(idx + 1).copied();
+        match c {
+            ' ' | '\t' | '\n' => idx += 1,
+            '(' => {
+                tokens.push(Token::Open);
+                idx += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                idx += 1;
+            }
+            '&' | '|' if next == Some(c) => {
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+                idx += 2;
+            }
+            '!' if next == Some('=') => {
+                tokens.push(Token::Op(Op::NotEq));
+                idx += 2;
+            }
+            '!' => {
+                tokens.push(Token::Not);
+                idx += 1;
+            }
+            '=' => {
+                tokens.push(Token::Op(Op::Eq));
+                idx += if next == Some('=') { 2 } else { 1 };
+            }
+            '<' | '>' => {
+                let op = match (c, next == Some('=')) {
+                    ('<', true) => Op::LessEq,
+                    ('<', false) => Op::Less,
+                    (_, true) => Op::GreaterEq,
+                    (_, false) => Op::Greater,
+                };
+                tokens.push(Token::Op(op));
+                idx += if next == Some('=') { 2 } else { 1 };
+            }
+            '~' => {
+                tokens.push(Token::Op(Op::Contains));
+                idx += 1;
+            }
+            '"' | '\'' => {
+                let start = idx + 1;
+                let Some(len) = chars[start..].iter().position(|&ch| ch == c) else {
+                    return Err(expr_err(s, "unclosed quote"));
+                };
+                tokens.push(Token::Word(chars[start..start + len].iter().collect()));
+                idx = start + len + 1;
+            }
+            _ => {
+                let start = idx;
+                while idx < chars.len() && !" \t\n()=!<>~&|\"'".contains(chars[idx]) {
+                    idx += 1;
+                }
+                if start == idx {
+                    return Err(expr_err(s, &format!("unexpected character '{c}'")));
+                }
+                let word: String = chars[start..idx].iter().collect();
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn canonical_field(field: &str) -> String {
+    let lower = field.to_lowercase();
+    match lower.as_str() {
+        "pri" | "priority" => "pri".to_string(),
+        "subj" | "subject" => "subj".to_string(),
+        "prj" | "project" | "proj" => "prj".to_string(),
+        "ctx" | "context" => "ctx".to_string(),
+        "hash" | "hashtag" => "hash".to_string(),
+        "t" | "thr" | "threshold" => "t".to_string(),
+        "created" | "create" => "created".to_string(),
+        "completed" | "finished" => "completed".to_string(),
+        "due" | "done" | "rec" | "id" | "spent" => lower,
+        _ => field.to_string(),
+    }
+}
+
+// Detects the type of a field value. Built-in fields and custom fields have fixed types.
+// For the rest tags, the type is guessed by the value in the expression.
+fn field_kind(field: &str, value: &str, c: &fmt::Conf) -> String {
+    match field {
+        "pri" | "subj" | "prj" | "ctx" | "hash" | "rec" => return "string".to_string(),
+        "due" | "t" | "created" | "completed" => return "date".to_string(),
+        "id" => return "integer".to_string(),
+        "spent" => return "duration".to_string(),
+        _ => {}
+    }
+    if let Some(f) = c.custom_fields.iter().find(|f| f.name == field) {
+        return f.kind.clone();
+    }
+    if field.ends_with("_date") {
+        return "date".to_string();
+    }
+    if field.ends_with("_dur") || field.ends_with("_duration") {
+        return "duration".to_string();
+    }
+    if field.ends_with("_size") || field.ends_with("_sz") {
+        return "bytes".to_string();
+    }
+    let first = value.split([',']).next().unwrap_or_default();
+    let first = first.split("..").find(|s| !s.is_empty()).unwrap_or_default();
+    let today = Local::now().date_naive();
+    if first.parse::<f64>().is_ok() {
+        "float".to_string()
+    } else if todotxt::parse_date(first, today).is_ok() {
+        "date".to_string()
+    } else if first.chars().any(|ch| ch.is_ascii_digit()) && conv::str_to_duration(first).is_some() {
+        "duration".to_string()
+    } else if first.chars().any(|ch| ch.is_ascii_digit()) && conv::str_to_bytes(first).is_some() {
+        "bytes".to_string()
+    } else if conv::str_to_date(first, today).is_some() {
+        "date".to_string()
+    } else {
+        "string".to_string()
+    }
+}
+
+fn make_rule(range: fmt::FmtSpec) -> fmt::FmtRule {
+    fmt::FmtRule { range, color: ColorSpec::new() }
+}
+
+fn value_to_spec(value: &str) -> fmt::FmtSpec {
+    if let Some((b, e)) = value.split_once("..") {
+        fmt::FmtSpec::Range(b.to_string(), e.to_string())
+    } else {
+        fmt::FmtSpec::List(value.split(',').map(|v| v.to_string()).collect())
+    }
+}
+
+fn make_cmp(field: &str, op: Op, value: &str, c: &fmt::Conf) -> Expr {
+    let field = canonical_field(field);
+    let value = if field == "pri" { value.to_uppercase() } else { value.to_string() };
+    let kind = field_kind(&field, &value, c);
+    let check = match op {
+        Op::Eq | Op::NotEq => Check::Rule(make_rule(value_to_spec(&value))),
+        Op::LessEq => Check::Rule(make_rule(fmt::FmtSpec::Range(String::new(), value.clone()))),
+        Op::GreaterEq => Check::Rule(make_rule(fmt::FmtSpec::Range(value.clone(), String::new()))),
+        Op::Less => Check::Strict(
+            make_rule(fmt::FmtSpec::Range(String::new(), value.clone())),
+            make_rule(fmt::FmtSpec::List(vec![value.clone()])),
+        ),
+        Op::Greater => Check::Strict(
+            make_rule(fmt::FmtSpec::Range(value.clone(), String::new())),
+            make_rule(fmt::FmtSpec::List(vec![value.clone()])),
+        ),
+        Op::Contains => Check::Contains(value.to_lowercase()),
+    };
+    let cmp = Expr::Cmp { field, kind, check };
+    if op == Op::NotEq { Expr::Not(Box::new(cmp)) } else { cmp }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    conf: &'a fmt::Conf,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+    fn parse_or(&mut self) -> Result<Expr, terr::TodoError> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+    fn parse_and(&mut self) -> Result<Expr, terr::TodoError> {
+        let mut left = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+    fn parse_not(&mut self) -> Result<Expr, terr::TodoError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            let e = self.parse_not()?;
+            return Ok(Expr::Not(Box::new(e)));
+        }
+        self.parse_primary()
+    }
+    fn parse_primary(&mut self) -> Result<Expr, terr::TodoError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let e = self.parse_or()?;
+                if self.next() != Some(Token::Close) {
+                    return Err(expr_err(self.src, "missing closing parenthesis"));
+                }
+                Ok(e)
+            }
+            Some(Token::Word(w)) => {
+                if let Some(Token::Op(op)) = self.peek().cloned() {
+                    self.pos += 1;
+                    let Some(Token::Word(value)) = self.next() else {
+                        return Err(expr_err(self.src, &format!("value expected after '{w}'")));
+                    };
+                    return Ok(make_cmp(&w, op, &value, self.conf));
+                }
+                let shortcut = match w.chars().next() {
+                    Some('+') => Some("prj"),
+                    Some('@') => Some("ctx"),
+                    Some('#') => Some("hash"),
+                    _ => None,
+                };
+                match shortcut {
+                    Some(field) if w.len() > 1 => Ok(make_cmp(field, Op::Eq, &w[1..], self.conf)),
+                    _ => Ok(Expr::Has(canonical_field(&w))),
+                }
+            }
+            Some(t) => Err(expr_err(self.src, &format!("unexpected token {t:?}"))),
+            None => Err(expr_err(self.src, "unexpected end of expression")),
+        }
+    }
+}
+
+// Compiles a filter expression. Custom fields of the configuration define types of tag values.
+pub fn parse(s: &str, c: &fmt::Conf) -> Result<Expr, terr::TodoError> {
+    let tokens = tokenize(s)?;
+    if tokens.is_empty() {
+        return Err(expr_err(s, "empty expression"));
+    }
+    let mut parser = Parser { src: s, tokens, pos: 0, conf: c };
+    let e = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(expr_err(s, "unexpected text after the end of expression"));
+    }
+    Ok(e)
+}
+
+fn date_str(d: Option<NaiveDate>) -> Vec<String> {
+    match d {
+        Some(d) => vec![todotxt::format_date(d)],
+        None => Vec::new(),
+    }
+}
+
+// Returns all values of a todo field. An empty list means the todo does not have the field.
+fn field_values(task: &todotxt::Task, id: usize, field: &str) -> Vec<String> {
+    match field {
+        "pri" => {
+            if task.priority < todotxt::NO_PRIORITY {
+                vec![todotxt::priority_to_char(task.priority).to_string()]
+            } else {
+                Vec::new()
+            }
+        }
+        "subj" => vec![task.subject.clone()],
+        "prj" => task.projects.clone(),
+        "ctx" => task.contexts.clone(),
+        "hash" => task.hashtags.clone(),
+        "due" => date_str(task.due_date),
+        "t" => date_str(task.threshold_date),
+        "created" => date_str(task.create_date),
+        "completed" => date_str(task.finish_date),
+        "done" => {
+            if task.finished {
+                vec!["1".to_string()]
+            } else {
+                Vec::new()
+            }
+        }
+        "rec" => task.recurrence.as_ref().map(|r| vec![format!("{r}")]).unwrap_or_default(),
+        "id" => vec![format!("{}", id + 1)],
+        "spent" => {
+            let spent = timer::spent_time(task).num_seconds();
+            if spent > 0 { vec![format!("{spent}")] } else { Vec::new() }
+        }
+        tag => task.tags.get(tag).map(|v| vec![v.clone()]).unwrap_or_default(),
+    }
+}
+
+impl Expr {
+    // Returns true if the todo with the given ID matches the expression. Relative dates are
+    // calculated from the date `today`
+    pub fn matches(&self, task: &todotxt::Task, id: usize, today: NaiveDate) -> bool {
+        match self {
+            Expr::And(l, r) => l.matches(task, id, today) && r.matches(task, id, today),
+            Expr::Or(l, r) => l.matches(task, id, today) || r.matches(task, id, today),
+            Expr::Not(e) => !e.matches(task, id, today),
+            Expr::Has(field) => !field_values(task, id, field).is_empty(),
+            Expr::Cmp { field, kind, check } => {
+                let values = field_values(task, id, field);
+                values.iter().any(|v| match check {
+                    Check::Rule(rule) => rule.matches_on(v, kind, today).is_some(),
+                    Check::Strict(rule, eq) => {
+                        rule.matches_on(v, kind, today).is_some() && eq.matches_on(v, kind, today).is_none()
+                    }
+                    Check::Contains(s) => v.to_lowercase().contains(s.as_str()),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_err_test() {
+        let c = fmt::Conf::default();
+        let tests: Vec<&str> = vec!["", "(due", "due =", "due and", "pri = a )", "subj ~ \"unclosed", "or due"];
+        for test in tests.iter() {
+            assert!(parse(test, &c).is_err(), "'{test}' must fail");
+        }
+    }
+
+    #[test]
+    fn matches_test() {
+        struct Test {
+            expr: &'static str,
+            res: Vec<usize>,
+        }
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let tasks: Vec<todotxt::Task> = vec![
+            todotxt::Task::parse("(A) call mom @phone +family due:2000-01-05 est:3", today),
+            todotxt::Task::parse("(C) write report +work status:doing due:2024-05-11 est:2.5", today),
+            todotxt::Task::parse("x 2001-02-02 buy milk @shop #food", today),
+            todotxt::Task::parse("read a book size:10mb", today),
+        ];
+        let mut c = fmt::Conf::default();
+        c.custom_fields.push(fmt::CustomField {
+            name: "est".to_string(),
+            title: "Estimate".to_string(),
+            kind: "float".to_string(),
+            ..Default::default()
+        });
+        let tests: Vec<Test> = vec![
+            Test { expr: "pri", res: vec![0, 1] },
+            Test { expr: "not pri", res: vec![2, 3] },
+            Test { expr: "pri <= b", res: vec![0] },
+            Test { expr: "pri > A", res: vec![1] },
+            Test { expr: "+family or @shop", res: vec![0, 2] },
+            Test { expr: "prj = work && status = doing", res: vec![1] },
+            Test { expr: "status != doing", res: vec![0, 2, 3] },
+            Test { expr: "est > 2.5", res: vec![0] },
+            Test { expr: "est >= 2.5", res: vec![0, 1] },
+            Test { expr: "est = 1..2.5", res: vec![1] },
+            Test { expr: "due < today", res: vec![0] },
+            Test { expr: "due = today..1w", res: vec![1] },
+            Test { expr: "due = 2024-05-12..", res: vec![] },
+            Test { expr: "due = 2000-01-05", res: vec![0] },
+            Test { expr: "done || (subj ~ \"A BOOK\" and size > 1mb)", res: vec![2, 3] },
+            Test { expr: "!(#food) AND NOT due", res: vec![3] },
+            Test { expr: "completed >= 2001-01-01", res: vec![2] },
+            Test { expr: "id = 2,4", res: vec![1, 3] },
+        ];
+        for test in tests.iter() {
+            let e = parse(test.expr, &c).unwrap();
+            let res: Vec<usize> =
+                tasks.iter().enumerate().filter(|(id, t)| e.matches(t, *id, today)).map(|(id, _)| id).collect();
+            assert_eq!(test.res, res, "{} --> {:?} != {:?}", test.expr, test.res, res);
+        }
+    }
+}
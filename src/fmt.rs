@@ -11,7 +11,7 @@ use unicode_width::UnicodeWidthStr;
 use crate::colauto::cleanup_description;
 use crate::conf;
 use crate::conv;
-use crate::human_date;
+use crate::stats;
 use crate::subj_clean::{Hide, hide_all};
 
 const SPENT_WIDTH: usize = 6;
@@ -275,13 +275,12 @@ impl FmtRule {
             }
         }
     }
-    fn matches_date(&self, value: &str) -> Option<ColorSpec> {
-        let today = Local::now().date_naive();
+    fn matches_date(&self, value: &str, today: NaiveDate) -> Option<ColorSpec> {
         let v = todotxt::parse_date(value, today).ok()?;
         match &self.range {
             FmtSpec::List(l) => {
                 for item in l.iter() {
-                    let vv = human_date::human_to_date(today, item, 7).ok()?;
+                    let vv = conv::str_to_date(item, today)?;
                     if vv == v {
                         return Some(self.color.clone());
                     }
@@ -293,18 +292,18 @@ impl FmtRule {
                     return Some(self.color.clone());
                 }
                 if b.is_empty() {
-                    let e = human_date::human_to_date(today, e, 7).ok()?;
+                    let e = conv::str_to_date(e, today)?;
                     if v <= e {
                         return Some(self.color.clone());
                     }
                 } else if e.is_empty() {
-                    let b = human_date::human_to_date(today, b, 7).ok()?;
+                    let b = conv::str_to_date(b, today)?;
                     if v >= b {
                         return Some(self.color.clone());
                     }
                 } else {
-                    let e = human_date::human_to_date(today, e, 7).ok()?;
-                    let b = human_date::human_to_date(today, b, 7).ok()?;
+                    let e = conv::str_to_date(e, today)?;
+                    let b = conv::str_to_date(b, today)?;
                     if v >= b && v <= e {
                         return Some(self.color.clone());
                     }
@@ -389,10 +388,15 @@ impl FmtRule {
     }
 
     fn matches(&self, value: &str, kind: &str) -> Option<ColorSpec> {
+        self.matches_on(value, kind, Local::now().date_naive())
+    }
+
+    // Relative dates, like `today` or `1w`, are calculated from the date `today`
+    pub(crate) fn matches_on(&self, value: &str, kind: &str, today: NaiveDate) -> Option<ColorSpec> {
         match kind {
             "int" | "integer" => self.matches_int(value),
             "float" => self.matches_float(value),
-            "date" => self.matches_date(value),
+            "date" => self.matches_date(value, today),
             "duration" => self.matches_duration(value),
             "bytes" => self.matches_bytes(value),
             "str" | "string" => self.matches_str(value),
@@ -411,6 +415,17 @@ pub struct CustomField {
 }
 
 impl CustomField {
+    // Returns false if the value cannot be parsed as a value of the field kind
+    pub fn is_valid(&self, val: &str) -> bool {
+        match self.kind.as_str() {
+            "int" | "integer" => val.parse::<i64>().is_ok(),
+            "float" => val.parse::<f64>().is_ok(),
+            "date" => todotxt::parse_date(val, Local::now().date_naive()).is_ok(),
+            "duration" => conv::str_to_duration(val).is_some(),
+            "bytes" => conv::str_to_bytes(val).is_some(),
+            _ => true,
+        }
+    }
     fn matches(&self, val: &str) -> Option<ColorSpec> {
         for rule in self.rules.iter() {
             let clr = rule.matches(val, &self.kind);
@@ -444,7 +459,10 @@ pub struct Conf {
     pub custom_fields: Vec<CustomField>,
     pub custom_names: Vec<String>, // for performance
     pub hide: Hide,
-    pub group: Option<String>,
+    pub group: Vec<String>, // list of fields for nested grouping, empty list means no grouping
+    pub group_sort: Option<String>, // None - sort groups by name, otherwise by an aggregate value
+    pub group_sort_rev: bool,
+    pub group_totals: bool,
     pub hide_headers: bool,
     pub hide_fields: Vec<String>,
     pub date_format: String,
@@ -476,7 +494,10 @@ impl Default for Conf {
             custom_fields: Vec::new(),
             custom_names: Vec::new(),
             hide: Hide::Nothing,
-            group: None,
+            group: Vec::new(),
+            group_sort: None,
+            group_sort_rev: false,
+            group_totals: false,
             hide_headers: false,
             hide_fields: Vec::new(),
             date_format: String::new(),
@@ -587,6 +608,7 @@ fn print_header_line(stdout: &mut StandardStream, c: &Conf, fields: &[String], w
         match f.as_str() {
             "id" => continue,
             "done" => write!(stdout, "D ")?,
+            "arc" => write!(stdout, "A ")?,
             "pri" => write!(stdout, "P ")?,
             "created" => write!(stdout, "{:wid$} ", "Created", wid = width)?,
             "finished" => write!(stdout, "{:wid$} ", "Finished", wid = width)?,
@@ -882,6 +904,10 @@ fn print_line(
             "done" => {
                 print_done_val(stdout, task, &arg, &fg)?;
             }
+            "arc" => {
+                let archived = conf.archived_from.is_some_and(|from| id > from);
+                print_with_color(stdout, if archived { "a " } else { "  " }, &fg)?;
+            }
             "pri" => {
                 print_priority_val(stdout, task, &arg, c)?;
             }
@@ -951,14 +977,14 @@ fn print_line(
             }
             "src" => {
                 let width = field_width_cached(f, flist, widths);
-                let value = if let Some(src) = &task.source
+                let (value, clr) = if let Some(src) = &task.source
                     && src.id < conf.task_lists.len()
                 {
-                    src.name.clone()
+                    (src.name.clone(), conf.task_lists[src.id].color.clone().unwrap_or_else(|| fg.clone()))
                 } else {
-                    String::new()
+                    (String::new(), fg.clone())
                 };
-                print_with_color(stdout, &format!("{value:width$} "), &fg)?;
+                print_with_color(stdout, &format!("{value:width$} "), &clr)?;
             }
             "src_id" => {
                 let width = field_width_cached(f, flist, widths);
@@ -1334,7 +1360,7 @@ pub fn print_body_single(
     Ok(())
 }
 
-fn values_of_field(task: &todotxt::Task, field: &str) -> Vec<String> {
+pub(crate) fn values_of_field(task: &todotxt::Task, field: &str) -> Vec<String> {
     let mut items = Vec::new();
     let mut values = match field {
         "prj" | "project" => task.projects.clone(),
@@ -1375,7 +1401,7 @@ fn values_of_field(task: &todotxt::Task, field: &str) -> Vec<String> {
     items
 }
 fn select_uniq_values(tasks: &todo::TaskSlice, selected: &todo::IDSlice, only_selected: bool, c: &Conf) -> Vec<String> {
-    let group = match &c.group {
+    let group = match c.group.first() {
         None => return vec![String::new()],
         Some(g) => g.clone(),
     };
@@ -1419,50 +1445,202 @@ fn select_uniq_values(tasks: &todo::TaskSlice, selected: &todo::IDSlice, only_se
     v
 }
 
-fn print_body_selected(
+// Aggregated values of a group of todos
+#[derive(Debug, Default, PartialEq)]
+struct GroupTotals {
+    count: usize,
+    overdue: usize,
+    spent: Duration,
+    // Sum of values of every numeric custom field. None if no todo in the group has the field
+    custom: Vec<Option<f64>>,
+}
+
+fn is_numeric_kind(kind: &str) -> bool {
+    matches!(kind, "int" | "integer" | "float" | "duration")
+}
+
+fn custom_field_number(task: &todotxt::Task, field: &CustomField) -> Option<f64> {
+    let val = task.tags.get(&field.name)?;
+    match field.kind.as_str() {
+        "int" | "integer" | "float" => val.parse::<f64>().ok(),
+        "duration" => conv::str_to_duration(val).map(|d| d as f64),
+        _ => None,
+    }
+}
+
+fn group_totals(tasks: &todo::TaskSlice, ids: &todo::IDSlice, c: &Conf) -> GroupTotals {
+    let today = Local::now().date_naive();
+    let numeric: Vec<&CustomField> = c.custom_fields.iter().filter(|f| is_numeric_kind(&f.kind)).collect();
+    let mut totals = GroupTotals { custom: vec![None; numeric.len()], ..Default::default() };
+    for id in ids {
+        let task = &tasks[*id];
+        totals.count += 1;
+        if stats::is_task_overdue(task, today) {
+            totals.overdue += 1;
+        }
+        totals.spent += timer::spent_time(task);
+        for (idx, field) in numeric.iter().enumerate() {
+            if let Some(v) = custom_field_number(task, field) {
+                totals.custom[idx] = Some(totals.custom[idx].unwrap_or(0.0) + v);
+            }
+        }
+    }
+    totals
+}
+
+// Returns true if groups can be sorted by the key: it is an aggregate or a numeric custom field
+pub fn is_group_sort_key(key: &str, c: &Conf) -> bool {
+    matches!(key, "name" | "count" | "overdue" | "spent")
+        || c.custom_fields.iter().any(|f| f.name == key && is_numeric_kind(&f.kind))
+}
+
+// Returns the value of an aggregate to sort groups: `count`, `overdue`, `spent`, or a name of
+// numeric custom field.
+fn group_sort_value(totals: &GroupTotals, key: &str, c: &Conf) -> f64 {
+    match key {
+        "count" => totals.count as f64,
+        "overdue" => totals.overdue as f64,
+        "spent" => totals.spent.num_seconds() as f64,
+        name => {
+            let numeric = c.custom_fields.iter().filter(|f| is_numeric_kind(&f.kind));
+            match numeric.zip(totals.custom.iter()).find(|(f, _)| f.name == name) {
+                Some((_, v)) => v.unwrap_or(0.0),
+                None => 0.0,
+            }
+        }
+    }
+}
+
+// Splits todos into groups by values of the field. Groups are sorted by name or by aggregate.
+// The group of todos without any value always goes last.
+fn select_groups(
+    tasks: &todo::TaskSlice,
+    ids: &todo::IDSlice,
+    field: &str,
+    c: &Conf,
+) -> Vec<(String, todo::IDVec, GroupTotals)> {
+    let mut groups: Vec<(String, todo::IDVec)> = Vec::new();
+    for id in ids {
+        for v in values_of_field(&tasks[*id], field) {
+            match groups.iter_mut().find(|(name, _)| *name == v) {
+                Some((_, g_ids)) => g_ids.push(*id),
+                None => groups.push((v, vec![*id])),
+            }
+        }
+    }
+    let mut groups: Vec<(String, todo::IDVec, GroupTotals)> = groups
+        .into_iter()
+        .map(|(name, g_ids)| {
+            let totals = group_totals(tasks, &g_ids, c);
+            (name, g_ids, totals)
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        let by_name = a.0.cmp(&b.0);
+        let ord = match &c.group_sort {
+            None => by_name,
+            Some(key) => {
+                let av = group_sort_value(&a.2, key, c);
+                let bv = group_sort_value(&b.2, key, c);
+                av.partial_cmp(&bv).unwrap_or(Ordering::Equal).then(by_name)
+            }
+        };
+        if c.group_sort_rev { ord.reverse() } else { ord }
+    });
+    if let Some(pos) = groups.iter().position(|g| g.0.is_empty()) {
+        let empty = groups.remove(pos);
+        groups.push(empty);
+    }
+    groups
+}
+
+fn print_group_header(stdout: &mut StandardStream, field: &str, name: &str, level: usize) -> io::Result<()> {
+    let group_name = if name.is_empty() { "[Empty]" } else { name };
+    let indent = "  ".repeat(level);
+    match field {
+        "prj" | "project" => writeln!(stdout, "{indent}+{group_name}"),
+        "ctx" | "context" => writeln!(stdout, "{indent}@{group_name}"),
+        "hash" | "hashtag" => writeln!(stdout, "{indent}#{group_name}"),
+        _ => writeln!(stdout, "{indent}{group_name}"),
+    }
+}
+
+fn print_group_totals(stdout: &mut StandardStream, totals: &GroupTotals, level: usize, c: &Conf) -> io::Result<()> {
+    let mut items = vec![format!("{} todos", totals.count)];
+    if totals.overdue != 0 {
+        items.push(format!("overdue: {}", totals.overdue));
+    }
+    if totals.spent.num_seconds() > 0 {
+        items.push(format!("spent: {}", duration_str(totals.spent)));
+    }
+    let numeric = c.custom_fields.iter().filter(|f| is_numeric_kind(&f.kind));
+    for (field, v) in numeric.zip(totals.custom.iter()) {
+        let Some(v) = v else {
+            continue;
+        };
+        let v = match field.kind.as_str() {
+            "duration" => duration_str(Duration::seconds(*v as i64)),
+            "float" => format!("{v:.2}"),
+            _ => format!("{v}"),
+        };
+        items.push(format!("{}: {v}", field.title));
+    }
+    writeln!(stdout, "{}= {}", "  ".repeat(level + 1), items.join(", "))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn print_groups(
     stdout: &mut StandardStream,
     tasks: &todo::TaskSlice,
-    selected: &todo::IDSlice,
-    updated: &todo::ChangedSlice,
+    ids: &todo::IDSlice,
     conf: &conf::Conf,
     flist: &[String],
     widths: &[usize],
+    level: usize,
+    header_level: usize,
 ) -> io::Result<()> {
     let c = &conf.fmt;
-    let group = match &c.group {
-        None => String::new(),
-        Some(g) => g.clone(),
-    };
-    let groups = select_uniq_values(tasks, selected, true, c);
-    let print_groups = groups.len() > 1;
-    for g in groups {
-        if print_groups {
-            let group_name = if g.is_empty() { "[Empty]" } else { g.as_str() };
-            match group.as_str() {
-                "prj" | "project" => writeln!(stdout, "+{group_name}")?,
-                "ctx" | "context" => writeln!(stdout, "@{group_name}")?,
-                "hash" | "hashtag" => writeln!(stdout, "#{group_name}")?,
-                _ => writeln!(stdout, "{group_name}")?,
-            };
+    if level >= c.group.len() {
+        for id in ids {
+            print_line(stdout, &tasks[*id], *id + 1, conf, flist, widths)?;
         }
-        for (i, id) in selected.iter().enumerate() {
-            let print = updated.is_empty() || (i < updated.len() && updated[i]);
-            let print = print && (*id < tasks.len());
-            if print {
-                if print_groups {
-                    let vals = values_of_field(&tasks[*id], &group);
-                    if vals.contains(&g) {
-                        print_line(stdout, &tasks[*id], *id + 1, conf, flist, widths)?;
-                    }
-                } else {
-                    print_line(stdout, &tasks[*id], *id + 1, conf, flist, widths)?;
-                }
-            }
+        return Ok(());
+    }
+    let field = &c.group[level];
+    let groups = select_groups(tasks, ids, field, c);
+    // If all todos have the same value, group header is useless
+    if groups.len() <= 1 {
+        return print_groups(stdout, tasks, ids, conf, flist, widths, level + 1, header_level);
+    }
+    for (name, g_ids, totals) in groups {
+        print_group_header(stdout, field, &name, header_level)?;
+        print_groups(stdout, tasks, &g_ids, conf, flist, widths, level + 1, header_level + 1)?;
+        if c.group_totals {
+            print_group_totals(stdout, &totals, header_level, c)?;
         }
     }
     Ok(())
 }
 
+fn print_body_selected(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    selected: &todo::IDSlice,
+    updated: &todo::ChangedSlice,
+    conf: &conf::Conf,
+    flist: &[String],
+    widths: &[usize],
+) -> io::Result<()> {
+    let mut ids = Vec::new();
+    for (i, id) in selected.iter().enumerate() {
+        let print = updated.is_empty() || (i < updated.len() && updated[i]);
+        if print && *id < tasks.len() {
+            ids.push(*id);
+        }
+    }
+    print_groups(stdout, tasks, &ids, conf, flist, widths, 0, 0)
+}
+
 fn print_body_all(
     stdout: &mut StandardStream,
     tasks: &todo::TaskSlice,
@@ -1473,7 +1651,7 @@ fn print_body_all(
     widths: &[usize],
 ) -> io::Result<()> {
     let c = &conf.fmt;
-    let group = match &c.group {
+    let group = match c.group.first() {
         None => String::new(),
         Some(g) => g.clone(),
     };
@@ -1630,7 +1808,7 @@ pub(crate) fn parse_subj(subj: &str) -> Vec<&str> {
     parts
 }
 
-fn cut_string(s: &str, max_width: usize) -> &str {
+pub(crate) fn cut_string(s: &str, max_width: usize) -> &str {
     let w = s.width();
     if max_width == 0 || w <= max_width {
         return s;
@@ -1685,4 +1863,106 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn select_groups_test() {
+        struct Test {
+            field: &'static str,
+            sort: Option<&'static str>,
+            rev: bool,
+            res: Vec<(&'static str, Vec<usize>)>,
+        }
+        let today = NaiveDate::from_ymd_opt(2020, 3, 10).unwrap();
+        let tasks: todo::TaskVec = vec![
+            todotxt::Task::parse("first +b @home est:3", today),
+            todotxt::Task::parse("second +a @work est:1", today),
+            todotxt::Task::parse("third +b est:2.5", today),
+            todotxt::Task::parse("fourth +c +b", today),
+        ];
+        let mut c = Conf::default();
+        c.custom_fields.push(CustomField {
+            name: "est".to_string(),
+            title: "Estimate".to_string(),
+            kind: "float".to_string(),
+            ..Default::default()
+        });
+        let ids: todo::IDVec = vec![0, 1, 2, 3];
+        let tests: Vec<Test> = vec![
+            Test {
+                field: "prj",
+                sort: None,
+                rev: false,
+                res: vec![("a", vec![1]), ("b", vec![0, 2, 3]), ("c", vec![3])],
+            },
+            Test {
+                field: "ctx",
+                sort: None,
+                rev: false,
+                res: vec![("home", vec![0]), ("work", vec![1]), ("", vec![2, 3])],
+            },
+            Test {
+                field: "prj",
+                sort: Some("count"),
+                rev: true,
+                res: vec![("b", vec![0, 2, 3]), ("c", vec![3]), ("a", vec![1])],
+            },
+            Test {
+                field: "ctx",
+                sort: Some("est"),
+                rev: false,
+                res: vec![("work", vec![1]), ("home", vec![0]), ("", vec![2, 3])],
+            },
+        ];
+        for test in tests.iter() {
+            c.group_sort = test.sort.map(|s| s.to_string());
+            c.group_sort_rev = test.rev;
+            let groups = select_groups(&tasks, &ids, test.field, &c);
+            let res: Vec<(&str, Vec<usize>)> = groups.iter().map(|g| (g.0.as_str(), g.1.clone())).collect();
+            assert_eq!(test.res, res, "{} sorted by {:?}", test.field, test.sort);
+        }
+
+        let totals = group_totals(&tasks, &ids, &c);
+        assert_eq!(totals.count, 4);
+        assert_eq!(totals.custom, vec![Some(6.5)]);
+    }
+
+    #[test]
+    fn matches_date_test() {
+        struct Test {
+            range: FmtSpec,
+            value: &'static str,
+            res: bool,
+        }
+        let today = NaiveDate::from_ymd_opt(2022, 12, 31).unwrap();
+        let range = |b: &str, e: &str| FmtSpec::Range(b.to_string(), e.to_string());
+        let tests: Vec<Test> = vec![
+            Test { range: range("", "yesterday"), value: "2022-12-30", res: true },
+            Test { range: range("", "yesterday"), value: "2022-12-31", res: false },
+            Test { range: range("today", "soon"), value: "2023-01-07", res: true },
+            Test { range: range("today", "soon"), value: "2023-01-09", res: false },
+            Test { range: range("2023-01-01", "2023-01-31"), value: "2023-01-09", res: true },
+            Test { range: range("2023-01-01", ""), value: "2022-12-31", res: false },
+            Test { range: FmtSpec::List(vec!["2023-01-09".to_string()]), value: "2023-01-09", res: true },
+            Test { range: FmtSpec::List(vec!["tomorrow".to_string()]), value: "2023-01-01", res: true },
+        ];
+        for test in tests.iter() {
+            let rule = FmtRule { range: test.range.clone(), color: ColorSpec::new() };
+            let res = rule.matches_on(test.value, "date", today).is_some();
+            assert_eq!(test.res, res, "{:?} - {}", test.range, test.value);
+        }
+    }
+
+    #[test]
+    fn is_group_sort_key_test() {
+        let mut c = Conf::default();
+        for (name, kind) in [("est", "float"), ("who", "string")] {
+            c.custom_fields.push(CustomField { name: name.to_string(), kind: kind.to_string(), ..Default::default() });
+        }
+        for key in ["name", "count", "overdue", "spent", "est"] {
+            assert!(is_group_sort_key(key, &c), "{key}");
+        }
+        for key in ["who", "size", "Count", ""] {
+            assert!(!is_group_sort_key(key, &c), "{key}");
+        }
+    }
 }
@@ -0,0 +1,1450 @@
+use getopts::Options;
+
+// How an option takes a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arg {
+    No,
+    Required,
+    Optional,
+}
+
+// The part of the help an option is shown in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Group {
+    // Options that can be used with any command
+    General,
+    // Options that are used without a command, e.g. `--version`
+    Standalone,
+    Filter,
+    Modify,
+    Output,
+    // Options of a single command, they are shown only in the help of the command
+    Command,
+}
+
+// A command-line option
+pub struct OptDef {
+    pub short: &'static str,
+    pub long: &'static str,
+    pub arg: Arg,
+    pub hint: &'static str,
+    pub group: Group,
+    pub desc: &'static str,
+}
+
+// A command with its aliases, the option groups it accepts, and examples
+pub struct Command {
+    // The first name is the main one, the rest are aliases
+    pub names: &'static [&'static str],
+    pub args: &'static str,
+    pub desc: &'static str,
+    pub groups: &'static [Group],
+    // Options of the group `Command` that the command uses
+    pub options: &'static [&'static str],
+    pub examples: &'static [(&'static str, &'static str)],
+}
+
+// Free short options: BCDEFGIJKLMNOPQRSTUVWXYZbdgjlmnopquxyz"
+pub const OPTIONS: &[OptDef] = &[
+    OptDef { short: "h", long: "help", arg: Arg::No, hint: "", group: Group::General, desc: "Show this help" },
+    OptDef {
+        short: "a",
+        long: "all",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Filter,
+        desc: "Select all todos including completed ones",
+    },
+    OptDef {
+        short: "A",
+        long: "only-completed",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Filter,
+        desc: "Select only completed todos",
+    },
+    OptDef { short: "t", long: "active", arg: Arg::No, hint: "", group: Group::Filter, desc: "Only active records" },
+    OptDef {
+        short: "",
+        long: "dry-run",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Dry run: do not change todo list, only show which todos would be changed",
+    },
+    OptDef {
+        short: "v",
+        long: "verbose",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Display extra information (used file names etc)",
+    },
+    OptDef {
+        short: "e",
+        long: "regex",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Filter,
+        desc: "Treat the search string as regular expression. By default simple case-insensitive substring search is done",
+    },
+    OptDef {
+        short: "",
+        long: "wipe",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "'Clean' command deletes todos instead of moving them to 'done.txt'",
+    },
+    OptDef {
+        short: "s",
+        long: "sort",
+        arg: Arg::Optional,
+        hint: "FIELD1,FIELD2",
+        group: Group::Output,
+        desc: "Sort todos by the list of fields(if the list is empty todos are sorted by their priority)",
+    },
+    OptDef {
+        short: "",
+        long: "sort-rev",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Reverse todo list after sorting. It works only if the option 'sort' is set",
+    },
+    OptDef {
+        short: "",
+        long: "group",
+        arg: Arg::Required,
+        hint: "FIELD1,FIELD2",
+        group: Group::Output,
+        desc: "a list of field names that are used to group the list of tasks. Every next field makes a nested group",
+    },
+    OptDef {
+        short: "",
+        long: "group-sort",
+        arg: Arg::Required,
+        hint: "name | count | overdue | spent | FIELD",
+        group: Group::Output,
+        desc: "Sort groups by an aggregate value instead of group names: count, overdue, spent, or a name of numeric custom field",
+    },
+    OptDef {
+        short: "",
+        long: "group-sort-rev",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Reverse the order of groups",
+    },
+    OptDef {
+        short: "",
+        long: "group-totals",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Display subtotals after every group: number of todos, overdue todos, spent time, and sums of numeric custom fields",
+    },
+    OptDef {
+        short: "",
+        long: "rec",
+        arg: Arg::Required,
+        hint: "any | none",
+        group: Group::Filter,
+        desc: "Select only recurrent(any) or non-recurrent(none) todos",
+    },
+    OptDef {
+        short: "",
+        long: "due",
+        arg: Arg::Required,
+        hint: "any | none | today| tomorrow | yesterday | soon | 'range'",
+        group: Group::Filter,
+        desc: "Select records without due date(none), with any due date(any), overdue todos(overdue), today's todos(today), tomorrow's ones(tomorrow), or which are due in a few days(soon)",
+    },
+    OptDef {
+        short: "",
+        long: "created",
+        arg: Arg::Required,
+        hint: "any | none | today| tomorrow | yesterday | soon | 'range'",
+        group: Group::Filter,
+        desc: "Select records without creation date(none), with any creation date(any), created within a date range",
+    },
+    OptDef {
+        short: "",
+        long: "completed",
+        arg: Arg::Required,
+        hint: "any | none | today| tomorrow | yesterday | soon | 'range'",
+        group: Group::Filter,
+        desc: "Select records without completion date(none), with any completion date(any), completed within a date range",
+    },
+    OptDef {
+        short: "",
+        long: "older-than",
+        arg: Arg::Required,
+        hint: "NUMBER[d|w|m|y]",
+        group: Group::Filter,
+        desc: "Select completed records that were completed at least the given number of days/weeks/months/years ago. Useful for `clean` and `remove`",
+    },
+    OptDef {
+        short: "",
+        long: "threshold",
+        arg: Arg::Required,
+        hint: "any | none | today| tomorrow | yesterday | soon | 'range'",
+        group: Group::Filter,
+        desc: "Select records without threshold date(none), with any threshold date(any)",
+    },
+    OptDef {
+        short: "",
+        long: "project",
+        arg: Arg::Required,
+        hint: "PROJECT1,PROJECT2",
+        group: Group::Filter,
+        desc: "Comma-separated list of projects. Select records that have any of them. Special values: 'none' - select records with no project, and 'any' - select records that have at least one project. Basic pattern matching supported: '*ab' - project ends with 'ab', 'ab*' - project starts with 'ab', '*ab*' - project contains 'ab'",
+    },
+    OptDef {
+        short: "",
+        long: "context",
+        arg: Arg::Required,
+        hint: "CONTEXT1,CONTEXT2",
+        group: Group::Filter,
+        desc: "Comma-separated list of contexts. Select records that have any of them. Special values: 'none' - select records with no context, and 'any' - select records that have at least one context. Basic pattern matching supported: '*ab' - context ends with 'ab', 'ab*' - context starts with 'ab', '*ab*' - context contains 'ab'",
+    },
+    OptDef {
+        short: "",
+        long: "tag",
+        arg: Arg::Required,
+        hint: "TAG1,TAG2",
+        group: Group::Filter,
+        desc: "Comma-separated list of tags. Select records that have any of them. Special values: 'none' - select records with no tag, and 'any' - select records that have at least one tag. Basic pattern matching supported: '*ab' - tag ends with 'ab', 'ab*' - tag starts with 'ab', '*ab*' - tag contains 'ab'",
+    },
+    OptDef {
+        short: "",
+        long: "pri",
+        arg: Arg::Required,
+        hint: "none | any | a | b+ | c-",
+        group: Group::Filter,
+        desc: "Select todos without priority(none), with any priority(any), with a given priority, with a priority equal to or higher/lower than the given priority",
+    },
+    OptDef {
+        short: "",
+        long: "hashtag",
+        arg: Arg::Required,
+        hint: "HASHTAG1,HASHTAG2 | any | none",
+        group: Group::Filter,
+        desc: "Select only todos with any of hashtags",
+    },
+    OptDef {
+        short: "",
+        long: "set-pri",
+        arg: Arg::Required,
+        hint: "none | A-Z | + | -",
+        group: Group::Modify,
+        desc: "Change priority for selected todos: remove priority, set exact priority, increase or decrease it",
+    },
+    OptDef {
+        short: "",
+        long: "set-rec",
+        arg: Arg::Required,
+        hint: "none | 1m | 15d",
+        group: Group::Modify,
+        desc: "Change recurrence for selected todos: remove recurrence, or set a new one",
+    },
+    OptDef {
+        short: "",
+        long: "set-due",
+        arg: Arg::Required,
+        hint: "none | YYYY-MM-DD",
+        group: Group::Modify,
+        desc: "Change due date for selected todos: remove due date or set a new one",
+    },
+    OptDef {
+        short: "",
+        long: "set-time",
+        arg: Arg::Required,
+        hint: "none | TIME | TIME_RANGE",
+        group: Group::Modify,
+        desc: "Change time tag selected todos: remove time tag or set a new value for it",
+    },
+    OptDef {
+        short: "",
+        long: "set-threshold",
+        arg: Arg::Required,
+        hint: "none | YYYY-MM-DD",
+        group: Group::Modify,
+        desc: "Change threshold date for selected todos: remove threshold date or set a new one",
+    },
+    OptDef {
+        short: "",
+        long: "set-proj",
+        arg: Arg::Required,
+        hint: "PROJ1,PROJ2",
+        group: Group::Modify,
+        desc: "Add projects to selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "set-ctx",
+        arg: Arg::Required,
+        hint: "CTX1,CTX2",
+        group: Group::Modify,
+        desc: "Add contexts to selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "set-tag",
+        arg: Arg::Required,
+        hint: "TAG1:VALUE1,TAG2:VALUE2",
+        group: Group::Modify,
+        desc: "Add tags to selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "set-hashtag",
+        arg: Arg::Required,
+        hint: "HASHTAG1,HASHTAG2",
+        group: Group::Modify,
+        desc: "Add hashtags to selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "del-proj",
+        arg: Arg::Required,
+        hint: "PROJ",
+        group: Group::Modify,
+        desc: "Remove projects from selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "del-ctx",
+        arg: Arg::Required,
+        hint: "CTX",
+        group: Group::Modify,
+        desc: "Remove contexts from selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "del-tag",
+        arg: Arg::Required,
+        hint: "TAG1,TAG2",
+        group: Group::Modify,
+        desc: "Remove tags from selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "del-hashtag",
+        arg: Arg::Required,
+        hint: "HASHTAG1,HASHTAG2",
+        group: Group::Modify,
+        desc: "Remove hashtags from selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "repl-proj",
+        arg: Arg::Required,
+        hint: "PROJ1+PROJECT1,PROJ2+PROJECT2",
+        group: Group::Modify,
+        desc: "Replace projects for selected todos: a list of comma separated pairs of old and new project values. Old value and new value are separated with '+'",
+    },
+    OptDef {
+        short: "",
+        long: "repl-ctx",
+        arg: Arg::Required,
+        hint: "CT1@CTX,CT2@ANOTHER",
+        group: Group::Modify,
+        desc: "Replace contexts for selected todos: a list of comma separated pairs of old and new context values. Old value and new value are separated with '@'",
+    },
+    OptDef {
+        short: "",
+        long: "repl-hashtag",
+        arg: Arg::Required,
+        hint: "HASHTAG1:NEW1,HASHTAG2:NEW2",
+        group: Group::Modify,
+        desc: "Replace hashtags for selected todos",
+    },
+    OptDef {
+        short: "",
+        long: "short",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Show only ID, priority and subject for todos",
+    },
+    OptDef {
+        short: "",
+        long: "wrap",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Word wrap a long subject within subject column",
+    },
+    OptDef {
+        short: "w",
+        long: "width",
+        arg: Arg::Required,
+        hint: "WIDTH",
+        group: Group::Output,
+        desc: "Set terminal width. The application detects terminal width automatically but it is possible to limit the output width manually",
+    },
+    OptDef {
+        short: "",
+        long: "human",
+        arg: Arg::Optional,
+        hint: "empty value or FIELD1,FIELD2",
+        group: Group::Output,
+        desc: "Show relative date(for due and threshold dates) instead of default YYYY-MM-DD. Examples: 'today', '4d overdue', or 'in 2m'",
+    },
+    OptDef {
+        short: "",
+        long: "compact",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Show relative date in compact mode: without 'in' or 'overdue', overdue and future dates are distinguished by their colors",
+    },
+    OptDef {
+        short: "",
+        long: "fields",
+        arg: Arg::Required,
+        hint: "FIELD2,FIELD1",
+        group: Group::Output,
+        desc: "Set custom list of fields to display(ID is always visible). The list defines visibility of fields but not their order. The order cannot be changed",
+    },
+    OptDef {
+        short: "",
+        long: "local",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Use todo from the current working directory. It is the default mode. But if you set environment variable or modified config, the option can be used to override those values and use todo.txt from current working directory",
+    },
+    OptDef { short: "", long: "no-colors", arg: Arg::No, hint: "", group: Group::Output, desc: "Disable all colors" },
+    OptDef {
+        short: "",
+        long: "done",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Filter,
+        desc: "Use file of completed todos - done.txt. In this mode the only available command is 'list'",
+    },
+    OptDef {
+        short: "",
+        long: "reopen",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "For `unarchive`: mark restored todos incomplete",
+    },
+    OptDef {
+        short: "",
+        long: "with-archive",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Filter,
+        desc: "Load todos from both todo.txt and done.txt. Archived todos get IDs after all active ones and are marked in column 'A'. `undone` moves an archived todo back to todo.txt",
+    },
+    OptDef { short: "", long: "version", arg: Arg::No, hint: "", group: Group::Standalone, desc: "Show TTDL version" },
+    OptDef {
+        short: "c",
+        long: "config",
+        arg: Arg::Required,
+        hint: "CONF FILE PATH",
+        group: Group::General,
+        desc: "Path to configuration file",
+    },
+    OptDef {
+        short: "",
+        long: "todo-file",
+        arg: Arg::Required,
+        hint: "TODO FILE PATH",
+        group: Group::General,
+        desc: "Path to file with todos (if it is directory 'todo.txt' is added automatically) ",
+    },
+    OptDef {
+        short: "",
+        long: "done-file",
+        arg: Arg::Required,
+        hint: "DONE FILE PATH",
+        group: Group::General,
+        desc: "Path to file with archived todos (if it is directory 'done.txt' is added automatically, if it contains only file name then the directory is the same as for todo.txt). The file name may contain wildcards `*` and `?` to read a few archives at once, e.g. 'done-2026-*.txt'",
+    },
+    OptDef { short: "", long: "strict", arg: Arg::No, hint: "", group: Group::General, desc: "Enable strict mode" },
+    OptDef { short: "", long: "hidden", arg: Arg::No, hint: "", group: Group::Filter, desc: "Include hidden tasks" },
+    OptDef {
+        short: "",
+        long: "calendar",
+        arg: Arg::Required,
+        hint: "[FIELD1[,FIELD2]=][+][NUMBER][TYPE]",
+        group: Group::Command,
+        desc: "Display a calendar with dates highlighted if any todo is due on that date(foreground color). Today is highlighted with background color, Default values for `NUMBER` is `1` and for `TYPE` is `d`(days). Valid values for type are `d`(days), `w`(weeks), and `m`(months). Prepending plus sign shows the selected interval starting from today, not from Monday or first day of the month. Optional list of date fields to check(`due`, `t`, `created`, `finished`, or any tag with a date value) can be set before `=`. Default field is `due`",
+    },
+    OptDef {
+        short: "",
+        long: "syntax",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Enable keyword highlights when printing subject",
+    },
+    OptDef {
+        short: "",
+        long: "no-syntax",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Disable keyword highlights when printing subject",
+    },
+    OptDef {
+        short: "",
+        long: "markdown",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Enable Markdown formatting in subject text",
+    },
+    OptDef {
+        short: "",
+        long: "no-markdown",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Disable Markdown formatting in subject text",
+    },
+    OptDef {
+        short: "",
+        long: "confirm",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Ask before removing todos, wiping completed ones, or changing many todos with `edit` and `done`",
+    },
+    OptDef {
+        short: "",
+        long: "no-confirm",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Do not ask for confirmation even if it is enabled in the config",
+    },
+    OptDef {
+        short: "",
+        long: "keep-empty",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "do not remove empty todos when cleaning up(archiving) the list",
+    },
+    OptDef {
+        short: "",
+        long: "clean-subject",
+        arg: Arg::Required,
+        hint: "no|none|nothing|tags|all|yes. 'yes' is an alias for all, 'no|none|nothing' are the synonyms",
+        group: Group::Output,
+        desc: "hide the given items in a subject column when printing a task list. Items are hidden only if their corresponding columns are visible",
+    },
+    OptDef {
+        short: "",
+        long: "auto-hide-cols",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Hide columns that do not have values",
+    },
+    OptDef {
+        short: "",
+        long: "auto-show-cols",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Show all columns that have at least one value",
+    },
+    OptDef {
+        short: "",
+        long: "always-hide-cols",
+        arg: Arg::Required,
+        hint: "FIELD1,FIELD2",
+        group: Group::Output,
+        desc: "Comma-separated list of tags that TTDL never show in a separate column. E.g, 'prj,due' or 'pri,created,customtag'",
+    },
+    OptDef {
+        short: "",
+        long: "priority-on-done",
+        arg: Arg::Required,
+        hint: "VALUE",
+        group: Group::Command,
+        desc: "what to do with priority on task completion: keep - no special action(default behavior), move - place priority after completion date, tag - convert priority to a tag 'pri:', erase - remove priority. Note that in all modes, except `erase`, the operation is reversible and on task uncompleting, the task gets its priority back",
+    },
+    OptDef {
+        short: "",
+        long: "archive-rotation",
+        arg: Arg::Required,
+        hint: "none|monthly|yearly",
+        group: Group::Command,
+        desc: "how `clean` splits completed todos between archive files: none - all todos go to done.txt(default behavior), monthly - to done-YYYY-MM.txt, yearly - to done-YYYY.txt, by completion date. When rotation is enabled, archived todos are read from done.txt and all rotated files",
+    },
+    OptDef {
+        short: "",
+        long: "add-completion-date-always",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "When task is finished, always add completion date, regardless of whether or not creation date is present",
+    },
+    OptDef {
+        short: "k",
+        long: "keep-tags",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "in edit mode a new subject replaces regular text of the todo, everything else(tags, priority etc) is taken from the old and appended to the new subject. A convenient way to replace just text and keep all the tags without typing the tags again",
+    },
+    OptDef {
+        short: "",
+        long: "watch",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "Keep running and show the list again every time a task list changes or a new day starts",
+    },
+    OptDef {
+        short: "i",
+        long: "interactive",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "Open an external edit to modify all filtered tasks. If the task list is modified inside an editor, the old tasks will be removed and new ones will be added to the end of the task list. If you do not change anything or save an empty file, the edit operation will be canceled. To set editor, change config.global.editor option or set EDITOR environment variable.",
+    },
+    OptDef {
+        short: "",
+        long: "init",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Standalone,
+        desc: "create a default configuration file in user's configuration directory if the configuration file does not exist yet",
+    },
+    OptDef {
+        short: "",
+        long: "init-local",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Standalone,
+        desc: "create a default configuration file in the current working directory if the configuration file does not exist yet",
+    },
+    OptDef {
+        short: "",
+        long: "stdin",
+        arg: Arg::No,
+        hint: "",
+        group: Group::General,
+        desc: "Read new or replacement task content from standard input",
+    },
+    OptDef {
+        short: "H",
+        long: "no-headers",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Output,
+        desc: "Do not show headers and footers",
+    },
+    OptDef {
+        short: "",
+        long: "max",
+        arg: Arg::Required,
+        hint: "NUMBER",
+        group: Group::Output,
+        desc: "Set maximum number of todos to display",
+    },
+    OptDef {
+        short: "",
+        long: "filter-tag",
+        arg: Arg::Required,
+        hint: "TAG1=RANGE1;TAG2=RANGE2. Deprecated, use 'filter' instead",
+        group: Group::Filter,
+        desc: "Custom filter by user-defined tag values",
+    },
+    OptDef {
+        short: "f",
+        long: "filter",
+        arg: Arg::Required,
+        hint: "TAG1=RANGE1;TAG2=RANGE2",
+        group: Group::Filter,
+        desc: "Custom filter by user-defined tag values",
+    },
+    OptDef {
+        short: "",
+        long: "where",
+        arg: Arg::Required,
+        hint: "EXPRESSION",
+        group: Group::Filter,
+        desc: "Filter todos with a boolean expression: conditions on fields and tags combined with AND, OR, NOT, and parentheses",
+    },
+    OptDef {
+        short: "",
+        long: "update-threshold",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Modify,
+        desc: "Update threshold in addition to changing due date when a task is postponed",
+    },
+    OptDef {
+        short: "",
+        long: "hide-fields",
+        arg: Arg::Required,
+        hint: "FIELD1,FIELD2",
+        group: Group::Output,
+        desc: "Comma-separated list of fields to hide in both columns and subject",
+    },
+    OptDef {
+        short: "",
+        long: "date-format",
+        arg: Arg::Required,
+        hint: "default|human|short|FORMAT-STRING",
+        group: Group::Output,
+        desc: "Display format for dates. Format string includes %Y, %M, %D and more",
+    },
+    OptDef {
+        short: "",
+        long: "on",
+        arg: Arg::Required,
+        hint: "FIELD1[,FIELD2][=][DATE]",
+        group: Group::Command,
+        desc: "Set the date, default is today, for an agenda to show and optional field to use for checking date (default is 'due')",
+    },
+    OptDef {
+        short: "",
+        long: "time",
+        arg: Arg::Required,
+        hint: "[START][-][END]",
+        group: Group::Command,
+        desc: "Show an agenda for this time interval. Time is defined without a separator between hours and minutes, e.g, '930' means '9:30'",
+    },
+    OptDef {
+        short: "",
+        long: "slot",
+        arg: Arg::Required,
+        hint: "[SLOT_SIZE]",
+        group: Group::Command,
+        desc: "A slot size for an agenda in minutes. Default is 30 minutes",
+    },
+    OptDef {
+        short: "",
+        long: "hide-all-day",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "Do not show section 'All day' in agenda",
+    },
+    OptDef {
+        short: "",
+        long: "no-hide-all-day",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "Show section 'All day' in agenda if it is off in the configuration file",
+    },
+    OptDef {
+        short: "r",
+        long: "resolution",
+        arg: Arg::Required,
+        hint: "[MESSAGE]",
+        group: Group::Command,
+        desc: "A resolution is appended to every task that was completed",
+    },
+    OptDef {
+        short: "",
+        long: "src",
+        arg: Arg::Required,
+        hint: "[MESSAGE]",
+        group: Group::Filter,
+        desc: "Select a source task list for adding a task or for filtering (used only in multi-file mode",
+    },
+    OptDef {
+        short: "",
+        long: "list-sources",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Standalone,
+        desc: "Show info about all task lists",
+    },
+    OptDef {
+        short: "",
+        long: "to",
+        arg: Arg::Required,
+        hint: "NAME",
+        group: Group::Command,
+        desc: "For `mv`: a name or a number of the task list to move todos to",
+    },
+    OptDef {
+        short: "",
+        long: "archived",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "For `mv`: move also archived todos that match the filter, except the ID range",
+    },
+    OptDef {
+        short: "",
+        long: "board-field",
+        arg: Arg::Required,
+        hint: "FIELD",
+        group: Group::Command,
+        desc: "A field which values are used as board columns: pri, prj, ctx, hashtag, or any tag. Default is `status` tag",
+    },
+    OptDef {
+        short: "",
+        long: "board-columns",
+        arg: Arg::Required,
+        hint: "COL1,COL2",
+        group: Group::Command,
+        desc: "A list of board columns displayed first, even if they are empty",
+    },
+    OptDef {
+        short: "",
+        long: "listen",
+        arg: Arg::Required,
+        hint: "ADDRESS:PORT",
+        group: Group::Command,
+        desc: "For `serve`: the address to listen on. Default is 127.0.0.1:8080",
+    },
+    OptDef {
+        short: "",
+        long: "length",
+        arg: Arg::Required,
+        hint: "DURATION",
+        group: Group::Command,
+        desc: "For `pomodoro`: the length of the pomodoro, e.g. `25m` or `1h`. Default is 25m",
+    },
+    OptDef {
+        short: "",
+        long: "break",
+        arg: Arg::Required,
+        hint: "DURATION",
+        group: Group::Command,
+        desc: "For `pomodoro`: the length of the break after the pomodoro. `0` skips the break. Default is 5m",
+    },
+    OptDef {
+        short: "",
+        long: "format",
+        arg: Arg::Required,
+        hint: "TEMPLATE",
+        group: Group::Command,
+        desc: "For `status`: the output template. Fields: {total}, {active}, {done}, {overdue}, {today}, {soon}, {recurrent}, {timer}, and {spent}. Default is \"{active} todos, {overdue} overdue, {today} due today\"",
+    },
+    OptDef {
+        short: "",
+        long: "json",
+        arg: Arg::No,
+        hint: "",
+        group: Group::Command,
+        desc: "For `status`: print JSON for a custom module of waybar: the text made by the template, a tooltip, and CSS classes `overdue`, `today`, and `timer`",
+    },
+];
+
+const FILTER_NOTES: &str = r#"    +project - select todos which are related to project "project"; if more than one project name is defined in command line, they are combined with OR;
+    @context - select todos which have context "project"; if more than one context is set, they are combined with OR;"#;
+
+const ID_NOTE: &str = "[ID or ID range] - ID is the order number of a todo starting from 1. The range is inclusive. It is OK to use non-existing IDs - all invalid IDs are skipped while processing the command";
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        names: &["list", "l", "ls"],
+        args: "[ID or ID range] [filter]",
+        desc: "list todos",
+        groups: &[Group::Filter, Group::Output],
+        options: &["calendar", "watch"],
+        examples: &[
+            (
+                "ttdl l -s=proj,pri",
+                "show all incomplete todos sorted by their project and by priority inside each project",
+            ),
+            ("ttdl l \"car*\"", "list all todos which have substring `car*` in their subject, project or context"),
+            (
+                "ttdl l \"car*\" -e",
+                "list all todos which have subject, project or context matched regular expression `car*`",
+            ),
+            ("ttdl l \"car\"", "list all todos which have substring `car` in their subject, project or context"),
+            ("ttdl l --max 10", "show only the first 10 incomplete todos"),
+            (
+                "ttdl l \"car*\" --max 5",
+                "list at most 5 todos which have substring `car*` in their subject, project or context",
+            ),
+            (
+                "ttdl l -s=proj,pri --max 5",
+                "show first 5 incomplete todos sorted by their project and by priority inside each project",
+            ),
+            ("ttdl l --pri=a", "show all incomplete todos with the highest priority A"),
+            ("ttdl l --due=soon --watch", "show todos that are due soon, and update the list when todos change"),
+            ("ttdl l --pri=b+", "show all incomplete todos with priority B and higher (only A and B in this case)"),
+            ("ttdl l +car +train", "show all incomplete todos which related either to `car` or to `train` projects"),
+            (
+                "ttdl l +my* @*tax",
+                "show all incomplete todos that have a project tag starts with `my` and a context ends with `tax`",
+            ),
+            ("ttdl l --due=tomorrow -a", "show all todos that are due tomorrow"),
+            (
+                "ttdl l --due=soon",
+                "show all incomplete todos which are due are due in less a few days, including overdue ones (the range is configurable and default value is 7 days)",
+            ),
+            ("ttdl l --due=overdue", "show all incomplete overdue todos"),
+            ("ttdl l --due=today -a", "show all todos that are due today"),
+            (
+                "ttdl l --due=today..tomorrow -a",
+                "show all todos that are overdue between today and tomorrow(inclusive)",
+            ),
+            ("ttdl l --due=today:tomorrow -a", "the same as above"),
+            (
+                "ttdl l --completed=-1w..today -a",
+                "show all todos that were done the last week(literally: from a week ago through today)",
+            ),
+            (
+                "ttdl l --created=-3d..",
+                "show active todos that are created 3 days ago or earlier(3 days old and younger)",
+            ),
+            (
+                "ttdl l --due=..2d..",
+                "show active todos that are either overdue or their due date within 2 days from the current date",
+            ),
+            (
+                "ttdl l --where=\"+house and (due < today or pri = A)\"",
+                "show todos of project `house` that are either overdue or have the highest priority",
+            ),
+            (
+                "ttdl l -a +myproj @ui @rest",
+                "show both incomplete and done todos related to project 'myproj' which contains either 'ui' or 'rest' context",
+            ),
+            ("ttdl l --calendar=m", "show calendar for this month and mark dates that have one or more due todos"),
+            (
+                "ttdl l --calendar=2w",
+                "show calendar for this and next week and mark dates that have one or more due todos",
+            ),
+            ("ttdl l --calendar=+1m", "show calendar for 30 days(one month) starting with today"),
+            ("ttdl l --calendar=+-10d", "show calendar for 10 days in the past(one month) ending with today"),
+            (
+                "ttdl l --calendar=due,t=m",
+                "show calendar for this month and mark dates that have due or threshold todos",
+            ),
+        ],
+    },
+    Command {
+        names: &["add", "a", "new"],
+        args: "SUBJECT",
+        desc: "add a new todo",
+        groups: &[],
+        options: &["src"],
+        examples: &[
+            (
+                "ttdl a \"send tax declaration +personal @finance @tax due:2018-04-01 rec:1y\"",
+                "add a new recurrent todo(yearly todo) with a due date first of April every year",
+            ),
+            ("ttdl a \"(A) send tax return docs due:2018-04-01\"", "add a new todo with the highest priority `A`"),
+        ],
+    },
+    Command {
+        names: &["done", "d", "complete", "close"],
+        args: "[ID or ID range] [filter]",
+        desc: "mark regular incomplete todos completed, pushes due date for recurrent todos to their next date",
+        groups: &[Group::Filter],
+        options: &["resolution", "priority-on-done", "add-completion-date-always"],
+        examples: &[("ttdl d 2-5", "mark todos with IDs from 2 through 5 done")],
+    },
+    Command {
+        names: &["undone", "u", "open"],
+        args: "[ID or ID range] [filter]",
+        desc: "remove finish date and completion mark from completed todos",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["clean", "c", "archive", "arc"],
+        args: "[ID or ID range] [filter]",
+        desc: "move all completed todos to `done.txt`. If option `--wipe` is set then completed todos are removed instead of moving",
+        groups: &[Group::Filter],
+        options: &["wipe", "keep-empty", "archive-rotation"],
+        examples: &[("ttdl clean --older-than=30d", "archive only todos completed 30 or more days ago")],
+    },
+    Command {
+        names: &["unarchive", "unarc"],
+        args: "[ID or ID range] [filter]",
+        desc: "move selected todos from `done.txt` back to `todo.txt`. IDs are the same as displayed by `ttdl --done list`. Use option `--reopen` to mark restored todos incomplete",
+        groups: &[Group::Filter],
+        options: &["reopen"],
+        examples: &[
+            ("ttdl unarchive 3 --reopen", "restore the third archived todo and mark it incomplete"),
+            ("ttdl unarchive +myproj --dry-run", "show which archived todos of project `myproj` would be restored"),
+        ],
+    },
+    Command {
+        names: &["remove", "rm"],
+        args: "[ID or ID range] [filter]",
+        desc: "delete selected todos. Warning: by default completed todos are not selected, so be careful",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[
+            ("ttdl rm 2-5", "delete incomplete todos with IDs from 2 thorough 5"),
+            ("ttdl rm 2-5 -a", "delete both done and incomplete todos with IDs from 2 through 5"),
+            (
+                "ttdl rm 2-5 -A",
+                "delete all done todos with IDs from 2 through 5. The command does the same as `ttdl clean 2-5 --wipe` does",
+            ),
+        ],
+    },
+    Command {
+        names: &["edit", "e"],
+        args: "[ID or ID range] [SUBJECT] [filter] [new values]",
+        desc: "modifies selected todos. Warninig: if you try to change a subject of a few todos, only the first todo would be changed. It is by design. Date-like tags `due` and `threshold` accept simple expressions",
+        groups: &[Group::Filter, Group::Modify],
+        options: &["keep-tags", "interactive"],
+        examples: &[
+            (
+                "ttdl e 2-5 \"new subject\"",
+                "only the first incomplete todo with ID between 2 and 5 changes its subject",
+            ),
+            (
+                "ttdl e +proj --repl-ctx=bug1010@bug1020",
+                "replace context `bug1010` with `bug1020` for all incomplete todos that related to project `proj`",
+            ),
+            (
+                "ttdl e @customer_acme --set-due=2018-12-31",
+                "set due date 2018-12-31 for all incomplete todos that has `customer_acme` context",
+            ),
+            (
+                "ttdl e @customer_acme --set-due=none",
+                "remove due date 2018-12-31 for all incomplete todos that has `customer_acme` context",
+            ),
+            (
+                "ttdl e --pri=none --set-pri=z",
+                "set the lowest priority for all incomplete todos which do not have a priority set",
+            ),
+            (
+                "ttdl e @bug1000 --set-pri=+",
+                "increase priority for all incomplete todos which have context `bug1000`, todos which did not have priority set get the lowest priority `z`",
+            ),
+            ("ttdl e 2 --set-due=t+1w", "set the due date a week later than the task threshold date"),
+            ("ttdl e 2 --set-due=due+2d", "push the due date by 2 days"),
+            (
+                "ttdl e 2 --set-due=limit+1d",
+                "takes task's tag `limit` as a date, adds 1 day and sets the result to the due date",
+            ),
+            ("ttdl e 2 --set-due=t+1w+3d", "push the due date by a week and a half or more accurate by 10 days"),
+            (
+                "ttdl e 2-5 -i",
+                "open an external editor of you choice the the incomplete todos with ID between 2 and 5 for interactive editing. After saving the changes and closing the editor, TTDL updates the task list",
+            ),
+        ],
+    },
+    Command {
+        names: &["append", "app"],
+        args: "[ID or ID range] TEXT [filter]",
+        desc: "adds a text to the end of todos",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["prepend", "prep"],
+        args: "[ID or ID range] TEXT [filter]",
+        desc: "inserts a text at the beginning of todos",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["start"],
+        args: "TODO_ID",
+        desc: "activates todo's timer",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["pomodoro"],
+        args: "TODO_ID [--length=DURATION] [--break=DURATION]",
+        desc: "starts todo's timer, counts down, and stops the timer at the end of the pomodoro. The number of completed pomodoros is kept in the tag `pomo`",
+        groups: &[Group::Filter],
+        options: &["length", "break"],
+        examples: &[
+            ("ttdl pomodoro 2", "work on todo 2 for 25 minutes, and then take a 5 minute break"),
+            (
+                "ttdl pomodoro 2 --length=50m --break=10m",
+                "work on todo 2 for 50 minutes, and then take a 10 minute break",
+            ),
+        ],
+    },
+    Command {
+        names: &["stop"],
+        args: "TODO_ID",
+        desc: "stops the timer for a todo and updates time spent for it",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["status"],
+        args: "[filter] [--format=TEMPLATE] [--json]",
+        desc: "print a one-line summary of todos for shell prompts and status bars",
+        groups: &[Group::Filter],
+        options: &["format", "json"],
+        examples: &[
+            ("ttdl status --format=\"{overdue}!{today}\"", "print the number of overdue todos and todos due today"),
+            ("ttdl status +work --json", "print the summary of project `work` for waybar"),
+        ],
+    },
+    Command {
+        names: &["stats"],
+        args: "[--short]",
+        desc: "shows todo list summary",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[
+            ("ttdl stats --short", "displays only the number of total, active, done, overdue, and recurrent todos"),
+            (
+                "ttdl stats",
+                "detailed view with additional grouping by project and displaying total time spent on each group",
+            ),
+        ],
+    },
+    Command {
+        names: &["postpone"],
+        args: "[ID or ID range] INTERVAL [filter]",
+        desc: "push due date of todos by the interval: a number and d/w/m/y without a space between them",
+        groups: &[Group::Filter],
+        options: &["update-threshold"],
+        examples: &[("ttdl postpone 2 1w", "push the due date of the todo with ID 2 by a week")],
+    },
+    Command {
+        names: &["listprojects", "listproj", "lp"],
+        args: "[filter]",
+        desc: "list all projects",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[
+            ("ttdl lp", "show alphabetically sorted list of all projects"),
+            ("ttdl lp +un*", "show projects starting with 'un'"),
+        ],
+    },
+    Command {
+        names: &["listcontexts", "listcon", "lc"],
+        args: "[filter]",
+        desc: "list all contexts",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[
+            ("ttdl lc", "show alphabetically sorted list of all contexts"),
+            ("ttdl lc @phon*", "show contexts starting with 'phon'"),
+        ],
+    },
+    Command {
+        names: &["listhashtags", "listhash", "lh"],
+        args: "[filter]",
+        desc: "list all hashtags",
+        groups: &[Group::Filter],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["agenda", "ag"],
+        args: "[filter]",
+        desc: "show a list of tasks for a single day as an agenda (i.e, dispalys a timetable and tasks in each time slot)",
+        groups: &[Group::Filter],
+        options: &["on", "time", "slot", "hide-all-day", "no-hide-all-day"],
+        examples: &[(
+            "ttdl agenda --hide-all-day",
+            "show agenda without footer with tasks that do not have any time slot defined",
+        )],
+    },
+    Command {
+        names: &["board", "kanban"],
+        args: "[filter]",
+        desc: "show todos as a board: columns side by side, every column contains todos with the same value of a field (by default, tag `status`)",
+        groups: &[Group::Filter, Group::Output],
+        options: &["board-field", "board-columns"],
+        examples: &[
+            (
+                "ttdl board --board-columns=todo,doing,done",
+                "show a board with three columns, todos without `status` tag are in the extra column `[Empty]`",
+            ),
+            ("ttdl board +myproj --board-field=ctx", "show todos of project `myproj` grouped by context"),
+        ],
+    },
+    Command {
+        names: &["move"],
+        args: "TODO_ID COLUMN",
+        desc: "move todos to another board column: set a new value of the board field (`-` removes the value)",
+        groups: &[Group::Filter],
+        options: &["board-field"],
+        examples: &[("ttdl move 2 doing", "replace the value of tag `status` of the todo with ID 2 with `doing`")],
+    },
+    Command {
+        names: &["mv", "transfer"],
+        args: "TODO_ID --to=NAME",
+        desc: "move todos to another task list (used only in multi-file mode)",
+        groups: &[Group::Filter],
+        options: &["to", "archived"],
+        examples: &[
+            ("ttdl mv 2-4 --to=work", "move todos with IDs from 2 through 4 to the task list `work`"),
+            (
+                "ttdl mv +myproj --to=2 --archived",
+                "move all incomplete and archived todos of project `myproj` to the second task list",
+            ),
+        ],
+    },
+    Command {
+        names: &["search", "find"],
+        args: "QUERY",
+        desc: "search active and archived todos of all sources and show them sorted by relevance. A word of the query may contain `*`, long words are matched with typos allowed. Archived todos are marked `arc` instead of ID",
+        groups: &[Group::Filter, Group::Output],
+        options: &[],
+        examples: &[
+            (
+                "ttdl search milk bread",
+                "show todos containing `milk` or `bread`, the todos containing both words go first",
+            ),
+            ("ttdl search rep* +work", "show todos of project `work` that contain words starting with `rep`"),
+        ],
+    },
+    Command {
+        names: &["sync"],
+        args: "",
+        desc: "commit changes of task lists to their git repositories, rebase them on top of the remote branch, and push them back. Conflicting todo.txt changes are merged the same way the command `merge` does. The remote is set by `git_remote` in the config(default is `origin`)",
+        groups: &[],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["merge"],
+        args: "BASE OURS THEIRS",
+        desc: "three-way merge of todo.txt files: the result is written to OURS, true conflicts are reported and make the command exit with code 1. Use `--dry-run` to print the result instead of writing it. It can be used as a git merge driver",
+        groups: &[],
+        options: &[],
+        examples: &[(
+            "git config merge.ttdl.driver \"ttdl merge %O %A %B\"",
+            "set TTDL as a merge driver, and then add `todo.txt merge=ttdl` to `.gitattributes`",
+        )],
+    },
+    Command {
+        names: &["serve"],
+        args: "[--listen=ADDRESS:PORT]",
+        desc: "run a local HTTP server with a JSON API to list, add, complete, edit, and archive todos",
+        groups: &[],
+        options: &["listen"],
+        examples: &[
+            ("ttdl serve --listen=127.0.0.1:9000", "start the server on port 9000"),
+            (
+                "curl 'http://127.0.0.1:9000/todos?project=car&sort=pri'",
+                "list todos of project `car` sorted by priority",
+            ),
+        ],
+    },
+    Command {
+        names: &["lsp"],
+        args: "",
+        desc: "run a language server over stdin and stdout for editing todo.txt files in an editor: diagnostics, completion, hover with relative dates, and code actions to complete and postpone todos",
+        groups: &[],
+        options: &[],
+        examples: &[],
+    },
+    Command {
+        names: &["completions"],
+        args: "bash | zsh | fish",
+        desc: "print a shell completion script. Besides commands and options, it completes IDs, projects, contexts, hashtags, tags, and task list names of the current task lists",
+        groups: &[],
+        options: &[],
+        examples: &[(
+            "ttdl completions bash > ~/.local/share/bash-completion/completions/ttdl",
+            "enable completion in bash",
+        )],
+    },
+    Command {
+        names: &["help"],
+        args: "[COMMAND]",
+        desc: "show the help: the list of commands, or the options and examples of a single command",
+        groups: &[],
+        options: &[],
+        examples: &[("ttdl help edit", "show options of the command `edit`")],
+    },
+    Command {
+        names: &["man"],
+        args: "",
+        desc: "print the manual page in roff format",
+        groups: &[],
+        options: &[],
+        examples: &[("ttdl man > ttdl.1", "create the manual page")],
+    },
+];
+
+// Returns all command names and their aliases
+pub fn command_names() -> Vec<&'static str> {
+    COMMANDS.iter().flat_map(|c| c.names.iter().copied()).collect()
+}
+
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.names.contains(&name))
+}
+
+fn add_option(opts: &mut Options, opt: &OptDef) {
+    match opt.arg {
+        Arg::No => opts.optflag(opt.short, opt.long, opt.desc),
+        Arg::Required => opts.optopt(opt.short, opt.long, opt.desc, opt.hint),
+        Arg::Optional => opts.optflagopt(opt.short, opt.long, opt.desc, opt.hint),
+    };
+}
+
+// Builds getopts options from the option list
+pub fn options() -> Options {
+    let mut opts = Options::new();
+    for opt in OPTIONS {
+        add_option(&mut opts, opt);
+    }
+    opts
+}
+
+// Returns true if the command accepts the option
+fn uses_option(cmd: &Command, opt: &OptDef) -> bool {
+    opt.group == Group::General || cmd.groups.contains(&opt.group) || cmd.options.contains(&opt.long)
+}
+
+fn command_title(cmd: &Command) -> String {
+    let names = cmd.names.join(" | ");
+    if cmd.args.is_empty() { names } else { format!("{names} {0}", cmd.args) }
+}
+
+fn group_title(group: Group) -> &'static str {
+    match group {
+        Group::General => "General options",
+        Group::Standalone => "Options used without a command",
+        Group::Filter => "Filter options",
+        Group::Modify => "Modifying options",
+        Group::Output => "Output options",
+        Group::Command => "Options of commands",
+    }
+}
+
+fn option_title(opt: &OptDef) -> String {
+    let mut names = Vec::new();
+    if !opt.short.is_empty() {
+        names.push(format!("-{0}", opt.short));
+    }
+    names.push(format!("--{0}", opt.long));
+    names.join(" | ")
+}
+
+fn print_examples(cmd: &Command, indent: &str) {
+    for (example, desc) in cmd.examples {
+        println!("{indent}`{example}` - {desc}");
+    }
+}
+
+// Prints all commands with examples, and names of options grouped by their purpose
+pub fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {program} command [ID or ID range] [subject] [filter] [new values] [extra options]");
+    print!("{}", opts.usage(&brief));
+    println!("\n\n{ID_NOTE}\n");
+    println!("Available commands:");
+    for cmd in COMMANDS {
+        println!("    {0} - {1}", command_title(cmd), cmd.desc);
+        print_examples(cmd, "        ");
+    }
+    println!("\nRun `{program} help COMMAND` to see options and examples of a single command.");
+    for group in [Group::Filter, Group::Modify, Group::Output, Group::General] {
+        let names: Vec<String> = OPTIONS.iter().filter(|o| o.group == group).map(option_title).collect();
+        println!("\n{0}:\n    {1}", group_title(group), names.join(", "));
+        if group == Group::Filter {
+            println!("{FILTER_NOTES}");
+        }
+    }
+}
+
+// Prints the description, options, and examples of a single command
+pub fn print_command_usage(program: &str, cmd: &Command) {
+    let mut opts = Options::new();
+    for opt in OPTIONS.iter().filter(|o| uses_option(cmd, o)) {
+        add_option(&mut opts, opt);
+    }
+    let brief = format!("Usage: {program} {0}\n\n{1}", command_title(cmd), cmd.desc);
+    print!("{}", opts.usage(&brief));
+    if cmd.groups.contains(&Group::Filter) {
+        println!("\n{FILTER_NOTES}");
+    }
+    if !cmd.examples.is_empty() {
+        println!("\nExamples:");
+        print_examples(cmd, "    ");
+    }
+}
+
+// Escapes text for roff: backslashes, dashes, and control characters at the beginning of a line
+fn roff_escape(s: &str) -> String {
+    let s = s.replace('\\', "\\e").replace('-', "\\-");
+    if s.starts_with(['.', '\'']) { format!("\\&{s}") } else { s }
+}
+
+fn roff_option(opt: &OptDef) -> String {
+    let mut names = Vec::new();
+    if !opt.short.is_empty() {
+        names.push(format!("\\fB\\-{0}\\fR", opt.short));
+    }
+    names.push(format!("\\fB\\-\\-{0}\\fR", roff_escape(opt.long)));
+    let mut title = names.join(", ");
+    match opt.arg {
+        Arg::No => {}
+        Arg::Required => title += &format!(" \\fI{0}\\fR", roff_escape(opt.hint)),
+        Arg::Optional => title += &format!(" [\\fI{0}\\fR]", roff_escape(opt.hint)),
+    }
+    format!(".TP\n{title}\n{0}\n", roff_escape(opt.desc))
+}
+
+// Returns the manual page in roff format
+pub fn man_page() -> String {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut s = format!(".TH TTDL 1 \"\" \"ttdl {version}\" \"User Commands\"\n");
+    s += ".SH NAME\nttdl \\- terminal todo list manager\n";
+    s += ".SH SYNOPSIS\n.B ttdl\n[\\fIcommand\\fR] [\\fIID or ID range\\fR] [\\fIsubject\\fR] [\\fIfilter\\fR] [\\fInew values\\fR] [\\fIoptions\\fR]\n";
+    s += ".SH DESCRIPTION\nTTDL manages todos kept in todo.txt format.\n";
+    s += "If the first argument is not a command, TTDL shows the todos when there are no arguments, and adds a new todo otherwise.\n";
+    s += &format!(".PP\n{0}\n", roff_escape(ID_NOTE));
+    s += ".SH COMMANDS\n";
+    for cmd in COMMANDS {
+        let names: Vec<String> = cmd.names.iter().map(|n| format!("\\fB{n}\\fR")).collect();
+        let args = if cmd.args.is_empty() { String::new() } else { format!(" \\fI{0}\\fR", roff_escape(cmd.args)) };
+        s += &format!(".TP\n{0}{args}\n{1}\n", names.join(", "), roff_escape(cmd.desc));
+        for (example, desc) in cmd.examples {
+            s += &format!(".br\n\\fB{0}\\fR \\- {1}\n", roff_escape(example), roff_escape(desc));
+        }
+    }
+    s += ".SH OPTIONS\n";
+    for group in [Group::General, Group::Standalone, Group::Filter, Group::Modify, Group::Output, Group::Command] {
+        s += &format!(".SS {0}\n", group_title(group));
+        for opt in OPTIONS.iter().filter(|o| o.group == group) {
+            s += &roff_option(opt);
+        }
+    }
+    s += ".SH FILES\n.TP\n\\fIttdl.toml\\fR\nThe configuration file. Run \\fBttdl \\-\\-init\\fR to create it.\n";
+    s += ".TP\n\\fItodo.txt\\fR, \\fIdone.txt\\fR\nActive and archived todos.\n";
+    s += ".SH SEE ALSO\nhttps://github.com/VladimirMarkelov/ttdl\n";
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_test() {
+        for (idx, opt) in OPTIONS.iter().enumerate() {
+            assert!(!OPTIONS[idx + 1..].iter().any(|o| o.long == opt.long), "duplicated option {0}", opt.long);
+            if opt.group == Group::Command {
+                assert!(COMMANDS.iter().any(|c| c.options.contains(&opt.long)), "unused option {0}", opt.long);
+            }
+        }
+        for cmd in COMMANDS {
+            for name in cmd.options {
+                assert!(OPTIONS.iter().any(|o| o.long == *name), "unknown option {name} of {0}", cmd.names[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn roff_escape_test() {
+        assert_eq!(roff_escape("--dry-run"), "\\-\\-dry\\-run");
+        assert_eq!(roff_escape(".hidden"), "\\&.hidden");
+        assert_eq!(roff_escape("a\\b"), "a\\eb");
+    }
+}
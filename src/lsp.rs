@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use chrono::{Duration, Local, NaiveDate};
+use json::JsonValue;
+use todo_lib::{todo, todotxt};
+
+use crate::conf;
+use crate::fmt;
+
+// Error code of JSON-RPC for unsupported requests
+const METHOD_NOT_FOUND: i32 = -32601;
+// Error code of JSON-RPC for messages that are not valid JSON
+const PARSE_ERROR: i32 = -32700;
+// Tags that every todo.txt file may use, in addition to tags found in the files
+const BUILTIN_TAGS: &[&str] = &["due", "t", "rec", "time", "spent", "id"];
+
+// A problem found in a todo. Columns are byte offsets in the line
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    line: usize,
+    start: usize,
+    end: usize,
+    message: String,
+}
+
+// Reads a message framed with `Content-Length` header. Returns None at the end of input.
+// A message that is not valid JSON is returned as an error with the raw text of the message.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Result<JsonValue, String>>> {
+    let mut size: Option<usize> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if size.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            size = value.trim().parse().ok();
+        }
+    }
+    let mut body = vec![0u8; size.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+    Ok(Some(json::parse(&body).map_err(|_| body.to_string())))
+}
+
+// Looks for the request ID in a message that is not valid JSON, e.g. in a truncated one
+fn broken_message_id(body: &str) -> Option<JsonValue> {
+    let rest = body[body.find("\"id\"")? + "\"id\"".len()..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if let Some(s) = rest.strip_prefix('"') {
+        s.find('"')? + 2
+    } else {
+        rest.find(|c: char| !c.is_ascii_digit() && c != '-').unwrap_or(rest.len())
+    };
+    json::parse(&rest[..end]).ok().filter(|id| id.is_number() || id.is_string())
+}
+
+fn write_message(out: &mut impl Write, msg: &JsonValue) -> io::Result<()> {
+    let body = msg.dump();
+    write!(out, "Content-Length: {0}\r\n\r\n{body}", body.len())?;
+    out.flush()
+}
+
+// LSP counts columns in UTF-16 code units
+fn to_utf16(line: &str, byte: usize) -> usize {
+    line[..byte.min(line.len())].encode_utf16().count()
+}
+
+fn from_utf16(line: &str, col: usize) -> usize {
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= col {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+// Returns byte offsets of all words of the line
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut res = Vec::new();
+    let mut start = None;
+    for (idx, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(st)) => {
+                res.push((st, &line[st..idx]));
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+    if let Some(st) = start {
+        res.push((st, &line[st..]));
+    }
+    res
+}
+
+// Returns the byte offset where the word under the cursor starts, and the word
+fn word_at(line: &str, pos: usize) -> (usize, &str) {
+    let start = line[..pos].rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let end = line[pos..].find(char::is_whitespace).map(|i| i + pos).unwrap_or(line.len());
+    (start, &line[start..end])
+}
+
+fn check_tag(name: &str, value: &str, conf: &conf::Conf, today: NaiveDate) -> Option<String> {
+    match name {
+        todotxt::DUE_TAG | todotxt::THR_TAG => match todotxt::parse_date(value, today) {
+            Ok(_) => None,
+            Err(e) => Some(format!("Invalid date: {e}")),
+        },
+        todotxt::REC_TAG => match todotxt::Recurrence::from_str(value) {
+            Ok(_) => None,
+            Err(_) => Some(format!("Invalid recurrence '{value}'")),
+        },
+        "time" => conf::validate_time_range(value).err().map(|e| format!("Invalid time range: {e}")),
+        _ => {
+            let field = conf.fmt.custom_fields.iter().find(|f| f.name == name)?;
+            if field.is_valid(value) {
+                None
+            } else {
+                Some(format!("Invalid value '{value}' of field '{name}': {0} expected", field.kind))
+            }
+        }
+    }
+}
+
+fn diagnostics(text: &str, conf: &conf::Conf, today: NaiveDate) -> Vec<Diagnostic> {
+    let mut res = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        for (start, word) in words(line) {
+            let Some((name, value)) = todotxt::split_tag(word) else {
+                continue;
+            };
+            if let Some(message) = check_tag(name, value, conf, today) {
+                res.push(Diagnostic { line: line_no, start, end: start + word.len(), message });
+            }
+        }
+    }
+    res
+}
+
+fn range_json(line_no: usize, line: &str, start: usize, end: usize) -> JsonValue {
+    json::object! {
+        "start" => json::object! { "line" => line_no, "character" => to_utf16(line, start) },
+        "end" => json::object! { "line" => line_no, "character" => to_utf16(line, end) },
+    }
+}
+
+// Returns the variants that complete the beginning of a word
+fn completion_items(word: &str, tasks: &[todotxt::Task], conf: &conf::Conf) -> Vec<String> {
+    let mut items: Vec<String> = Vec::new();
+    let mut add = |s: String| {
+        if !items.contains(&s) {
+            items.push(s);
+        }
+    };
+    if let Some(prefix) = word.chars().next().filter(|c| ['+', '@', '#'].contains(c)) {
+        for task in tasks {
+            let list = match prefix {
+                '+' => &task.projects,
+                '@' => &task.contexts,
+                _ => &task.hashtags,
+            };
+            for item in list {
+                add(format!("{prefix}{item}"));
+            }
+        }
+    } else if let Some((name, _)) = word.split_once(':') {
+        for task in tasks {
+            if let Some(value) = task.tags.get(name) {
+                add(format!("{name}:{value}"));
+            }
+        }
+    } else {
+        for tag in BUILTIN_TAGS {
+            add(format!("{tag}:"));
+        }
+        for field in &conf.fmt.custom_fields {
+            add(format!("{0}:", field.name));
+        }
+        for task in tasks {
+            for tag in task.tags.keys() {
+                add(format!("{tag}:"));
+            }
+        }
+    }
+    items.retain(|item| item.starts_with(word) && item != word);
+    items.sort();
+    items
+}
+
+// Returns a description of a date under the cursor, e.g. "due: 2024-05-01 (in 3 days)"
+fn hover_text(word: &str, conf: &conf::Conf, today: NaiveDate) -> Option<String> {
+    let (name, value) = match todotxt::split_tag(word) {
+        Some((name, value)) => (Some(name), value),
+        None => (None, word),
+    };
+    // A recurrence is not a date here
+    if !value.contains('-') {
+        return None;
+    }
+    let dt = todotxt::parse_date(value, today).ok()?;
+    let (rel, _) = fmt::format_relative_date(dt, conf.fmt.compact);
+    let date = todotxt::format_date(dt);
+    Some(match name {
+        Some(name) => format!("{name}: {date} ({rel})"),
+        None => format!("{date} ({rel})"),
+    })
+}
+
+// Returns the text that replaces the line after completing the todo. A recurrent todo adds a new line
+fn done_text(line: &str, conf: &conf::Conf, today: NaiveDate) -> Option<String> {
+    let task = todotxt::Task::parse(line, today);
+    if task.finished || line.trim().is_empty() {
+        return None;
+    }
+    let completion_date_mode = match conf.add_completion_date_always {
+        true => todotxt::CompletionDateMode::AlwaysSet,
+        false => todotxt::CompletionDateMode::WhenCreationDateIsPresent,
+    };
+    let completion_config =
+        todotxt::CompletionConfig { completion_mode: conf.priority_on_done(&task), completion_date_mode };
+    let mut tasks = vec![task];
+    todo::done(&mut tasks, Some(&vec![0]), completion_config);
+    let lines: Vec<String> = tasks.iter().map(|t| t.to_string()).collect();
+    Some(lines.join("\n"))
+}
+
+// Returns the text of the line with the due date moved by `days`. Threshold date moves together
+// with the due date if `postpone_threshold` is enabled
+fn postpone_text(line: &str, days: i64, conf: &conf::Conf, today: NaiveDate) -> Option<String> {
+    let mut task = todotxt::Task::parse(line, today);
+    if task.finished {
+        return None;
+    }
+    let due = task.due_date?;
+    let new_due = due + Duration::days(days);
+    task.update_tag_with_value(todotxt::DUE_TAG, &todotxt::format_date(new_due));
+    task.due_date = Some(new_due);
+    if conf.postpone_threshold
+        && let Some(thr) = task.threshold_date
+    {
+        let new_thr = thr + Duration::days(days);
+        task.update_tag_with_value(todotxt::THR_TAG, &todotxt::format_date(new_thr));
+        task.threshold_date = Some(new_thr);
+    }
+    Some(task.to_string())
+}
+
+struct Server<'a> {
+    conf: &'a conf::Conf,
+    // Todos of task lists from the config: they are used for completion
+    tasks: Vec<todotxt::Task>,
+    docs: HashMap<String, String>,
+}
+
+impl Server<'_> {
+    fn line(&self, params: &JsonValue) -> Option<(usize, &str)> {
+        let text = self.docs.get(params["textDocument"]["uri"].as_str()?)?;
+        let line_no = params["position"]["line"].as_usize().or(params["range"]["start"]["line"].as_usize())?;
+        Some((line_no, text.lines().nth(line_no).unwrap_or("")))
+    }
+
+    fn publish(&self, out: &mut impl Write, uri: &str) -> io::Result<()> {
+        let mut list = JsonValue::new_array();
+        if let Some(text) = self.docs.get(uri) {
+            let today = Local::now().date_naive();
+            let lines: Vec<&str> = text.lines().collect();
+            for d in diagnostics(text, self.conf, today) {
+                let _ = list.push(json::object! {
+                    "range" => range_json(d.line, lines[d.line], d.start, d.end),
+                    "severity" => 1,
+                    "source" => "ttdl",
+                    "message" => d.message,
+                });
+            }
+        }
+        let msg = json::object! {
+            "jsonrpc" => "2.0",
+            "method" => "textDocument/publishDiagnostics",
+            "params" => json::object! { "uri" => uri, "diagnostics" => list },
+        };
+        write_message(out, &msg)
+    }
+
+    fn completion(&self, params: &JsonValue) -> JsonValue {
+        let Some((line_no, line)) = self.line(params) else {
+            return JsonValue::Null;
+        };
+        let pos = from_utf16(line, params["position"]["character"].as_usize().unwrap_or(0));
+        let (start, word) = word_at(line, pos);
+        let word = &word[..pos - start];
+        let today = Local::now().date_naive();
+        let mut tasks: Vec<todotxt::Task> = self.tasks.clone();
+        for text in self.docs.values() {
+            tasks.extend(text.lines().map(|l| todotxt::Task::parse(l, today)));
+        }
+        let mut items = JsonValue::new_array();
+        for item in completion_items(word, &tasks, self.conf) {
+            let _ = items.push(json::object! {
+                "label" => item.clone(),
+                "textEdit" => json::object! { "range" => range_json(line_no, line, start, pos), "newText" => item },
+            });
+        }
+        items
+    }
+
+    fn hover(&self, params: &JsonValue) -> JsonValue {
+        let Some((line_no, line)) = self.line(params) else {
+            return JsonValue::Null;
+        };
+        let pos = from_utf16(line, params["position"]["character"].as_usize().unwrap_or(0));
+        let (start, word) = word_at(line, pos);
+        match hover_text(word, self.conf, Local::now().date_naive()) {
+            Some(text) => json::object! {
+                "contents" => text,
+                "range" => range_json(line_no, line, start, start + word.len()),
+            },
+            None => JsonValue::Null,
+        }
+    }
+
+    fn code_actions(&self, params: &JsonValue) -> JsonValue {
+        let mut actions = JsonValue::new_array();
+        let (Some(uri), Some((line_no, line))) = (params["textDocument"]["uri"].as_str(), self.line(params)) else {
+            return actions;
+        };
+        let today = Local::now().date_naive();
+        let variants = [
+            ("Mark done", done_text(line, self.conf, today)),
+            ("Postpone by 1 day", postpone_text(line, 1, self.conf, today)),
+            ("Postpone by 1 week", postpone_text(line, 7, self.conf, today)),
+        ];
+        for (title, text) in variants {
+            let Some(text) = text else {
+                continue;
+            };
+            let edit = json::object! { "range" => range_json(line_no, line, 0, line.len()), "newText" => text };
+            let mut changes = JsonValue::new_object();
+            changes[uri] = json::array![edit];
+            let _ = actions.push(json::object! {
+                "title" => title,
+                "kind" => "quickfix",
+                "edit" => json::object! { "changes" => changes },
+            });
+        }
+        actions
+    }
+
+    // Processes a message. Returns false when the client asks to exit
+    fn handle(&mut self, out: &mut impl Write, msg: &JsonValue) -> io::Result<bool> {
+        let method = msg["method"].as_str().unwrap_or("");
+        let params = &msg["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+        let result = match method {
+            "initialize" => json::object! {
+                "capabilities" => json::object! {
+                    "textDocumentSync" => 1,
+                    "completionProvider" => json::object! { "triggerCharacters" => json::array!["+", "@", "#", ":"] },
+                    "hoverProvider" => true,
+                    "codeActionProvider" => true,
+                },
+                "serverInfo" => json::object! { "name" => "ttdl", "version" => env!("CARGO_PKG_VERSION") },
+            },
+            "shutdown" => JsonValue::Null,
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                self.docs.insert(uri.clone(), text);
+                return self.publish(out, &uri).map(|_| true);
+            }
+            "textDocument/didChange" => {
+                // The server asks for full text sync, so the last change is the whole document
+                let changes = &params["contentChanges"];
+                if let Some(text) = changes[changes.len().saturating_sub(1)]["text"].as_str() {
+                    self.docs.insert(uri.clone(), text.to_string());
+                }
+                return self.publish(out, &uri).map(|_| true);
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                return self.publish(out, &uri).map(|_| true);
+            }
+            "textDocument/completion" => self.completion(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/codeAction" => self.code_actions(params),
+            _ => {
+                // Notifications without a handler are ignored
+                if !msg["id"].is_null() {
+                    let resp = json::object! {
+                        "jsonrpc" => "2.0",
+                        "id" => msg["id"].clone(),
+                        "error" => json::object! {
+                            "code" => METHOD_NOT_FOUND,
+                            "message" => format!("Method '{method}' is not supported"),
+                        },
+                    };
+                    write_message(out, &resp)?;
+                }
+                return Ok(true);
+            }
+        };
+        let resp = json::object! { "jsonrpc" => "2.0", "id" => msg["id"].clone(), "result" => result };
+        write_message(out, &resp)?;
+        Ok(true)
+    }
+}
+
+// Runs a language server over stdin and stdout until the client sends `exit`
+pub fn run(conf: &conf::Conf, tasks: Vec<todotxt::Task>) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut server = Server { conf, tasks, docs: HashMap::new() };
+    while let Some(msg) = read_message(&mut reader)? {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(body) => {
+                // A broken notification is skipped: there is no one to report the error to
+                if let Some(id) = broken_message_id(&body) {
+                    let resp = json::object! {
+                        "jsonrpc" => "2.0",
+                        "id" => id,
+                        "error" => json::object! { "code" => PARSE_ERROR, "message" => "Parse error" },
+                    };
+                    write_message(&mut out, &resp)?;
+                }
+                continue;
+            }
+        };
+        if !server.handle(&mut out, &msg)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_message_test() {
+        let raw = "Content-Length: 17\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{\"method\":\"exit\"}";
+        let msg = read_message(&mut raw.as_bytes()).unwrap().unwrap().unwrap();
+        assert_eq!(msg["method"], "exit");
+        assert!(read_message(&mut "".as_bytes()).unwrap().is_none());
+
+        // A broken message does not stop reading the next ones
+        let raw = "Content-Length: 5\r\n\r\n{\"id\"Content-Length: 17\r\n\r\n{\"method\":\"exit\"}";
+        let mut reader = raw.as_bytes();
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), Err("{\"id\"".to_string()));
+        assert_eq!(read_message(&mut reader).unwrap().unwrap().unwrap()["method"], "exit");
+    }
+
+    #[test]
+    fn broken_message_id_test() {
+        struct Test {
+            body: &'static str,
+            res: Option<JsonValue>,
+        }
+        let tests: Vec<Test> = vec![
+            Test { body: "{\"jsonrpc\":\"2.0\",\"id\": 12,\"method\":\"textDocument/hover\",", res: Some(12.into()) },
+            Test { body: "{\"id\":\"req-1\",\"method\":", res: Some("req-1".into()) },
+            Test { body: "{\"method\":\"textDocument/didOpen\",\"params\":", res: None },
+            Test { body: "{\"id\":", res: None },
+            Test { body: "{\"id\":\"unclosed", res: None },
+        ];
+        for test in tests.iter() {
+            assert_eq!(test.res, broken_message_id(test.body), "{}", test.body);
+        }
+    }
+
+    #[test]
+    fn utf16_test() {
+        let line = "тест 😀 due:2024-01-01";
+        let byte = line.find("due").unwrap();
+        assert_eq!(to_utf16(line, byte), 8);
+        assert_eq!(from_utf16(line, 8), byte);
+        assert_eq!(from_utf16(line, 100), line.len());
+    }
+
+    #[test]
+    fn diagnostics_test() {
+        let mut conf = conf::Conf::default();
+        conf.fmt.custom_fields.push(fmt::CustomField {
+            name: "size".to_string(),
+            kind: "bytes".to_string(),
+            ..Default::default()
+        });
+        let today = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let text =
+            "call mom due:2024-13-01\nx done rec:abc\nmeet time:18:00-17:00 size:10k\nok due:2024-05-02 size:big";
+        let res: Vec<(usize, usize, usize)> =
+            diagnostics(text, &conf, today).iter().map(|d| (d.line, d.start, d.end)).collect();
+        assert_eq!(res, vec![(0, 9, 23), (1, 7, 14), (2, 5, 21), (3, 18, 26)]);
+    }
+
+    #[test]
+    fn completion_items_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let conf = conf::Conf::default();
+        let tasks: Vec<todotxt::Task> = ["a +car @home", "b +cat +dog est:3 due:2024-05-02", "c +car est:5"]
+            .iter()
+            .map(|l| todotxt::Task::parse(l, today))
+            .collect();
+        assert_eq!(completion_items("+ca", &tasks, &conf), vec!["+car", "+cat"]);
+        assert_eq!(completion_items("@", &tasks, &conf), vec!["@home"]);
+        assert_eq!(completion_items("est:", &tasks, &conf), vec!["est:3", "est:5"]);
+        assert_eq!(completion_items("e", &tasks, &conf), vec!["est:"]);
+    }
+
+    #[test]
+    fn code_action_text_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let conf = conf::Conf::default();
+        assert_eq!(postpone_text("call due:2024-05-01", 7, &conf, today), Some("call due:2024-05-08".to_string()));
+        assert_eq!(postpone_text("call", 7, &conf, today), None);
+        assert_eq!(done_text("x call", &conf, today), None);
+        assert!(done_text("call", &conf, today).is_some_and(|s| s.starts_with("x ")));
+    }
+}
@@ -2,21 +2,37 @@
 extern crate serde_derive;
 
 mod agenda;
+mod archive;
+mod board;
 mod cal;
 mod colauto;
+mod completion;
 mod conf;
+mod diff;
+mod edit;
+mod expr;
 mod fmt;
+mod help;
+mod lsp;
 #[cfg(feature = "markdown")]
 mod md;
+mod merge;
+mod pomodoro;
+mod search;
+mod serve;
 mod stats;
+mod status;
 mod subj_clean;
 mod tml;
+mod vcs;
+mod wildcard;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::env;
 use std::fs::{File, read_to_string};
 use std::hash::Hasher;
 use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, exit};
 use std::str::FromStr;
 
@@ -35,6 +51,8 @@ const TASK_HIDDEN_OFF: &str = "0";
 const TASK_HIDDEN_FLD: &str = "h";
 const COMPLETE_TASK: &str = "completed";
 const UNCOMPLETE_TASK: &str = "uncompleted";
+// How often `list --watch` checks task lists for changes
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 type FnDoneUndone =
     fn(tasks: &mut Vec<todotxt::Task>, ids: Option<&todo::IDVec>, mode: todotxt::CompletionConfig) -> todo::ChangedVec;
@@ -46,7 +64,11 @@ fn task_is_hidden(task: &todotxt::Task) -> bool {
     }
 }
 
-fn is_filter_empty(flt: &tfilter::Conf) -> bool {
+fn is_filter_empty(conf: &conf::Conf) -> bool {
+    if conf.where_expr.is_some() {
+        return false;
+    }
+    let flt = &conf.flt;
     if flt.regex.is_some() || flt.due.is_some() || flt.thr.is_some() || flt.rec.is_some() {
         return false;
     }
@@ -83,6 +105,10 @@ fn filter_tasks(tasks: &[todotxt::Task], c: &conf::Conf) -> todo::IDVec {
     if !c.show_hidden {
         todos.retain(|&id| !task_is_hidden(&tasks[id]));
     }
+    if let Some(e) = &c.where_expr {
+        let today = chrono::Local::now().date_naive();
+        todos.retain(|&id| e.matches(&tasks[id], id, today));
+    }
     todos
 }
 
@@ -97,6 +123,124 @@ fn calculate_updated(v: &todo::ChangedSlice) -> u32 {
     cnt
 }
 
+// Calls `f` for every todo with the completion settings of the task list the todo belongs to
+fn apply_completion(
+    tasks: &mut todo::TaskVec,
+    ids: &todo::IDSlice,
+    c: &conf::Conf,
+    f: FnDoneUndone,
+) -> todo::ChangedVec {
+    let completion_date_mode = match c.add_completion_date_always {
+        true => todotxt::CompletionDateMode::AlwaysSet,
+        false => todotxt::CompletionDateMode::WhenCreationDateIsPresent,
+    };
+    let modes: Vec<todotxt::CompletionMode> = ids.iter().map(|&id| c.priority_on_done(&tasks[id])).collect();
+    let mut updated = vec![false; ids.len()];
+    let mut processed = vec![false; ids.len()];
+    for idx in 0..ids.len() {
+        if processed[idx] {
+            continue;
+        }
+        let mode = modes[idx];
+        let positions: Vec<usize> = (idx..ids.len()).filter(|&i| !processed[i] && modes[i] == mode).collect();
+        let group: todo::IDVec = positions.iter().map(|&i| ids[i]).collect();
+        let completion_config = CompletionConfig { completion_mode: mode, completion_date_mode };
+        let res = f(tasks, Some(&group), completion_config);
+        for (pos, upd) in positions.iter().zip(res.iter()) {
+            updated[*pos] = *upd;
+            processed[*pos] = true;
+        }
+    }
+    updated
+}
+
+// Excludes todos of read-only task lists from the selection
+fn skip_readonly(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    todos: &mut todo::IDVec,
+    c: &conf::Conf,
+) -> io::Result<()> {
+    let total = todos.len();
+    todos.retain(|&id| !c.is_readonly(&tasks[id]));
+    if todos.len() != total {
+        writeln!(stdout, "Skipped {0} todos of read-only task lists", total - todos.len())?;
+    }
+    Ok(())
+}
+
+// Returns true if the command must refuse to modify all todos when the filter is empty.
+// In confirm mode the user is asked instead.
+fn refuse_all(c: &conf::Conf) -> bool {
+    is_filter_empty(c) && (!c.confirm || c.dry)
+}
+
+// The number of todos a bulk command may change without asking for confirmation
+fn bulk_count(c: &conf::Conf) -> usize {
+    if is_filter_empty(c) { 0 } else { c.confirm_count }
+}
+
+// Prints the question and reads an answer from stdin
+fn ask(stdout: &mut StandardStream, question: &str) -> io::Result<String> {
+    write!(stdout, "{question}")?;
+    stdout.flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+// In confirm mode, shows the todos the command is going to change and asks whether to continue.
+// `new` contains the todos after the change. The answer `i` lets the user choose todos one by one,
+// and `todos` keeps only the chosen ones. Returns false if the command must be cancelled.
+fn confirm_todos(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    todos: &mut todo::IDVec,
+    new: Option<&todo::TaskSlice>,
+    action: &str,
+    min_count: usize,
+    c: &conf::Conf,
+) -> io::Result<bool> {
+    if !c.confirm || c.dry || todos.len() <= min_count {
+        return Ok(true);
+    }
+    let (cols, widths) = cols_with_width(tasks, todos, c);
+    writeln!(stdout, "Todos to be {action}:")?;
+    fmt::print_header(stdout, &c.fmt, &cols, &widths)?;
+    fmt::print_todos(stdout, tasks, todos, &[], c, &cols, &widths, false)?;
+    if let Some(new) = new {
+        writeln!(stdout, "\nReplace with:")?;
+        let updated = vec![true; todos.len()];
+        fmt::print_todos(stdout, new, todos, &updated, c, &cols, &widths, true)?;
+    }
+    fmt::print_footer(stdout, tasks, todos, &[], &c.fmt, &cols, &widths)?;
+    let answer = ask(stdout, &format!("Apply to {0} todos? [y/N/i - choose one by one]: ", todos.len()))?;
+    match answer.as_str() {
+        "y" | "yes" => return Ok(true),
+        "i" => {}
+        _ => {
+            writeln!(stdout, "Cancelled")?;
+            return Ok(false);
+        }
+    }
+    let mut chosen = Vec::new();
+    for (idx, &id) in todos.iter().enumerate() {
+        fmt::print_body_single(stdout, tasks, id, id + 1, c, &cols, &widths)?;
+        if let Some(new) = new {
+            fmt::print_body_single(stdout, new, idx, id + 1, c, &cols, &widths)?;
+        }
+        if matches!(ask(stdout, "Apply? [y/N]: ")?.as_str(), "y" | "yes") {
+            chosen.push(id);
+        }
+    }
+    *todos = chosen;
+    if todos.is_empty() {
+        writeln!(stdout, "No todo chosen")?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 fn process_tasks(
     stdout: &mut StandardStream,
     tasks: &mut todo::TaskVec,
@@ -104,20 +248,22 @@ fn process_tasks(
     action: &str,
     f: FnDoneUndone,
 ) -> io::Result<(bool, todo::IDVec, todo::ChangedVec)> {
-    let todos = filter_tasks(tasks, c);
-
-    let completion_config = CompletionConfig {
-        completion_mode: c.priority_on_done,
-        completion_date_mode: match c.add_completion_date_always {
-            true => todotxt::CompletionDateMode::AlwaysSet,
-            false => todotxt::CompletionDateMode::WhenCreationDateIsPresent,
-        },
-    };
+    let mut todos = filter_tasks(tasks, c);
+    skip_readonly(stdout, tasks, &mut todos, c)?;
+    if action == COMPLETE_TASK && c.confirm && !c.dry {
+        let mut clones = todo::clone_tasks(tasks, &todos);
+        let ids: todo::IDVec = (0..clones.len()).collect();
+        apply_completion(&mut clones, &ids, c, f);
+        if !confirm_todos(stdout, tasks, &mut todos, Some(&clones), action, bulk_count(c), c)? {
+            return Ok((false, Vec::new(), Vec::new()));
+        }
+    }
 
     if c.dry {
         let mut clones = todo::clone_tasks(tasks, &todos);
         let old_len = clones.len();
-        let updated = f(&mut clones, None, completion_config);
+        let ids: todo::IDVec = (0..clones.len()).collect();
+        let updated = apply_completion(&mut clones, &ids, c, f);
         let updated_cnt = calculate_updated(&updated);
 
         if updated_cnt == 0 {
@@ -153,7 +299,7 @@ fn process_tasks(
         Ok((false, Vec::new(), Vec::new()))
     } else {
         let old_len = tasks.len();
-        let updated = f(tasks, Some(&todos), completion_config);
+        let updated = apply_completion(tasks, &todos, c, f);
         let updated_cnt = calculate_updated(&updated);
 
         if updated_cnt == 0 {
@@ -191,41 +337,59 @@ fn process_tasks(
     }
 }
 
-fn task_add(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &mut conf::Conf) -> io::Result<()> {
+// Appends default projects and contexts of a task list that the subject does not contain yet
+fn add_source_tags(subj: &str, tlist: &conf::Source, now: NaiveDate) -> String {
+    let task = todotxt::Task::parse(subj, now);
+    let mut subj = subj.to_string();
+    for prj in &tlist.projects {
+        if !task.projects.iter().any(|p| p.eq_ignore_ascii_case(prj)) {
+            subj += &format!(" +{prj}");
+        }
+    }
+    for ctx in &tlist.contexts {
+        if !task.contexts.iter().any(|c| c.eq_ignore_ascii_case(ctx)) {
+            subj += &format!(" @{ctx}");
+        }
+    }
+    subj
+}
+
+// Chooses a task list for a new todo and prepares its subject: adds default projects and contexts
+// of the task list and converts date expressions. Returns the task list ID and the subject
+fn prepare_new_todo(conf: &mut conf::Conf) -> Result<(usize, String), String> {
     let source_id = match conf.selected_task_list() {
-        None => {
-            if conf.source.is_empty() {
-                eprintln!(
-                    "Failed to choose a task list for adding a new task. Either pass it in command line with `--src` or mark one of task list as a default one"
-                );
-            } else {
-                eprintln!("Cannot find the todo list '{0}'", conf.source);
-            }
-            std::process::exit(1);
+        None if conf.source.is_empty() => {
+            return Err("Failed to choose a task list for adding a new task. Either pass it in command line with `--src` or mark one of task list as a default one".to_string());
         }
+        None => return Err(format!("Cannot find the todo list '{0}'", conf.source)),
         Some(idx) => idx,
     };
-    let subj = match &conf.todo.subject {
-        None => {
-            eprintln!("Subject is empty");
-            return Ok(());
-        }
-        Some(s) => s.clone(),
+    let tlist = conf.task_lists[source_id].clone();
+    if tlist.readonly {
+        return Err(format!("Cannot add a todo to the read-only task list '{0}'", tlist.name));
+    }
+    let Some(subj) = conf.todo.subject.clone() else {
+        return Err("Subject is empty".to_string());
     };
     let now = chrono::Local::now().date_naive();
-    let mut tag_list = date_expr::TaskTagList::from_str(&subj, now);
-    let soon = conf.fmt.colors.soon_days;
-    let subj = match date_expr::calculate_main_tags(now, &mut tag_list, soon) {
+    let subj = add_source_tags(&subj, &tlist, now);
+    if let Some(auto_date) = tlist.creation_date_auto {
+        conf.todo.auto_create_date = auto_date;
+    }
+    let subj = convert_date_exprs(&subj, conf)?;
+    conf.todo.subject = Some(subj.clone());
+    Ok((source_id, subj))
+}
+
+fn task_add(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &mut conf::Conf) -> io::Result<()> {
+    let (source_id, subj) = match prepare_new_todo(conf) {
+        Ok(res) => res,
         Err(e) => {
-            eprintln!("{e:?}");
+            eprintln!("{e}");
             std::process::exit(1);
         }
-        Ok(changed) => match changed {
-            false => subj,
-            true => date_expr::update_tags_in_str(&tag_list, &subj),
-        },
     };
-    conf.todo.subject = Some(subj.clone());
+    let now = chrono::Local::now().date_naive();
 
     if conf.dry {
         let mut t = todotxt::Task::parse(&subj, now);
@@ -275,6 +439,10 @@ fn build_col_list(tasks: &todo::TaskSlice, ids: &todo::IDSlice, conf: &conf::Con
     if !conf.always_hide_columns.is_empty() {
         cols.retain(|x| !conf.always_hide_columns.iter().any(|it| it == x));
     }
+    // Archived todos are marked in the column right after ID
+    if conf.archived_from.is_some() && !cols.iter().any(|it| it == "arc") {
+        cols.insert(0, "arc".to_string());
+    }
     let id_exists = cols.iter().any(|it| it == "id");
     if !id_exists {
         cols.push("id".to_string());
@@ -311,19 +479,84 @@ fn task_list(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::
     err
 }
 
+// Loads todos the same way as for a single run of `list`
+fn load_watched_tasks(conf: &mut conf::Conf) -> Result<todo::TaskVec, String> {
+    let mut tasks = load_task_lists(conf)?;
+    if conf.with_archive {
+        let mut archived = load_done_lists(conf)?;
+        conf.archived_from = Some(tasks.len());
+        tasks.append(&mut archived);
+    }
+    conf.fmt.max = tasks.len();
+    Ok(tasks)
+}
+
+// The stamp of all files that `load_watched_tasks` reads, including every archive file if
+// archives are rotated
+fn watched_files_stamp(conf: &conf::Conf) -> String {
+    let mut files = Vec::new();
+    for tlist in &conf.task_lists {
+        if !conf.use_done {
+            files.push(tlist.todo_file.clone());
+        }
+        if conf.use_done || conf.with_archive {
+            files.append(&mut archive::archive_paths(&tlist.done_file, conf.archive_rotation));
+        }
+    }
+    let files: Vec<&Path> = files.iter().map(|f| f.as_path()).collect();
+    serve::files_stamp(&files)
+}
+
+// Shows the list and shows it again after clearing the screen every time a task list changes
+// or a new day starts. Files are polled because their editors often replace them instead of writing
+fn task_list_watch(stdout: &mut StandardStream, tasks: todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
+    let mut conf = conf.clone();
+    let mut tasks = tasks;
+    let mut stamp = watched_files_stamp(&conf);
+    let mut today = chrono::Local::now().date_naive();
+    loop {
+        write!(stdout, "\x1b[2J\x1b[H")?;
+        if conf.calendar.is_none() {
+            task_list(stdout, &tasks, &conf)?;
+        } else {
+            task_list_calendar(stdout, &tasks, &conf)?;
+        }
+        stdout.flush()?;
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let new_stamp = watched_files_stamp(&conf);
+            let new_today = chrono::Local::now().date_naive();
+            if new_stamp != stamp || new_today != today {
+                stamp = new_stamp;
+                today = new_today;
+                break;
+            }
+        }
+        match load_watched_tasks(&mut conf) {
+            Ok(t) => tasks = t,
+            // The file may be in the middle of saving: keep the old list until the next change
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
 fn fill_calendar(
     first_date: NaiveDate,
     last_date: NaiveDate,
     tasks: &todo::TaskSlice,
     selected: &todo::IDSlice,
-) -> HashMap<NaiveDate, u32> {
-    let mut res = HashMap::new();
+    fields: &[String],
+    today: NaiveDate,
+) -> cal::DayCounter {
+    let mut res = cal::DayCounter::new();
     for id in selected.iter() {
-        if let Some(dt) = tasks[*id].due_date {
-            if dt < first_date || dt > last_date {
-                continue;
+        for (idx, field) in fields.iter().enumerate() {
+            if let Some(dt) = cal::task_field_date(&tasks[*id], field, today) {
+                if dt < first_date || dt > last_date {
+                    continue;
+                }
+                res.entry(dt).or_default().push((*id, idx));
             }
-            *res.entry(dt).or_insert(0) += 1;
         }
     }
     res
@@ -342,7 +575,7 @@ fn print_calendar_body(
     today: NaiveDate,
     start_date: NaiveDate,
     end_date: NaiveDate,
-    counter: &HashMap<NaiveDate, u32>,
+    counter: &cal::DayCounter,
     conf: &conf::Conf,
 ) -> io::Result<()> {
     let (w, _) = term_size::dimensions().unwrap_or((0, 0));
@@ -350,14 +583,15 @@ fn print_calendar_body(
         eprintln!("Failed to detect terminal dimensions");
         return Ok(());
     }
-    let mut cp = CalPrinter::new(start_date, end_date, w as u16);
+    let mut cp = CalPrinter::new(start_date, end_date, w as u16, &conf.calendar_fields);
     loop {
         let done = cp.print_next_line(stdout, counter, today, conf)?;
         if done {
             break;
         }
     }
-    Ok(())
+    reset_colors(stdout);
+    cp.print_legend(stdout, &conf.calendar_fields)
 }
 
 fn task_list_calendar(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::Conf) -> io::Result<()> {
@@ -366,15 +600,254 @@ fn task_list_calendar(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf
     let rng = conf.calendar.expect("calendar range must be set");
     let start_date = calendar_first_day(now, &rng, conf.first_sunday);
     let end_date = calendar_last_day(now, &rng, conf.first_sunday);
-    let counter = fill_calendar(start_date, end_date, tasks, &todos);
+    let counter = fill_calendar(start_date, end_date, tasks, &todos, &conf.calendar_fields, now);
 
     let res = print_calendar_body(stdout, now, start_date, end_date, &counter, conf);
     reset_colors(stdout);
     if res.is_err() { res } else { writeln!(stdout) }
 }
 
+fn task_list_board(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::Conf) -> io::Result<()> {
+    let mut todos = filter_tasks(tasks, conf);
+    tsort::sort(&mut todos, tasks, &conf.sort);
+    let columns = board::build_columns(tasks, &todos, &conf.board_field, &conf.board_columns);
+    let res = board::print_board(stdout, tasks, &columns, conf);
+    reset_colors(stdout);
+    res
+}
+
+fn task_move(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
+    let column = match &conf.todo.subject {
+        Some(s) if !s.trim().is_empty() => s.trim().to_string(),
+        _ => {
+            writeln!(stdout, "Board column is not defined. Usage: ttdl move ID COLUMN")?;
+            std::process::exit(1);
+        }
+    };
+    if is_filter_empty(conf) {
+        writeln!(stdout, "Warning: you are going to move all the tasks. Please specify tasks to move.")?;
+        std::process::exit(1);
+    }
+    if !conf.board_columns.is_empty() && column != board::NO_COLUMN && !conf.board_columns.contains(&column) {
+        writeln!(stdout, "Invalid column '{column}'. Available columns: {}", conf.board_columns.join(", "))?;
+        std::process::exit(1);
+    }
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
+    let action = "moved";
+    if todos.is_empty() {
+        writeln!(stdout, "No todo {action}")?;
+        return Ok(());
+    }
+    let mut clones = todo::clone_tasks(tasks, &todos);
+    let ids: todo::IDVec = (0..clones.len()).collect();
+    let updated = match board::move_tasks(&mut clones, &ids, &conf.board_field, &column, &conf.board_columns) {
+        Ok(u) => u,
+        Err(e) => {
+            writeln!(stdout, "{e}")?;
+            std::process::exit(1);
+        }
+    };
+    if calculate_updated(&updated) == 0 {
+        writeln!(stdout, "No todo was {action}")?;
+        return Ok(());
+    }
+    let (cols, widths) = cols_with_width(tasks, &todos, conf);
+    if conf.dry {
+        writeln!(stdout, "Todos to be {action}:")?;
+        fmt::print_header(stdout, &conf.fmt, &cols, &widths)?;
+        fmt::print_todos(stdout, tasks, &todos, &updated, conf, &cols, &widths, false)?;
+        writeln!(stdout, "\nNew todos:")?;
+        fmt::print_todos(stdout, &clones, &todos, &updated, conf, &cols, &widths, true)?;
+        fmt::print_footer(stdout, tasks, &todos, &updated, &conf.fmt, &cols, &widths)?;
+        return Ok(());
+    }
+    for ((id, clone), changed) in todos.iter().zip(clones).zip(updated.iter()) {
+        if *changed {
+            tasks[*id] = clone;
+        }
+    }
+    writeln!(stdout, "Changed todos:")?;
+    fmt::print_header(stdout, &conf.fmt, &cols, &widths)?;
+    fmt::print_todos(stdout, tasks, &todos, &updated, conf, &cols, &widths, false)?;
+    fmt::print_footer(stdout, tasks, &todos, &updated, &conf.fmt, &cols, &widths)?;
+    if let Err(e) = save_task_lists(tasks, &todos, &updated, None, conf) {
+        writeln!(stdout, "{e:?}")?;
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn task_search(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::Conf) -> io::Result<()> {
+    let tokens = match &conf.todo.subject {
+        Some(s) => search::parse_query(s),
+        None => Vec::new(),
+    };
+    if tokens.is_empty() {
+        writeln!(stdout, "Search query is empty. Usage: ttdl search QUERY")?;
+        std::process::exit(1);
+    }
+    // Archived tasks are appended after the active ones, so IDs of active tasks do not change.
+    // Archives are always loaded: for search, the status filter is never "incomplete only"(see
+    // `conf::parse_args`), so completed todos are always searched, and archives contain only them
+    let mut all = tasks.to_vec();
+    let mut selected = filter_tasks(tasks, conf);
+    let done_tasks = match load_done_lists(conf) {
+        Ok(l) => l,
+        Err(e) => {
+            writeln!(stdout, "{e}")?;
+            std::process::exit(1);
+        }
+    };
+    let active = all.len();
+    selected.extend(filter_tasks(&done_tasks, conf).into_iter().map(|id| id + active));
+    all.extend(done_tasks);
+
+    let today = chrono::Local::now().date_naive();
+    let mut hits = search::search(&all, &selected, active, &tokens, today);
+    if let Some(max) = conf.max_items {
+        hits.truncate(max);
+    }
+    let res = search::print_hits(stdout, &all, &hits, &tokens, conf);
+    reset_colors(stdout);
+    res
+}
+
+// Moves todos to the task list `list_id`. Returns what todos were moved and IDs of all affected task lists.
+fn move_to_list(
+    tasks: &mut todo::TaskSlice,
+    ids: &todo::IDSlice,
+    list_id: usize,
+    conf: &conf::Conf,
+) -> (todo::ChangedVec, HashSet<usize>) {
+    let mut updated = vec![false; ids.len()];
+    let mut list_ids = HashSet::new();
+    for (idx, id) in ids.iter().enumerate() {
+        let task = &mut tasks[*id];
+        let old_id = match &task.source {
+            Some(src) if src.id != list_id => src.id,
+            _ => continue,
+        };
+        task.set_source(&conf.task_lists[list_id].name, list_id);
+        list_ids.insert(old_id);
+        list_ids.insert(list_id);
+        updated[idx] = true;
+    }
+    (updated, list_ids)
+}
+
+fn task_transfer(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
+    if conf.is_single_file_mode() {
+        writeln!(stdout, "Moving todos between task lists requires a few task lists in the config")?;
+        std::process::exit(1);
+    }
+    let list_id = match conf.list_name_to_index(&conf.move_to) {
+        Some(id) => id,
+        None => {
+            writeln!(stdout, "Task list '{0}' not found. Usage: ttdl mv ID --to=NAME", conf.move_to)?;
+            std::process::exit(1);
+        }
+    };
+    if conf.task_lists[list_id].readonly {
+        writeln!(stdout, "Cannot move todos to the read-only task list '{0}'", conf.move_to)?;
+        std::process::exit(1);
+    }
+    if is_filter_empty(conf) {
+        writeln!(stdout, "Warning: you are going to move all the tasks. Please specify tasks to move.")?;
+        std::process::exit(1);
+    }
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
+    let mut done_tasks = Vec::new();
+    let mut done_todos = Vec::new();
+    if conf.move_archived {
+        done_tasks = match load_done_lists(conf) {
+            Ok(l) => l,
+            Err(e) => {
+                writeln!(stdout, "{e}")?;
+                std::process::exit(1);
+            }
+        };
+        // IDs point to active todos, so only the rest of the filter is applied to archived ones
+        let mut flt_conf = conf.clone();
+        flt_conf.flt.range = tfilter::ItemRange::None;
+        if is_filter_empty(&flt_conf) {
+            writeln!(
+                stdout,
+                "Warning: archived todos are selected only by a filter. Please specify a filter other than IDs."
+            )?;
+            std::process::exit(1);
+        }
+        flt_conf.flt.all = tfilter::TodoStatus::All;
+        done_todos = filter_tasks(&done_tasks, &flt_conf);
+        skip_readonly(stdout, &done_tasks, &mut done_todos, conf)?;
+    }
+    let action = format!("moved to '{0}'", conf.task_lists[list_id].name);
+    if todos.is_empty() && done_todos.is_empty() {
+        writeln!(stdout, "No todo {action}")?;
+        return Ok(());
+    }
+
+    if conf.dry {
+        let mut clones = todo::clone_tasks(tasks, &todos);
+        let ids: todo::IDVec = (0..clones.len()).collect();
+        let (updated, _) = move_to_list(&mut clones, &ids, list_id, conf);
+        let mut done_clones = todo::clone_tasks(&done_tasks, &done_todos);
+        let ids: todo::IDVec = (0..done_clones.len()).collect();
+        let (done_updated, _) = move_to_list(&mut done_clones, &ids, list_id, conf);
+        if calculate_updated(&updated) + calculate_updated(&done_updated) == 0 {
+            writeln!(stdout, "No todo was {action}")?;
+            return Ok(());
+        }
+        if calculate_updated(&updated) != 0 {
+            let (cols, widths) = cols_with_width(tasks, &todos, conf);
+            writeln!(stdout, "Todos to be {action}:")?;
+            fmt::print_header(stdout, &conf.fmt, &cols, &widths)?;
+            fmt::print_todos(stdout, tasks, &todos, &updated, conf, &cols, &widths, false)?;
+            fmt::print_footer(stdout, tasks, &todos, &updated, &conf.fmt, &cols, &widths)?;
+        }
+        if calculate_updated(&done_updated) != 0 {
+            let mut done_conf = conf.clone();
+            done_conf.fmt.max = done_tasks.len();
+            let (cols, widths) = cols_with_width(&done_tasks, &done_todos, &done_conf);
+            writeln!(stdout, "Archived todos to be {action}:")?;
+            fmt::print_header(stdout, &done_conf.fmt, &cols, &widths)?;
+            fmt::print_todos(stdout, &done_tasks, &done_todos, &done_updated, &done_conf, &cols, &widths, false)?;
+            fmt::print_footer(stdout, &done_tasks, &done_todos, &done_updated, &done_conf.fmt, &cols, &widths)?;
+        }
+        return Ok(());
+    }
+
+    let (updated, list_ids) = move_to_list(tasks, &todos, list_id, conf);
+    let (done_updated, done_list_ids) = move_to_list(&mut done_tasks, &done_todos, list_id, conf);
+    if calculate_updated(&updated) + calculate_updated(&done_updated) == 0 {
+        writeln!(stdout, "No todo was {action}")?;
+        return Ok(());
+    }
+    if calculate_updated(&updated) != 0 {
+        let (cols, widths) = cols_with_width(tasks, &todos, conf);
+        writeln!(stdout, "Changed todos:")?;
+        fmt::print_header(stdout, &conf.fmt, &cols, &widths)?;
+        fmt::print_todos(stdout, tasks, &todos, &updated, conf, &cols, &widths, false)?;
+        fmt::print_footer(stdout, tasks, &todos, &updated, &conf.fmt, &cols, &widths)?;
+    }
+    if calculate_updated(&done_updated) != 0 {
+        writeln!(stdout, "{0} archived todos {action}", calculate_updated(&done_updated))?;
+    }
+    let res = if conf.move_archived {
+        save_moved_lists(tasks, &list_ids, &done_tasks, &done_list_ids, conf)
+    } else {
+        save_task_lists(tasks, &todos, &updated, Some(list_ids), conf)
+    };
+    if let Err(e) = res {
+        writeln!(stdout, "{e:?}")?;
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn task_done(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
-    if is_filter_empty(&conf.flt) {
+    if refuse_all(conf) {
         writeln!(stdout, "Warning: you are going to mark all the tasks 'done'. Please specify tasks to complete.")?;
         std::process::exit(1);
     }
@@ -383,6 +856,21 @@ fn task_done(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf
         eprintln!("{e:?}");
         std::process::exit(1);
     }
+    if processed && conf.archive_on_done {
+        let done: todo::IDVec = todos
+            .iter()
+            .zip(updated.iter())
+            .filter(|(id, upd)| **upd && tasks[**id].finished)
+            .map(|(id, _)| *id)
+            .collect();
+        if !done.is_empty() {
+            if let Err(e) = archive_and_remove(tasks, &done, &done, conf) {
+                eprintln!("{e:?}");
+                std::process::exit(1);
+            }
+            writeln!(stdout, "{} todos archived", done.len())?;
+        }
+    }
     Ok(())
 }
 
@@ -480,7 +968,7 @@ fn task_list_agenda(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf:
 }
 
 fn task_undone(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
-    if is_filter_empty(&conf.flt) {
+    if is_filter_empty(conf) {
         writeln!(
             stdout,
             "Warning: you are going to undone all the tasks completed tasks. Please specify tasks to undone."
@@ -525,7 +1013,7 @@ fn removed_source_ids(ids: &todo::IDSlice, todos: &todo::IDSlice, removed: &todo
 }
 
 fn task_remove(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
-    if is_filter_empty(&conf.flt) {
+    if refuse_all(conf) {
         writeln!(stdout, "Warning: deletion of all tasks requested. Please specify tasks to delete.")?;
         std::process::exit(1);
     }
@@ -533,10 +1021,11 @@ fn task_remove(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &co
     if flt_conf.flt.all == tfilter::TodoStatus::Active {
         flt_conf.flt.all = tfilter::TodoStatus::All;
     }
-    let todos = filter_tasks(tasks, conf);
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
     if todos.is_empty() {
         writeln!(stdout, "No todo deleted")?
-    } else {
+    } else if confirm_todos(stdout, tasks, &mut todos, None, "removed", 0, conf)? {
         if flt_conf.dry {
             writeln!(stdout, "Todos to be removed:")?
         } else {
@@ -563,19 +1052,73 @@ fn task_remove(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &co
     Ok(())
 }
 
+// Appends todos `to_archive` to the archive, and then removes todos `to_remove` from the active lists.
+fn archive_and_remove(
+    tasks: &mut todo::TaskVec,
+    to_archive: &todo::IDSlice,
+    to_remove: &todo::IDVec,
+    conf: &conf::Conf,
+) -> Result<(), terr::TodoError> {
+    let cloned = todo::clone_tasks(tasks, to_archive);
+    if !cloned.is_empty() {
+        archive_task_lists(&cloned, conf)?;
+    }
+    let task_ids = task_source_ids(tasks);
+    let removed = todo::remove(tasks, Some(to_remove));
+    let updated_task_lists = removed_source_ids(&task_ids, to_remove, &removed);
+    if calculate_updated(&removed) != 0 {
+        save_task_lists(tasks, to_remove, &removed, Some(updated_task_lists), conf)?;
+    }
+    Ok(())
+}
+
+// Archives todos completed `conf.auto_clean_days` or more days ago, the same way as `--older-than`
+// selects them. Returns the number of archived todos
+fn auto_clean(
+    stdout: &mut StandardStream,
+    tasks: &mut todo::TaskVec,
+    conf: &conf::Conf,
+) -> Result<usize, terr::TodoError> {
+    let today = chrono::Local::now().date_naive();
+    let old: todo::IDVec = tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| {
+            t.finished
+                && !conf.is_readonly(t)
+                && t.finish_date.is_some_and(|dt| (today - dt).num_days() >= i64::from(conf.auto_clean_days))
+        })
+        .map(|(id, _)| id)
+        .collect();
+    if old.is_empty() {
+        return Ok(0);
+    }
+    if conf.verbose {
+        writeln!(stdout, "Archiving {0} todos completed {1} or more days ago", old.len(), conf.auto_clean_days)
+            .map_err(|e| terr::TodoError::IOError(e.to_string()))?;
+    }
+    archive_and_remove(tasks, &old, &old, conf)?;
+    Ok(old.len())
+}
+
 fn task_clean(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
     let mut conf = conf.clone();
     conf.flt = tfilter::Conf { all: tfilter::TodoStatus::Done, ..conf.flt.clone() };
     let mut todos = filter_tasks(tasks, &conf);
+    skip_readonly(stdout, tasks, &mut todos, &conf)?;
     let done_todos = todos.clone();
     if !conf.keep_empty {
         let mut empty_conf = conf.clone();
         empty_conf.flt = tfilter::Conf { all: tfilter::TodoStatus::Empty, ..conf.flt.clone() };
         let mut empty_todos = filter_tasks(tasks, &empty_conf);
+        empty_todos.retain(|&id| !conf.is_readonly(&tasks[id]));
         for et in empty_todos.drain(..) {
             todos.push(et);
         }
     }
+    if conf.wipe && !todos.is_empty() && !confirm_todos(stdout, tasks, &mut todos, None, "wiped", 0, &conf)? {
+        return Ok(());
+    }
     if todos.is_empty() {
         writeln!(stdout, "No todo archived")?
     } else {
@@ -589,27 +1132,69 @@ fn task_clean(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &con
         fmt::print_todos(stdout, tasks, &todos, &[], &conf, &cols, &widths, false)?;
         fmt::print_footer(stdout, tasks, &todos, &[], &conf.fmt, &cols, &widths)?;
         if !conf.dry {
-            let cloned = todo::clone_tasks(tasks, &done_todos);
-            if !conf.wipe
-                && let Err(e) = archive_task_lists(&cloned, &conf)
-            {
+            let to_archive = if conf.wipe { Vec::new() } else { done_todos };
+            if let Err(e) = archive_and_remove(tasks, &to_archive, &todos, &conf) {
                 eprintln!("{e:?}");
                 exit(1);
             }
-            let task_ids = task_source_ids(tasks);
-            let removed = todo::remove(tasks, Some(&todos));
-            let updated_task_lists = removed_source_ids(&task_ids, &todos, &removed);
-            if calculate_updated(&removed) != 0
-                && let Err(e) = save_task_lists(tasks, &todos, &removed, Some(updated_task_lists), &conf)
-            {
-                writeln!(stdout, "{e:?}")?;
-                std::process::exit(1);
-            }
         }
     }
     Ok(())
 }
 
+fn task_unarchive(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::Conf) -> io::Result<()> {
+    if is_filter_empty(conf) {
+        writeln!(stdout, "Warning: you are going to unarchive all the tasks. Please specify tasks to unarchive.")?;
+        std::process::exit(1);
+    }
+    let done_tasks = match load_done_lists(conf) {
+        Ok(l) => l,
+        Err(e) => {
+            writeln!(stdout, "{e}")?;
+            std::process::exit(1);
+        }
+    };
+    let mut flt_conf = conf.clone();
+    if flt_conf.flt.all == tfilter::TodoStatus::Active {
+        flt_conf.flt.all = tfilter::TodoStatus::All;
+    }
+    flt_conf.fmt.max = done_tasks.len();
+    let mut todos = filter_tasks(&done_tasks, &flt_conf);
+    skip_readonly(stdout, &done_tasks, &mut todos, conf)?;
+    if todos.is_empty() {
+        writeln!(stdout, "No todo unarchived")?;
+        return Ok(());
+    }
+    let mut restored = todo::clone_tasks(&done_tasks, &todos);
+    if conf.reopen {
+        todo::undone(&mut restored, None, conf.todo.completion_mode);
+    }
+
+    let (cols, widths) = cols_with_width(&done_tasks, &todos, &flt_conf);
+    if conf.dry {
+        writeln!(stdout, "Todos to be unarchived:")?;
+    } else {
+        writeln!(stdout, "Unarchived todos:")?;
+    }
+    fmt::print_header(stdout, &flt_conf.fmt, &cols, &widths)?;
+    fmt::print_todos(stdout, &done_tasks, &todos, &[], &flt_conf, &cols, &widths, false)?;
+    if conf.dry && conf.reopen {
+        writeln!(stdout, "\nNew todos:")?;
+        let updated = vec![true; todos.len()];
+        fmt::print_todos(stdout, &restored, &todos, &updated, &flt_conf, &cols, &widths, true)?;
+    }
+    fmt::print_footer(stdout, &done_tasks, &todos, &[], &flt_conf.fmt, &cols, &widths)?;
+    if conf.dry {
+        return Ok(());
+    }
+
+    if let Err(e) = unarchive_task_lists(tasks, &done_tasks, &todos, &restored, conf) {
+        writeln!(stdout, "{e:?}")?;
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn copy_tags_from_task(subj: &str, task: &mut todotxt::Task) -> String {
     let now = chrono::Local::now().date_naive();
     let mut tsk = todotxt::Task::parse(subj, now);
@@ -634,23 +1219,49 @@ fn copy_tags_from_task(subj: &str, task: &mut todotxt::Task) -> String {
     sbj
 }
 
-fn create_temp_file(tasks: &mut todo::TaskVec, ids: &todo::IDVec) -> io::Result<TempPath> {
+// In multi-file mode, todos are grouped by task lists, and every group starts with the task list header.
+// Read-only task lists are skipped. Writable task lists without selected todos get empty sections,
+// so new todos can be added to any of them.
+// Returns the temporary file and IDs of todos in the order they are written to the file.
+fn create_temp_file(
+    tasks: &todo::TaskSlice,
+    ids: &todo::IDSlice,
+    conf: &conf::Conf,
+) -> io::Result<(TempPath, todo::IDVec)> {
     let named = NamedTempFile::new()?;
     let filetmp = named.into_temp_path();
     let mut file = File::create(filetmp.as_os_str())?;
-    for idx in ids {
-        writeln!(file, "{0}", tasks[*idx])?;
+    if conf.is_single_file_mode() {
+        for idx in ids {
+            writeln!(file, "{0}", tasks[*idx])?;
+        }
+        return Ok((filetmp, ids.to_vec()));
     }
-    Ok(filetmp)
+    let mut order = Vec::new();
+    for (list_id, tlist) in conf.task_lists.iter().enumerate() {
+        if tlist.readonly {
+            continue;
+        }
+        writeln!(file, "{0}", edit::list_header(conf, list_id))?;
+        for idx in ids.iter().filter(|&&id| task_list_id(&tasks[id]) == list_id) {
+            writeln!(file, "{0}", tasks[*idx])?;
+            order.push(*idx);
+        }
+        writeln!(file)?;
+    }
+    Ok((filetmp, order))
+}
+
+fn task_list_id(task: &todotxt::Task) -> usize {
+    task.source.as_ref().map_or(0, |src| src.id)
 }
 
-fn tmp_file_hash(f: &TempPath) -> io::Result<Option<u64>> {
+fn tmp_file_hash(f: &TempPath, conf: &conf::Conf) -> io::Result<Option<u64>> {
     let mut hasher = std::hash::DefaultHasher::new();
     let mut file = File::open(f.as_os_str())?;
     let mut data = vec![];
     file.read_to_end(&mut data)?;
-    let has_some = data.iter().any(|&c| c != b' ' && c != b'\n' && c != b'\r');
-    if !has_some {
+    if !edit::has_todos(&String::from_utf8_lossy(&data), conf) {
         Ok(None)
     } else {
         hasher.write(&data);
@@ -658,12 +1269,181 @@ fn tmp_file_hash(f: &TempPath) -> io::Result<Option<u64>> {
     }
 }
 
-fn task_edit(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
-    if conf.use_editor && conf.dry {
-        writeln!(stdout, "Interactive editing does not support dry run")?;
+// Converts date expressions in a subject of a todo, e.g. `due:today+1w` to `due:2026-10-25`
+fn convert_date_exprs(subj: &str, conf: &conf::Conf) -> Result<String, String> {
+    let now = chrono::Local::now().date_naive();
+    let mut tag_list = date_expr::TaskTagList::from_str(subj, now);
+    let soon = conf.fmt.colors.soon_days;
+    match date_expr::calculate_main_tags(now, &mut tag_list, soon)? {
+        false => Ok(subj.to_string()),
+        true => Ok(date_expr::update_tags_in_str(&tag_list, subj)),
+    }
+}
+
+fn print_edit_group(
+    stdout: &mut StandardStream,
+    title: &str,
+    tasks: &todo::TaskSlice,
+    ids: &todo::IDSlice,
+    conf: &conf::Conf,
+) -> io::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let (cols, widths) = cols_with_width(tasks, ids, conf);
+    writeln!(stdout, "{title}")?;
+    fmt::print_header(stdout, &conf.fmt, &cols, &widths)?;
+    fmt::print_todos(stdout, tasks, ids, &[], conf, &cols, &widths, false)?;
+    fmt::print_footer(stdout, tasks, ids, &[], &conf.fmt, &cols, &widths)
+}
+
+// Edits todos in an external editor. The edited file is compared with the original one line by line:
+// unchanged todos stay intact, changed todos are updated in place, so they keep their IDs,
+// new todos are appended to the end of their task lists, and deleted todos are removed.
+fn task_edit_interactive(
+    stdout: &mut StandardStream,
+    tasks: &mut todo::TaskVec,
+    todos: &todo::IDSlice,
+    conf: &conf::Conf,
+) -> io::Result<()> {
+    // unwrap cannot fail here as we already check it for 'Some' before.
+    let editor = conf.editor().unwrap();
+    let (filepath, order) = create_temp_file(tasks, todos, conf)?;
+    let orig_hash = tmp_file_hash(&filepath, conf)?;
+    let mut child = Command::new(editor).arg(filepath.as_os_str()).spawn()?;
+    if let Err(e) = child.wait() {
+        writeln!(stdout, "Failed to execute editor: {e:?}")?;
+        exit(1);
+    }
+    let new_hash = tmp_file_hash(&filepath, conf)?;
+    match new_hash {
+        None => {
+            writeln!(stdout, "Empty file detected. Edit operation canceled")?;
+            exit(0);
+        }
+        Some(h) if orig_hash == Some(h) => {
+            // The temporary file was not changed. Nothing to do
+            writeln!(stdout, "No changes detected. Edit operation canceled")?;
+            exit(0);
+        }
+        Some(_) => {}
+    }
+    let content = read_to_string(filepath.as_os_str())?;
+    filepath.close()?;
+    let lines = match edit::parse_temp_file(&content, conf) {
+        Ok(l) => l,
+        Err(e) => {
+            writeln!(stdout, "{e}. Edit operation canceled")?;
+            exit(1);
+        }
+    };
+    let mut new_lines = Vec::new();
+    for (list_id, subj) in lines {
+        match convert_date_exprs(&subj, conf) {
+            Ok(s) => new_lines.push((list_id, s)),
+            Err(e) => {
+                writeln!(stdout, "{e}")?;
+                exit(1);
+            }
+        }
+    }
+    let old_lines: Vec<(usize, String)> =
+        order.iter().map(|&id| (task_list_id(&tasks[id]), tasks[id].to_string())).collect();
+
+    // All changes are applied to a copy first: it is used to display changes in both modes
+    let now = chrono::Local::now().date_naive();
+    let single = conf.is_single_file_mode();
+    let mut edited = tasks.clone();
+    let (mut changed, mut added, mut removed) = (Vec::new(), Vec::new(), Vec::new());
+    let mut list_ids: HashSet<usize> = HashSet::new();
+    let mut edits = Vec::new();
+    for edit in diff::diff(&old_lines, &new_lines) {
+        // A line replaced with a line of another task list is a removed todo and a new one
+        match edit {
+            diff::Edit::Changed(o, n) if old_lines[o].0 != new_lines[n].0 => {
+                edits.push(diff::Edit::Removed(o));
+                edits.push(diff::Edit::Added(n));
+            }
+            _ => edits.push(edit),
+        }
+    }
+    for edit in edits {
+        match edit {
+            diff::Edit::Same(_, _) => {}
+            diff::Edit::Changed(o, n) => {
+                let id = order[o];
+                let (list_id, subj) = &new_lines[n];
+                let mut task = todotxt::Task::parse(subj, now);
+                if !single {
+                    task.set_source(&conf.task_lists[*list_id].name, *list_id);
+                }
+                edited[id] = task;
+                list_ids.insert(*list_id);
+                changed.push(id);
+            }
+            diff::Edit::Removed(o) => {
+                list_ids.insert(old_lines[o].0);
+                removed.push(order[o]);
+            }
+            diff::Edit::Added(n) => {
+                let (list_id, subj) = &new_lines[n];
+                let mut cnf = conf.todo.clone();
+                cnf.subject = Some(subj.clone());
+                let id = todo::add(&mut edited, &cnf);
+                if id == todo::INVALID_ID {
+                    writeln!(stdout, "Failed to add: parse error '{subj}'")?;
+                    continue;
+                }
+                if !single {
+                    edited[id].set_source(&conf.task_lists[*list_id].name, *list_id);
+                }
+                list_ids.insert(*list_id);
+                added.push(id);
+            }
+        }
+    }
+    if changed.is_empty() && added.is_empty() && removed.is_empty() {
+        writeln!(stdout, "No todo changed")?;
+        return Ok(());
+    }
+
+    let (changed_title, new_title, added_title, removed_title) = if conf.dry {
+        ("Todos to be changed:", "\nNew todos:", "\nTodos to be added:", "\nTodos to be removed:")
+    } else {
+        ("Changed todos:", "\nNew todos:", "\nAdded todos:", "\nRemoved todos:")
+    };
+    print_edit_group(stdout, changed_title, tasks, &changed, conf)?;
+    print_edit_group(stdout, new_title, &edited, &changed, conf)?;
+    print_edit_group(stdout, added_title, &edited, &added, conf)?;
+    print_edit_group(stdout, removed_title, tasks, &removed, conf)?;
+    let (c, a, r) = (changed.len(), added.len(), removed.len());
+    if conf.dry {
+        writeln!(stdout, "{c} tasks to be changed, {a} tasks to be added, {r} tasks to be removed.")?;
+        return Ok(());
+    }
+
+    todo::remove(&mut edited, Some(&removed));
+    *tasks = edited;
+    if let Err(e) = save_task_lists(tasks, &[], &[], Some(list_ids), conf) {
+        writeln!(stdout, "{e:?}")?;
         std::process::exit(1);
     }
-    let editor = conf.editor();
+    writeln!(stdout, "Changed {c} tasks, added {a} tasks, removed {r} tasks.")
+}
+
+// Asks for confirmation before `edit` changes many todos
+fn confirm_edit(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    todos: &mut todo::IDVec,
+    conf: &conf::Conf,
+) -> io::Result<bool> {
+    let mut clones = todo::clone_tasks(tasks, todos);
+    todo::edit(&mut clones, None, &conf.todo);
+    confirm_todos(stdout, tasks, todos, Some(&clones), "changed", bulk_count(conf), conf)
+}
+
+fn task_edit(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
     if conf.use_editor && conf.editor().is_none() {
         writeln!(
             stdout,
@@ -671,85 +1451,19 @@ fn task_edit(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf
         )?;
         std::process::exit(1);
     }
-    if conf.use_editor && !conf.is_single_file_mode() {
-        writeln!(stdout, "Using an external editor is not supported in multi-file mode")?;
-        std::process::exit(1);
-    }
-    if is_filter_empty(&conf.flt) && !conf.use_editor {
+    if refuse_all(conf) && !conf.use_editor {
         writeln!(stdout, "Warning: modifying of all tasks requested. Please specify tasks to edit.")?;
         std::process::exit(1);
     }
-    let todos = filter_tasks(tasks, conf);
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
     let action = "changed";
     if todos.is_empty() {
         writeln!(stdout, "No todo changed")?
     } else if conf.use_editor {
-        // unwrap cannot fail here as we already check it for 'Some' before.
-        let editor = editor.unwrap();
-        let filepath = create_temp_file(tasks, &todos)?;
-        let orig_hash = tmp_file_hash(&filepath)?;
-        let mut child = Command::new(editor).arg(filepath.as_os_str()).spawn()?;
-        if let Err(e) = child.wait() {
-            writeln!(stdout, "Failed to execute editor: {e:?}")?;
-            exit(1);
-        }
-        let new_hash = tmp_file_hash(&filepath)?;
-        match (orig_hash, new_hash) {
-            (_, None) => {
-                writeln!(stdout, "Empty file detected. Edit operation canceled")?;
-                exit(0);
-            }
-            (_, Some(b)) => {
-                let now = chrono::Local::now().date_naive();
-                let content = read_to_string(filepath.as_os_str())?;
-                let mut removed_cnt = 0;
-                if let Some(a) = orig_hash {
-                    if a == b {
-                        // The temporary file was not changed. Nothing to do
-                        writeln!(stdout, "No changes detected. Edit operation canceled")?;
-                        exit(0);
-                    }
-                    let removed = todo::remove(tasks, Some(&todos));
-                    removed_cnt = calculate_updated(&removed);
-                }
-                let mut added_cnt = 0;
-                for line in content.lines() {
-                    let subj = line.trim();
-                    if subj.is_empty() {
-                        continue;
-                    }
-                    // TODO: move duplicated code (here and in task_add) to a separate fn
-                    let mut tag_list = date_expr::TaskTagList::from_str(subj, now);
-                    let soon = conf.fmt.colors.soon_days;
-                    let subj = match date_expr::calculate_main_tags(now, &mut tag_list, soon) {
-                        Err(e) => {
-                            writeln!(stdout, "{e:?}")?;
-                            exit(1);
-                        }
-                        Ok(changed) => match changed {
-                            false => subj.to_string(),
-                            true => date_expr::update_tags_in_str(&tag_list, subj),
-                        },
-                    };
-                    let mut cnf = conf.clone();
-                    cnf.todo.subject = Some(subj.clone());
-                    let id = todo::add(tasks, &cnf.todo);
-                    if id == todo::INVALID_ID {
-                        writeln!(stdout, "Failed to add: parse error '{subj}'")?;
-                    } else {
-                        added_cnt += 1;
-                    }
-                }
-                // In editor mode only single task list is supported: it is OK to call todo::save
-                let todo_path = conf.default_todo_file();
-                if let Err(e) = todo::save(tasks, todo_path) {
-                    writeln!(stdout, "Failed to save to '{0:?}': {e}", todo_path)?;
-                    std::process::exit(1);
-                }
-                writeln!(stdout, "Removed {removed_cnt} tasks, added {added_cnt} tasks.")?;
-            }
-        }
-        filepath.close()?;
+        task_edit_interactive(stdout, tasks, &todos, conf)?;
+    } else if conf.confirm && !conf.dry && !conf.keep_tags && !confirm_edit(stdout, tasks, &mut todos, conf)? {
+        return Ok(());
     } else if conf.dry {
         let mut clones = todo::clone_tasks(tasks, &todos);
         let updated = if conf.keep_tags {
@@ -824,7 +1538,7 @@ fn task_add_text(
     conf: &conf::Conf,
     to_end: bool,
 ) -> io::Result<()> {
-    if is_filter_empty(&conf.flt) {
+    if refuse_all(conf) {
         writeln!(stdout, "Warning: you are going to add text to all tasks. Please specify tasks to modify.")?;
         std::process::exit(1);
     }
@@ -835,11 +1549,15 @@ fn task_add_text(
         }
         Some(s) => s,
     };
-    let todos = filter_tasks(tasks, conf);
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
     if todos.is_empty() {
         writeln!(stdout, "No todo changed")?;
         return Ok(());
     }
+    if !confirm_todos(stdout, tasks, &mut todos, None, "changed", bulk_count(conf), conf)? {
+        return Ok(());
+    }
 
     if conf.dry {
         let mut clones = todo::clone_tasks(tasks, &todos);
@@ -900,7 +1618,8 @@ fn task_start_stop(
     conf: &conf::Conf,
     start: bool,
 ) -> io::Result<()> {
-    let todos = filter_tasks(tasks, conf);
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
     let action = if start { "started" } else { "stopped" };
     if todos.is_empty() {
         writeln!(stdout, "No todo {action}")?
@@ -941,6 +1660,79 @@ fn task_start_stop(
     Ok(())
 }
 
+// Starts the timer of a todo, and stops it when the pomodoro ends. Task lists are loaded again
+// before stopping the timer because they may have changed during the pomodoro
+fn task_pomodoro(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
+    let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
+    if todos.len() != 1 {
+        writeln!(stdout, "Pomodoro needs exactly one todo, but {0} todos are selected", todos.len())?;
+        std::process::exit(1);
+    }
+    let id = todos[0];
+    if tasks[id].finished {
+        writeln!(stdout, "Cannot start a pomodoro for a completed todo")?;
+        std::process::exit(1);
+    }
+    let length = fmt::duration_str(chrono::Duration::seconds(conf.pomodoro_length));
+    if conf.dry {
+        writeln!(stdout, "Pomodoro of {length} would start for todo: {0}", tasks[id].subject)?;
+        return Ok(());
+    }
+    let subj = tasks[id].subject.clone();
+    if !timer::is_timer_on(&tasks[id]) {
+        let updated = todo::start(tasks, Some(&todos));
+        if let Err(e) = save_task_lists(tasks, &todos, &updated, None, conf) {
+            writeln!(stdout, "{e:?}")?;
+            std::process::exit(1);
+        }
+    }
+    let started = tasks[id].to_string();
+    writeln!(stdout, "Pomodoro of {length} started: {subj}")?;
+    writeln!(
+        stdout,
+        "Press Enter to stop it earlier. Ctrl+C leaves the timer running: stop it with `ttdl stop {0}`",
+        id + 1
+    )?;
+    let enter = pomodoro::enter_presses();
+    let completed = pomodoro::countdown(stdout, conf.pomodoro_length, "Pomodoro", &enter, conf)?;
+
+    *tasks = match load_task_lists(conf) {
+        Ok(t) => t,
+        Err(e) => {
+            writeln!(stdout, "{e}")?;
+            std::process::exit(1);
+        }
+    };
+    let Some(id) = pomodoro::find_started(tasks, id, &started) else {
+        writeln!(stdout, "The todo has been changed during the pomodoro. Stop its timer with `ttdl stop`")?;
+        std::process::exit(1);
+    };
+    let count = pomodoro::finish(tasks, id, completed);
+    if let Err(e) = save_task_lists(tasks, &[id], &[true], None, conf) {
+        writeln!(stdout, "{e:?}")?;
+        std::process::exit(1);
+    }
+    if !completed {
+        writeln!(stdout, "Pomodoro stopped: {subj}")?;
+        return Ok(());
+    }
+    let msg = format!("Pomodoro completed: {subj}");
+    writeln!(stdout, "{msg} (pomodoros: {count})")?;
+    pomodoro::notify(conf, &msg);
+
+    if conf.pomodoro_break > 0 {
+        if !pomodoro::countdown(stdout, conf.pomodoro_break, "Break", &enter, conf)? {
+            writeln!(stdout, "Break skipped")?;
+            return Ok(());
+        }
+        let msg = "Break is over";
+        writeln!(stdout, "{msg}")?;
+        pomodoro::notify(conf, msg);
+    }
+    Ok(())
+}
+
 fn postpone_and_stop_recurrence(todo: &mut todotxt::Task, rec: &todotxt::Recurrence) -> NaiveDate {
     let now = chrono::Local::now().date_naive();
     let dt = if let Some(dd) = todo.due_date { dd } else { now };
@@ -982,7 +1774,7 @@ fn postpone_threshold(task: &mut todotxt::Task, diff: Option<chrono::Duration>,
 }
 
 fn task_postpone(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> io::Result<()> {
-    if is_filter_empty(&conf.flt) {
+    if refuse_all(conf) {
         writeln!(stdout, "Warning: postponing of all tasks requested. Please specify tasks to postpone.")?;
         std::process::exit(1);
     }
@@ -1001,8 +1793,11 @@ fn task_postpone(stdout: &mut StandardStream, tasks: &mut todo::TaskVec, conf: &
         }
     };
     let mut todos = filter_tasks(tasks, conf);
+    skip_readonly(stdout, tasks, &mut todos, conf)?;
     if todos.is_empty() {
         writeln!(stdout, "No todo postponed")?
+    } else if !confirm_todos(stdout, tasks, &mut todos, None, "postponed", bulk_count(conf), conf)? {
+        return Ok(());
     } else if conf.dry {
         let mut clones = todo::clone_tasks(tasks, &todos);
         let mut updated: Vec<bool> = Vec::new();
@@ -1132,6 +1927,28 @@ where
     items
 }
 
+// Prints values of the given kind for shell completion, one per line
+fn print_candidates(tasks: &todo::TaskSlice, conf: &conf::Conf) {
+    let all: todo::IDVec = (0..tasks.len()).collect();
+    let items = match conf.completion.as_str() {
+        "projects" => collect_unique_items(tasks, &all, |task| &task.projects),
+        "contexts" => collect_unique_items(tasks, &all, |task| &task.contexts),
+        "hashtags" => collect_unique_items(tasks, &all, |task| &task.hashtags),
+        "tags" => {
+            let mut tags: Vec<String> = tasks.iter().flat_map(|task| task.tags.keys().cloned()).collect();
+            tags.sort();
+            tags.dedup();
+            tags
+        }
+        "sources" => conf.task_lists.iter().filter(|tl| !tl.name.is_empty()).map(|tl| tl.name.clone()).collect(),
+        "ids" => (1..=tasks.len()).map(|id| id.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    for item in items {
+        println!("{item}");
+    }
+}
+
 fn task_list_projects(stdout: &mut StandardStream, tasks: &todo::TaskSlice, conf: &conf::Conf) -> io::Result<()> {
     let mut conf = conf.clone();
     conf.show_hidden = true;
@@ -1178,10 +1995,7 @@ fn load_task_lists(conf: &conf::Conf) -> Result<todo::TaskVec, String> {
 
     for (idx, tlist) in conf.task_lists.iter().enumerate() {
         let mut curr_tasks = if use_done {
-            match todo::load(&tlist.done_file) {
-                Ok(l) => l,
-                Err(e) => return Err(format!("Failed to load done list from '{0}': {e:?}", tlist.done_file.display())),
-            }
+            archive::load(&tlist.done_file, conf.archive_rotation)?
         } else {
             match todo::load(&tlist.todo_file) {
                 Ok(l) => l,
@@ -1200,6 +2014,24 @@ fn load_task_lists(conf: &conf::Conf) -> Result<todo::TaskVec, String> {
     Ok(tasks)
 }
 
+// Loads archived tasks of all task lists regardless of `--done` option.
+fn load_done_lists(conf: &conf::Conf) -> Result<todo::TaskVec, String> {
+    let mut tasks: todo::TaskVec = Vec::new();
+    let single = conf.is_single_file_mode();
+
+    for (idx, tlist) in conf.task_lists.iter().enumerate() {
+        let mut curr_tasks = archive::load(&tlist.done_file, conf.archive_rotation)?;
+        if !single {
+            for task in curr_tasks.iter_mut() {
+                task.set_source(&tlist.name, idx);
+            }
+        }
+        tasks.append(&mut curr_tasks);
+    }
+
+    Ok(tasks)
+}
+
 // In mutli-source mode, extract only tasks of a single task list.
 fn clone_list_by_id(tasks: &todo::TaskSlice, list_id: usize) -> todo::TaskVec {
     let mut tlist: todo::TaskVec = Vec::new();
@@ -1222,7 +2054,13 @@ fn save_task_lists(
     to_save: Option<HashSet<usize>>,
     conf: &conf::Conf,
 ) -> Result<(), terr::TodoError> {
+    if let Some(from) = conf.archived_from {
+        return save_merged_lists(tasks, from, select, updated, conf);
+    }
     if conf.is_single_file_mode() {
+        if conf.task_lists[0].readonly {
+            return Err(terr::TodoError::IOError("The task list is read-only".to_string()));
+        }
         let todo_path = conf.default_todo_file();
         return todo::save(tasks, todo_path);
     }
@@ -1244,6 +2082,7 @@ fn save_task_lists(
     if list_ids.is_empty() {
         return Ok(());
     }
+    conf.check_writable(&list_ids).map_err(terr::TodoError::IOError)?;
 
     for list_id in &list_ids {
         let task_list = clone_list_by_id(tasks, *list_id);
@@ -1258,11 +2097,107 @@ fn save_task_lists(
     Ok(())
 }
 
+// Splits merged active and archived todos of a task list back into active and archived ones.
+// Archived todos that are not completed anymore become active.
+fn split_merged_list(tasks: &todo::TaskSlice, from: usize, list_id: Option<usize>) -> (todo::TaskVec, todo::TaskVec) {
+    let mut active: todo::TaskVec = Vec::new();
+    let mut archived: todo::TaskVec = Vec::new();
+    for (idx, task) in tasks.iter().enumerate() {
+        if let Some(id) = list_id
+            && task.source.as_ref().map(|src| src.id) != Some(id)
+        {
+            continue;
+        }
+        if idx < from || !task.finished {
+            active.push(task.clone());
+        } else {
+            archived.push(task.clone());
+        }
+    }
+    (active, archived)
+}
+
+// Saves both active and archived todos of changed task lists when they were loaded together.
+fn save_merged_lists(
+    tasks: &todo::TaskSlice,
+    from: usize,
+    select: &todo::IDSlice,
+    updated: &todo::ChangedSlice,
+    conf: &conf::Conf,
+) -> Result<(), terr::TodoError> {
+    let single = conf.is_single_file_mode();
+    let mut list_ids = HashSet::new();
+    for (idx, id) in select.iter().enumerate() {
+        if !updated[idx] {
+            continue;
+        }
+        if single {
+            list_ids.insert(0);
+        } else if let Some(src) = &tasks[*id].source {
+            list_ids.insert(src.id);
+        }
+    }
+
+    conf.check_writable(&list_ids).map_err(terr::TodoError::IOError)?;
+    // A todo may move between active and archived files, so all files are written together
+    let mut lists: Vec<(todo::TaskVec, PathBuf)> = Vec::new();
+    for list_id in &list_ids {
+        let tlist = &conf.task_lists[*list_id];
+        let (active, archived) = split_merged_list(tasks, from, if single { None } else { Some(*list_id) });
+        lists.push((active, tlist.todo_file.clone()));
+        let files = archive::split_by_file(&archived, &tlist.done_file, conf.archive_rotation, true)
+            .map_err(terr::TodoError::IOError)?;
+        for (path, archived) in files {
+            lists.push((archived, path));
+        }
+    }
+    archive::save_together(&lists).map_err(terr::TodoError::IOError)
+}
+
+// Moves `restored` todos back to their active lists, and removes todos with IDs `ids` from `done_tasks`.
+// Both active and archived files of all affected task lists are rewritten together.
+fn unarchive_task_lists(
+    tasks: &todo::TaskSlice,
+    done_tasks: &todo::TaskSlice,
+    ids: &todo::IDSlice,
+    restored: &todo::TaskSlice,
+    conf: &conf::Conf,
+) -> Result<(), terr::TodoError> {
+    let lists = archive::unarchive_lists(tasks, done_tasks, ids, restored, conf).map_err(terr::TodoError::IOError)?;
+    archive::save_together(&lists).map_err(terr::TodoError::IOError)
+}
+
+// Rewrites active files of task lists `list_ids` and archives of task lists `done_list_ids`.
+// All files are written together, so a failure does not leave a moved todo in two task lists
+fn save_moved_lists(
+    tasks: &todo::TaskSlice,
+    list_ids: &HashSet<usize>,
+    done_tasks: &todo::TaskSlice,
+    done_list_ids: &HashSet<usize>,
+    conf: &conf::Conf,
+) -> Result<(), terr::TodoError> {
+    conf.check_writable(list_ids.iter().chain(done_list_ids)).map_err(terr::TodoError::IOError)?;
+    let mut lists: Vec<(todo::TaskVec, PathBuf)> = Vec::new();
+    for list_id in list_ids {
+        lists.push((clone_list_by_id(tasks, *list_id), conf.task_lists[*list_id].todo_file.clone()));
+    }
+    for list_id in done_list_ids {
+        let archived = clone_list_by_id(done_tasks, *list_id);
+        let files =
+            archive::split_by_file(&archived, &conf.task_lists[*list_id].done_file, conf.archive_rotation, true)
+                .map_err(terr::TodoError::IOError)?;
+        for (path, archived) in files {
+            lists.push((archived, path));
+        }
+    }
+    archive::save_together(&lists).map_err(terr::TodoError::IOError)
+}
+
 // Archive only changes task lists.
 // In case of the config does not contain section 'sources', it saves the entire list.
 fn archive_task_lists(tasks: &todo::TaskSlice, conf: &conf::Conf) -> Result<(), terr::TodoError> {
     if conf.is_single_file_mode() {
-        return todo::archive(tasks, &conf.task_lists[0].done_file);
+        return archive_task_list(tasks, &conf.task_lists[0].done_file, conf);
     }
     let mut list_ids = HashSet::new();
     for task in tasks.iter() {
@@ -1274,20 +2209,280 @@ fn archive_task_lists(tasks: &todo::TaskSlice, conf: &conf::Conf) -> Result<(),
     if list_ids.is_empty() {
         return Ok(());
     }
+    conf.check_writable(&list_ids).map_err(terr::TodoError::IOError)?;
 
     for list_id in &list_ids {
         let task_list = clone_list_by_id(tasks, *list_id);
-        if let Err(e) = todo::archive(&task_list, &conf.task_lists[*list_id].done_file) {
+        archive_task_list(&task_list, &conf.task_lists[*list_id].done_file, conf)?;
+    }
+
+    Ok(())
+}
+
+// Appends todos to the archive. With rotation enabled, todos are split between a few files.
+fn archive_task_list(tasks: &todo::TaskSlice, done_file: &Path, conf: &conf::Conf) -> Result<(), terr::TodoError> {
+    let files =
+        archive::split_by_file(tasks, done_file, conf.archive_rotation, false).map_err(terr::TodoError::IOError)?;
+    for (path, tlist) in files {
+        if let Err(e) = todo::archive(&tlist, &path) {
             return Err(terr::TodoError::IOError(format!(
                 "Failed to archive task list to '{0}': {e:?}",
-                conf.task_lists[*list_id].done_file.display()
+                path.display()
             )));
         }
     }
-
     Ok(())
 }
 
+// Merges files passed to the command `merge`. Returns the exit code: 0 for a clean merge, 1 if there are conflicts.
+// The result is written to OURS file as git requires for merge drivers.
+fn merge_files(conf: &conf::Conf) -> i32 {
+    let mut texts = Vec::new();
+    for path in &conf.merge_files {
+        match read_to_string(path) {
+            Ok(s) => texts.push(s),
+            Err(e) => {
+                eprintln!("Failed to read '{0}': {e}", path.display());
+                return 1;
+            }
+        }
+    }
+    let today = chrono::Local::now().date_naive();
+    let (merged, conflicts) = merge::merge_text(&texts[0], &texts[1], &texts[2], today);
+    if conf.dry {
+        print!("{merged}");
+    } else if let Err(e) = std::fs::write(&conf.merge_files[1], merged) {
+        eprintln!("Failed to save '{0}': {e}", conf.merge_files[1].display());
+        return 1;
+    }
+    if conflicts.is_empty() {
+        return 0;
+    }
+    eprint!("{}", merge::conflict_report(&conflicts));
+    1
+}
+
+fn print_status(tasks: &todo::TaskSlice, conf: &conf::Conf) -> i32 {
+    let todos = filter_tasks(tasks, conf);
+    let today = chrono::Local::now().date_naive();
+    let sum = status::summary(tasks, &todos, today, conf.fmt.colors.soon_days);
+    let res = if conf.status_json {
+        status::summary_json(&conf.status_format, &sum).map(|j| j.dump())
+    } else {
+        status::format_summary(&conf.status_format, &sum)
+    };
+    match res {
+        Ok(s) => {
+            println!("{s}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+// Returns a value that changes when any todo.txt file is modified
+fn task_lists_stamp(conf: &conf::Conf) -> String {
+    let files: Vec<&Path> = conf.task_lists.iter().map(|tl| tl.todo_file.as_path()).collect();
+    serve::files_stamp(&files)
+}
+
+// Converts an ID from a request path to an index of the todo
+fn request_todo_id(id: &str, tasks: &todo::TaskSlice, conf: &conf::Conf) -> Result<usize, serve::Response> {
+    match id.parse::<usize>() {
+        Ok(id) if id > 0 && id <= tasks.len() => {
+            if conf.is_readonly(&tasks[id - 1]) {
+                return Err(serve::Response::error(403, "The todo belongs to a read-only task list"));
+            }
+            Ok(id - 1)
+        }
+        _ => Err(serve::Response::error(404, &format!("Todo '{id}' not found"))),
+    }
+}
+
+fn todos_json(tasks: &todo::TaskSlice, ids: &todo::IDSlice) -> json::JsonValue {
+    let mut arr = json::JsonValue::new_array();
+    for &id in ids {
+        let _ = arr.push(serve::task_json(&tasks[id], id + 1));
+    }
+    arr
+}
+
+fn save_error(e: terr::TodoError) -> serve::Response {
+    serve::Response::error(500, &format!("{e:?}"))
+}
+
+fn serve_list(tasks: &todo::TaskSlice, conf: &conf::Conf) -> serve::Response {
+    let mut todos = filter_tasks(tasks, conf);
+    tsort::sort(&mut todos, tasks, &conf.sort);
+    if let Some(max) = conf.max_items {
+        todos.truncate(max);
+    }
+    serve::Response::new(200, todos_json(tasks, &todos))
+}
+
+fn serve_add(req: &serve::Request, tasks: &mut todo::TaskVec, conf: &mut conf::Conf) -> serve::Response {
+    match req.body_field("subject") {
+        Ok(Some(subj)) => conf.todo.subject = Some(subj),
+        Ok(None) => return serve::Response::error(400, "Subject is empty"),
+        Err(e) => return serve::Response::error(400, &e),
+    }
+    let (source_id, subj) = match prepare_new_todo(conf) {
+        Ok(res) => res,
+        Err(e) => return serve::Response::error(400, &e),
+    };
+    let id = todo::add(tasks, &conf.todo);
+    if id == todo::INVALID_ID {
+        return serve::Response::error(400, &format!("Failed to add: parse error '{subj}'"));
+    }
+    tasks[id].set_source(&conf.task_lists[source_id].name, source_id);
+    if let Err(e) = save_task_lists(tasks, &[id], &[true], None, conf) {
+        return save_error(e);
+    }
+    serve::Response::new(201, serve::task_json(&tasks[id], id + 1))
+}
+
+fn serve_edit(req: &serve::Request, id: usize, tasks: &mut todo::TaskVec, conf: &mut conf::Conf) -> serve::Response {
+    match req.body_field("subject") {
+        Ok(Some(subj)) => match convert_date_exprs(&subj, conf) {
+            Ok(subj) => conf.todo.subject = Some(subj),
+            Err(e) => return serve::Response::error(400, &e),
+        },
+        Ok(None) => {}
+        Err(e) => return serve::Response::error(400, &e),
+    }
+    let todos = vec![id];
+    let updated = todo::edit(tasks, Some(&todos), &conf.todo);
+    if calculate_updated(&updated) != 0
+        && let Err(e) = save_task_lists(tasks, &todos, &updated, None, conf)
+    {
+        return save_error(e);
+    }
+    serve::Response::new(200, serve::task_json(&tasks[id], id + 1))
+}
+
+fn serve_done(id: usize, tasks: &mut todo::TaskVec, conf: &conf::Conf) -> serve::Response {
+    let todos = vec![id];
+    let updated = apply_completion(tasks, &todos, conf, todo::done);
+    if calculate_updated(&updated) == 0 {
+        return serve::Response::new(200, serve::task_json(&tasks[id], id + 1));
+    }
+    if let Err(e) = save_task_lists(tasks, &todos, &updated, None, conf) {
+        return save_error(e);
+    }
+    let resp = serve::Response::new(200, serve::task_json(&tasks[id], id + 1));
+    if conf.archive_on_done
+        && tasks[id].finished
+        && let Err(e) = archive_and_remove(tasks, &todos, &todos, conf)
+    {
+        return save_error(e);
+    }
+    resp
+}
+
+fn serve_archive(tasks: &mut todo::TaskVec, conf: &mut conf::Conf) -> serve::Response {
+    if conf.task_lists.iter().any(|tl| wildcard::is_wildcard(&tl.done_file)) {
+        return serve::Response::error(
+            400,
+            "Archive file path with wildcards can be used only to display archived todos",
+        );
+    }
+    conf.flt.all = tfilter::TodoStatus::Done;
+    let mut todos = filter_tasks(tasks, conf);
+    todos.retain(|&id| !conf.is_readonly(&tasks[id]));
+    let archived = todos_json(tasks, &todos);
+    if !todos.is_empty()
+        && let Err(e) = archive_and_remove(tasks, &todos, &todos, conf)
+    {
+        return save_error(e);
+    }
+    serve::Response::new(200, archived)
+}
+
+// Handles a request and returns the response and the name of the change if todos were changed
+fn serve_request(
+    req: &serve::Request,
+    tasks: &mut todo::TaskVec,
+    conf: &conf::Conf,
+) -> (serve::Response, &'static str) {
+    let mut conf = match conf::request_conf(conf, &req.options()) {
+        Ok(c) => c,
+        Err(e) => return (serve::Response::error(400, &e.to_string()), ""),
+    };
+    let path: Vec<&str> = req.path.iter().map(|p| p.as_str()).collect();
+    match (req.method.as_str(), path.as_slice()) {
+        ("GET", ["todos"]) => (serve_list(tasks, &conf), ""),
+        ("GET", ["todos", id]) => match id.parse::<usize>() {
+            Ok(id) if id > 0 && id <= tasks.len() => {
+                (serve::Response::new(200, serve::task_json(&tasks[id - 1], id)), "")
+            }
+            _ => (serve::Response::error(404, &format!("Todo '{id}' not found")), ""),
+        },
+        ("POST", ["todos"]) => (serve_add(req, tasks, &mut conf), "add"),
+        ("PATCH", ["todos", id]) => match request_todo_id(id, tasks, &conf) {
+            Ok(id) => (serve_edit(req, id, tasks, &mut conf), "edit"),
+            Err(resp) => (resp, ""),
+        },
+        ("POST", ["todos", id, "done"]) => match request_todo_id(id, tasks, &conf) {
+            Ok(id) => (serve_done(id, tasks, &conf), "done"),
+            Err(resp) => (resp, ""),
+        },
+        ("POST", ["archive"]) => (serve_archive(tasks, &mut conf), "clean"),
+        _ => (serve::Response::error(404, "Unknown endpoint"), ""),
+    }
+}
+
+// Runs an HTTP server with JSON API. Todos are kept in memory and reloaded when any todo.txt
+// changes. The state of the files is sent in `ETag`: a changing request with `If-Match` header
+// fails if the files have changed since then.
+fn serve_todos(conf: &conf::Conf) -> i32 {
+    let mut tasks: todo::TaskVec = Vec::new();
+    let mut stamp = String::new();
+    let res = serve::run(&conf.listen, |req| {
+        let current = task_lists_stamp(conf);
+        if current != stamp {
+            match load_task_lists(conf) {
+                Ok(t) => tasks = t,
+                Err(e) => return serve::Response::error(500, &e),
+            }
+            stamp = current;
+        }
+        let changing = req.method != "GET";
+        if changing
+            && let Some(tag) = req.header("if-match")
+            && tag.trim_matches('"') != stamp
+        {
+            return serve::Response::error(412, "Task lists have changed");
+        }
+        let (mut resp, change) = serve_request(req, &mut tasks, conf);
+        if changing {
+            // Todos in memory are the same as saved ones only if the change succeeded
+            stamp = if resp.status < 300 { task_lists_stamp(conf) } else { String::new() };
+            if resp.status < 300 && conf.git_commit {
+                commit_changes(conf, change);
+            }
+        }
+        resp.etag = Some(stamp.clone());
+        resp
+    });
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Failed to listen on '{0}': {e}", conf.listen);
+            1
+        }
+    }
+}
+
+// A failed commit does not break anything: the task lists are already saved
+fn commit_changes(conf: &conf::Conf, action: &str) {
+    if let Err(e) = vcs::commit_changes(conf, action) {
+        eprintln!("Failed to commit changes: {e}");
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
@@ -1303,6 +2498,41 @@ fn main() {
         conf.print_task_lists();
         exit(0);
     }
+    if conf.mode == conf::RunMode::Merge {
+        exit(merge_files(&conf));
+    }
+    if conf.mode == conf::RunMode::Completions {
+        match completion::script(&conf.completion) {
+            Some(s) => print!("{s}"),
+            None => {
+                eprintln!("Unsupported shell '{0}'. Usage: ttdl completions bash|zsh|fish", conf.completion);
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+    if conf.mode == conf::RunMode::Serve {
+        exit(serve_todos(&conf));
+    }
+    if conf.mode == conf::RunMode::Lsp {
+        // Todos of task lists are used only for completion, so the server works without them
+        let tasks = load_task_lists(&conf).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            Vec::new()
+        });
+        if let Err(e) = lsp::run(&conf, tasks) {
+            eprintln!("{e}");
+            exit(1);
+        }
+        exit(0);
+    }
+    if conf.mode == conf::RunMode::Sync {
+        if let Err(e) = vcs::sync(&conf) {
+            eprintln!("{e}");
+            exit(1);
+        }
+        exit(0);
+    }
 
     let mut tasks: todo::TaskVec = match load_task_lists(&conf) {
         Err(e) => {
@@ -1311,6 +2541,26 @@ fn main() {
         }
         Ok(tlist) => tlist,
     };
+    if conf.mode == conf::RunMode::Complete {
+        print_candidates(&tasks, &conf);
+        exit(0);
+    }
+    // Status is called by prompts often, so it skips archives, auto cleaning, and formatting
+    if conf.mode == conf::RunMode::Status {
+        exit(print_status(&tasks, &conf));
+    }
+    if conf.with_archive {
+        match load_done_lists(&conf) {
+            Err(e) => {
+                eprintln!("{e:?}");
+                exit(1);
+            }
+            Ok(mut tlist) => {
+                conf.archived_from = Some(tasks.len());
+                tasks.append(&mut tlist);
+            }
+        }
+    }
     conf.fmt.max = tasks.len();
 
     if conf.mode == conf::RunMode::None {
@@ -1325,6 +2575,20 @@ fn main() {
         eprintln!("Invalid command: when using done.txt the only available command is `list`");
         exit(1);
     }
+    let wildcard_archive = conf.task_lists.iter().any(|tl| wildcard::is_wildcard(&tl.done_file));
+    let changes_archive = conf.with_archive
+        || conf.mode == conf::RunMode::Clean
+        || (conf.mode == conf::RunMode::Transfer && conf.move_archived)
+        || conf.mode == conf::RunMode::Unarchive
+        || (conf.mode == conf::RunMode::Done && conf.archive_on_done);
+    if changes_archive && wildcard_archive {
+        eprintln!("Invalid command: archive file path with wildcards can be used only to display archived todos");
+        exit(1);
+    }
+    if conf.with_archive && !conf::can_run_with_archive(conf.mode) {
+        eprintln!("Invalid command: the command cannot be used for merged active and archived todos");
+        exit(1);
+    }
 
     let mut stdout = match conf.fmt.color_term {
         fmt::TermColorType::Ansi => StandardStream::stdout(ColorChoice::AlwaysAnsi),
@@ -1334,10 +2598,11 @@ fn main() {
 
     // Grouping is available only in List mode
     if conf.mode != conf::RunMode::List {
-        conf.fmt.group = None;
+        conf.fmt.group.clear();
     }
     let err = match conf.mode {
         conf::RunMode::Add => task_add(&mut stdout, &mut tasks, &mut conf),
+        conf::RunMode::List if conf.watch => task_list_watch(&mut stdout, tasks, &conf),
         conf::RunMode::List => {
             if conf.calendar.is_none() {
                 task_list(&mut stdout, &tasks, &conf)
@@ -1354,12 +2619,18 @@ fn main() {
         conf::RunMode::Prepend => task_add_text(&mut stdout, &mut tasks, &conf, false),
         conf::RunMode::Start => task_start_stop(&mut stdout, &mut tasks, &conf, true),
         conf::RunMode::Stop => task_start_stop(&mut stdout, &mut tasks, &conf, false),
+        conf::RunMode::Pomodoro => task_pomodoro(&mut stdout, &mut tasks, &conf),
         conf::RunMode::Stats => stats::show_stats(&mut stdout, &tasks, &conf.fmt),
         conf::RunMode::Postpone => task_postpone(&mut stdout, &mut tasks, &conf),
         conf::RunMode::ListProjects => task_list_projects(&mut stdout, &tasks, &conf),
         conf::RunMode::ListContexts => task_list_contexts(&mut stdout, &tasks, &conf),
         conf::RunMode::ListHashtags => task_list_hashtags(&mut stdout, &tasks, &conf),
         conf::RunMode::Agenda => task_list_agenda(&mut stdout, &tasks, &conf),
+        conf::RunMode::Board => task_list_board(&mut stdout, &tasks, &conf),
+        conf::RunMode::Move => task_move(&mut stdout, &mut tasks, &conf),
+        conf::RunMode::Search => task_search(&mut stdout, &tasks, &conf),
+        conf::RunMode::Unarchive => task_unarchive(&mut stdout, &tasks, &conf),
+        conf::RunMode::Transfer => task_transfer(&mut stdout, &mut tasks, &conf),
         _ => Ok(()),
     };
     if let Err(e) = err
@@ -1368,4 +2639,37 @@ fn main() {
         eprintln!("{e}");
         std::process::exit(1);
     }
+    if conf.git_commit
+        && !conf.dry
+        && let Some(action) = conf::change_name(conf.mode)
+    {
+        commit_changes(&conf, action);
+    }
+    // Old todos are archived only after a command that changes todos has done its job: the command
+    // selects todos by IDs that a user saw before, and archiving shifts them
+    if conf.auto_clean_days > 0
+        && !conf.dry
+        && !conf.use_done
+        && !conf.with_archive
+        && !wildcard_archive
+        && conf::change_name(conf.mode).is_some()
+    {
+        // The command may have changed the files without updating the list in memory
+        let mut tasks = match load_task_lists(&conf) {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1);
+            }
+        };
+        match auto_clean(&mut stdout, &mut tasks, &conf) {
+            Ok(0) => {}
+            Ok(_) if conf.git_commit => commit_changes(&conf, "clean"),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{e:?}");
+                exit(1);
+            }
+        }
+    }
 }
@@ -0,0 +1,324 @@
+use chrono::NaiveDate;
+use todo_lib::{todo, todotxt};
+
+// The tag that identifies a todo regardless of its text
+const ID_TAG: &str = "id";
+
+// A true conflict: both sides changed the same field of a todo differently. Ours value is kept
+pub struct Conflict {
+    pub todo: String,
+    pub field: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+// Returns true if the word of a subject is a project, context, hashtag, or tag
+fn is_tag_word(word: &str) -> bool {
+    word.starts_with(['+', '@', '#']) || word.find(':').is_some_and(|pos| pos > 0)
+}
+
+// The text of a todo without projects, contexts, and tags
+fn plain_text(task: &todotxt::Task) -> String {
+    let words: Vec<&str> = task.subject.split_whitespace().filter(|w| !is_tag_word(w)).collect();
+    if words.is_empty() { task.subject.trim().to_string() } else { words.join(" ") }
+}
+
+// The key to find the same todo in different versions of a file
+fn task_key(task: &todotxt::Task) -> String {
+    match task.tags.get(ID_TAG) {
+        Some(id) => format!("{ID_TAG}:{id}"),
+        None => plain_text(task).to_lowercase(),
+    }
+}
+
+// Returns the merged value, or None if both sides changed the value differently
+fn pick<T: PartialEq + Clone>(base: Option<&T>, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs {
+        return Some(ours.clone());
+    }
+    match base {
+        Some(b) if b == ours => Some(theirs.clone()),
+        Some(b) if b == theirs => Some(ours.clone()),
+        _ => None,
+    }
+}
+
+// Merges lists of projects or contexts: an item stays if both sides have it or one side has added it
+fn merge_set(base: Option<&Vec<String>>, ours: &[String], theirs: &[String]) -> Vec<String> {
+    let in_base = |s: &String| base.is_some_and(|b| b.contains(s));
+    let mut res: Vec<String> = Vec::new();
+    for item in ours.iter().chain(theirs.iter()) {
+        let keep = (ours.contains(item) && theirs.contains(item)) || !in_base(item);
+        if keep && !res.contains(item) {
+            res.push(item.clone());
+        }
+    }
+    res
+}
+
+fn completion_str(v: &(bool, Option<NaiveDate>)) -> String {
+    match v {
+        (false, _) => "incomplete".to_string(),
+        (true, None) => "completed".to_string(),
+        (true, Some(dt)) => format!("completed on {dt}"),
+    }
+}
+
+fn priority_str(p: u8) -> String {
+    if p < todotxt::NO_PRIORITY { format!("({0})", (b'A' + p) as char) } else { "none".to_string() }
+}
+
+fn opt_str<T: ToString>(v: &Option<T>) -> String {
+    match v {
+        Some(v) => v.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+// Merges two versions of the same todo field by field
+fn merge_tasks(
+    base: Option<&todotxt::Task>,
+    ours: &todotxt::Task,
+    theirs: &todotxt::Task,
+    conflicts: &mut Vec<Conflict>,
+) -> todotxt::Task {
+    let mut found: Vec<(String, String, String)> = Vec::new();
+
+    // The text is taken from the side that changed it, and then all fields are set to merged values
+    let (o_text, t_text) = (plain_text(ours), plain_text(theirs));
+    let mut res = match pick(base.map(plain_text).as_ref(), &o_text, &t_text) {
+        Some(txt) if txt != o_text => theirs.clone(),
+        Some(_) => ours.clone(),
+        None => {
+            found.push(("text".to_string(), o_text, t_text));
+            ours.clone()
+        }
+    };
+
+    let completion = |t: &todotxt::Task| (t.finished, t.finish_date);
+    let (o_cmpl, t_cmpl) = (completion(ours), completion(theirs));
+    let (finished, finish_date) = pick(base.map(completion).as_ref(), &o_cmpl, &t_cmpl).unwrap_or_else(|| {
+        found.push(("completion".to_string(), completion_str(&o_cmpl), completion_str(&t_cmpl)));
+        o_cmpl
+    });
+    res.finished = finished;
+    res.finish_date = finish_date;
+
+    res.priority = pick(base.map(|t| t.priority).as_ref(), &ours.priority, &theirs.priority).unwrap_or_else(|| {
+        found.push(("priority".to_string(), priority_str(ours.priority), priority_str(theirs.priority)));
+        ours.priority
+    });
+
+    res.create_date = pick(base.map(|t| t.create_date).as_ref(), &ours.create_date, &theirs.create_date)
+        .unwrap_or_else(|| {
+            found.push(("creation date".to_string(), opt_str(&ours.create_date), opt_str(&theirs.create_date)));
+            ours.create_date
+        });
+
+    let projects = merge_set(base.map(|t| &t.projects), &ours.projects, &theirs.projects);
+    for prj in res.projects.clone() {
+        if !projects.contains(&prj) {
+            res.replace_project(&prj, "");
+        }
+    }
+    for prj in projects {
+        res.replace_project("", &prj);
+    }
+    let contexts = merge_set(base.map(|t| &t.contexts), &ours.contexts, &theirs.contexts);
+    for ctx in res.contexts.clone() {
+        if !contexts.contains(&ctx) {
+            res.replace_context(&ctx, "");
+        }
+    }
+    for ctx in contexts {
+        res.replace_context("", &ctx);
+    }
+
+    let mut keys: Vec<&String> = ours.tags.keys().chain(theirs.tags.keys()).collect();
+    if let Some(b) = base {
+        keys.extend(b.tags.keys());
+    }
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        // A tag missing in the base is the tag added by one of the sides
+        let b_val = base.map(|b| b.tags.get(key).cloned()).unwrap_or(None);
+        let (o_val, t_val) = (ours.tags.get(key).cloned(), theirs.tags.get(key).cloned());
+        let val = pick(Some(&b_val), &o_val, &t_val).unwrap_or_else(|| {
+            found.push((format!("tag '{key}'"), opt_str(&o_val), opt_str(&t_val)));
+            o_val
+        });
+        res.update_tag_with_value(key, val.as_deref().unwrap_or(""));
+    }
+
+    let todo = res.to_string();
+    for (field, ours, theirs) in found {
+        conflicts.push(Conflict { todo: todo.clone(), field, ours, theirs });
+    }
+    res
+}
+
+// Finds the first todo with the key that is not matched yet
+fn take_match(tasks: &todo::TaskSlice, keys: &[String], used: &mut [bool], key: &str) -> Option<usize> {
+    let idx = (0..tasks.len()).find(|&i| !used[i] && keys[i] == key)?;
+    used[idx] = true;
+    Some(idx)
+}
+
+fn parse_list(text: &str, today: NaiveDate) -> todo::TaskVec {
+    text.lines().filter(|l| !l.trim().is_empty()).map(|l| todotxt::Task::parse(l, today)).collect()
+}
+
+// Three-way merge of todo lists. Todos are matched by tag `id` or by their text without tags,
+// so the order of todos does not matter. Changes of completion, priority, projects, contexts,
+// and tags made on different sides are combined. If both sides change the same field, or one
+// side changes a todo that the other side removes, ours version is kept and the conflict is reported.
+pub fn merge(base: &str, ours: &str, theirs: &str, today: NaiveDate) -> (todo::TaskVec, Vec<Conflict>) {
+    let (base, ours, theirs) = (parse_list(base, today), parse_list(ours, today), parse_list(theirs, today));
+    let keys = |l: &todo::TaskSlice| -> Vec<String> { l.iter().map(task_key).collect() };
+    let (b_keys, o_keys, t_keys) = (keys(&base), keys(&ours), keys(&theirs));
+    let mut b_used = vec![false; base.len()];
+    let mut t_used = vec![false; theirs.len()];
+    let mut res = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (o, key) in ours.iter().zip(o_keys.iter()) {
+        let b = take_match(&base, &b_keys, &mut b_used, key).map(|i| &base[i]);
+        let t = take_match(&theirs, &t_keys, &mut t_used, key).map(|i| &theirs[i]);
+        match (b, t) {
+            (_, Some(t)) => res.push(merge_tasks(b, o, t, &mut conflicts)),
+            (None, None) => res.push(o.clone()),
+            (Some(b), None) => {
+                // Removed by theirs: keep it only if ours changed the todo
+                if b.to_string() != o.to_string() {
+                    let todo = o.to_string();
+                    let field = "todo".to_string();
+                    conflicts.push(Conflict {
+                        todo,
+                        field,
+                        ours: "changed".to_string(),
+                        theirs: "removed".to_string(),
+                    });
+                    res.push(o.clone());
+                }
+            }
+        }
+    }
+    for (idx, t) in theirs.iter().enumerate() {
+        if t_used[idx] {
+            continue;
+        }
+        match take_match(&base, &b_keys, &mut b_used, &t_keys[idx]) {
+            None => res.push(t.clone()),
+            Some(b_idx) => {
+                // Removed by ours: keep it only if theirs changed the todo
+                if base[b_idx].to_string() != t.to_string() {
+                    let todo = t.to_string();
+                    let field = "todo".to_string();
+                    conflicts.push(Conflict {
+                        todo,
+                        field,
+                        ours: "removed".to_string(),
+                        theirs: "changed".to_string(),
+                    });
+                    res.push(t.clone());
+                }
+            }
+        }
+    }
+    (res, conflicts)
+}
+
+// Merges files and returns the merged text and conflicts
+pub fn merge_text(base: &str, ours: &str, theirs: &str, today: NaiveDate) -> (String, Vec<Conflict>) {
+    let (tasks, conflicts) = merge(base, ours, theirs, today);
+    let mut text = String::new();
+    for task in tasks {
+        text += &format!("{task}\n");
+    }
+    (text, conflicts)
+}
+
+pub fn conflict_report(conflicts: &[Conflict]) -> String {
+    let mut report = String::new();
+    for c in conflicts {
+        report += &format!(
+            "Conflict in '{0}': {1} is '{2}' in ours and '{3}' in theirs\n",
+            c.todo, c.field, c.ours, c.theirs
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_test() {
+        struct Test {
+            base: &'static str,
+            ours: &'static str,
+            theirs: &'static str,
+            res: &'static str,
+            conflicts: usize,
+        }
+        let tests: Vec<Test> = vec![
+            // both sides add a todo
+            Test { base: "a\nb\n", ours: "a\nb\nc\n", theirs: "a\nb\nd\n", res: "a\nb\nc\nd\n", conflicts: 0 },
+            // they complete a todo, we add one
+            Test { base: "a\nb\n", ours: "a\nb\nc\n", theirs: "a\nx b\n", res: "a\nx b\nc\n", conflicts: 0 },
+            // both sides add the same todo
+            Test { base: "a\n", ours: "a\nc\n", theirs: "a\nc\n", res: "a\nc\n", conflicts: 0 },
+            // we remove a todo, they do not touch it
+            Test { base: "a\nb\n", ours: "b\n", theirs: "a\nb\n", res: "b\n", conflicts: 0 },
+            // duplicated todos are counted
+            Test { base: "a\na\n", ours: "a\na\n", theirs: "a\n", res: "a\n", conflicts: 0 },
+            // different fields of the same todo are changed
+            Test {
+                base: "call mom +family\n",
+                ours: "(A) call mom +family\n",
+                theirs: "x call mom +family @phone due:2026-10-20\n",
+                res: "x (A) call mom +family @phone due:2026-10-20\n",
+                conflicts: 0,
+            },
+            // we remove a project, they add a context
+            Test { base: "a +p +q\n", ours: "a +p\n", theirs: "a +p +q @c\n", res: "a +p @c\n", conflicts: 0 },
+            // the same field is changed differently
+            Test { base: "a\n", ours: "(A) a\n", theirs: "(B) a\n", res: "(A) a\n", conflicts: 1 },
+            Test {
+                base: "a due:2026-10-01\n",
+                ours: "a due:2026-10-02\n",
+                theirs: "a due:2026-10-03\n",
+                res: "a due:2026-10-02\n",
+                conflicts: 1,
+            },
+            // todos are matched by ID even if their text is different
+            Test {
+                base: "a id:1\n",
+                ours: "new text id:1\n",
+                theirs: "x a id:1\n",
+                res: "x new text id:1\n",
+                conflicts: 0,
+            },
+            // a todo changed by us and removed by them
+            Test { base: "a\nb\n", ours: "(A) a\nb\n", theirs: "b\n", res: "(A) a\nb\n", conflicts: 1 },
+            // a todo removed by us and changed by them
+            Test { base: "a\nb\n", ours: "b\n", theirs: "(A) a\nb\n", res: "b\n(A) a\n", conflicts: 1 },
+        ];
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        for (idx, test) in tests.iter().enumerate() {
+            let (res, conflicts) = merge_text(test.base, test.ours, test.theirs, today);
+            assert_eq!(res, test.res, "{idx}");
+            assert_eq!(conflicts.len(), test.conflicts, "{idx}");
+        }
+    }
+
+    #[test]
+    fn merge_set_test() {
+        let v = |s: &[&str]| -> Vec<String> { s.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(merge_set(Some(&v(&["a", "b"])), &v(&["a", "c"]), &v(&["a", "b", "d"])), v(&["a", "c", "d"]));
+        assert_eq!(merge_set(None, &v(&["a"]), &v(&["b"])), v(&["a", "b"]));
+        assert_eq!(merge_set(Some(&v(&["a"])), &v(&["a"]), &v(&[])), v(&[]));
+    }
+}
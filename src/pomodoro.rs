@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use termcolor::StandardStream;
+use todo_lib::todo;
+
+use crate::conf;
+
+// The tag that keeps the number of completed pomodoros of a todo
+pub const TAG: &str = "pomo";
+
+// Runs the notification command from the config, if it is set
+pub fn notify(conf: &conf::Conf, msg: &str) {
+    let Some(notify) = &conf.pomodoro_notify else {
+        return;
+    };
+    let shell = &conf.fmt.shell;
+    let res = Command::new(&shell[0]).args(&shell[1..]).arg(notify).env("TTDL_MESSAGE", msg).status();
+    match res {
+        Ok(st) if !st.success() => eprintln!("Notification command failed: {st}"),
+        Err(e) => eprintln!("Failed to run notification command: {e}"),
+        _ => {}
+    }
+}
+
+// Sends a message every time a user presses Enter. The thread ends at the end of input, so
+// a closed or redirected stdin never interrupts a countdown
+pub fn enter_presses() -> mpsc::Receiver<()> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        while io::stdin().read_line(&mut line).is_ok_and(|n| n > 0) {
+            if tx.send(()).is_err() {
+                break;
+            }
+            line.clear();
+        }
+    });
+    rx
+}
+
+// Counts the time down, and shows the time left if the output is a terminal.
+// Returns false if the countdown is interrupted by pressing Enter
+pub fn countdown(
+    stdout: &mut StandardStream,
+    secs: i64,
+    title: &str,
+    enter: &mpsc::Receiver<()>,
+    conf: &conf::Conf,
+) -> io::Result<bool> {
+    let end = Instant::now() + Duration::from_secs(secs as u64);
+    let mut completed = true;
+    loop {
+        let left = end.saturating_duration_since(Instant::now());
+        if conf.fmt.atty {
+            let left_secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+            write!(stdout, "\r{title}: {0:02}:{1:02} left ", left_secs / 60, left_secs % 60)?;
+            stdout.flush()?;
+        }
+        if left.is_zero() {
+            break;
+        }
+        let wait = left.min(Duration::from_secs(1));
+        match enter.recv_timeout(wait) {
+            Ok(()) => {
+                completed = false;
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => std::thread::sleep(wait),
+        }
+    }
+    if conf.fmt.atty {
+        writeln!(stdout)?;
+    }
+    Ok(completed)
+}
+
+// Looks for the todo which text was `started` when its pomodoro began. The todo is looked up
+// by its text because its ID changes if other todos are removed. The old ID is preferred if
+// a few todos have the same text
+pub fn find_started(tasks: &todo::TaskSlice, id: usize, started: &str) -> Option<usize> {
+    if tasks.get(id).is_some_and(|t| t.to_string() == started) {
+        return Some(id);
+    }
+    tasks.iter().position(|t| t.to_string() == started)
+}
+
+// Stops the timer of the todo. If the pomodoro is completed, increments the number of
+// pomodoros of the todo. Returns the number of completed pomodoros
+pub fn finish(tasks: &mut todo::TaskVec, id: usize, completed: bool) -> u32 {
+    todo::stop(tasks, Some(&vec![id]));
+    let count = tasks[id].tags.get(TAG).and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+    if !completed {
+        return count;
+    }
+    tasks[id].update_tag_with_value(TAG, &(count + 1).to_string());
+    count + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use todo_lib::{timer, todotxt};
+
+    #[test]
+    fn find_started_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let parse =
+            |lines: &[&str]| -> todo::TaskVec { lines.iter().map(|l| todotxt::Task::parse(l, today)).collect() };
+        let started = "write report tmr:1715328000";
+        // Nothing has changed
+        let tasks = parse(&["call mom", "write report tmr:1715328000"]);
+        assert_eq!(find_started(&tasks, 1, started), Some(1));
+        // A todo before it is removed
+        let tasks = parse(&["write report tmr:1715328000", "new todo"]);
+        assert_eq!(find_started(&tasks, 1, started), Some(0));
+        // The old ID wins if a few todos look the same
+        let tasks = parse(&["write report tmr:1715328000", "write report tmr:1715328000"]);
+        assert_eq!(find_started(&tasks, 1, started), Some(1));
+        // The todo is edited
+        let tasks = parse(&["call mom", "write report +work tmr:1715328000"]);
+        assert_eq!(find_started(&tasks, 1, started), None);
+    }
+
+    #[test]
+    fn finish_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let mut tasks: todo::TaskVec = ["first tmr:1715328000", "second pomo:2 tmr:1715328000", "third tmr:1715328000"]
+            .iter()
+            .map(|l| todotxt::Task::parse(l, today))
+            .collect();
+        assert_eq!(finish(&mut tasks, 0, true), 1);
+        assert_eq!(finish(&mut tasks, 1, true), 3);
+        assert_eq!(finish(&mut tasks, 2, false), 0);
+        assert_eq!(tasks[0].tags.get(TAG), Some(&"1".to_string()));
+        assert_eq!(tasks[1].tags.get(TAG), Some(&"3".to_string()));
+        assert_eq!(tasks[2].tags.get(TAG), None);
+        assert!(tasks.iter().all(|t| !timer::is_timer_on(t)));
+    }
+}
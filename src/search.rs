@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use termcolor::{Color, StandardStream, WriteColor};
+use todo_lib::{todo, todotxt};
+
+use crate::conf;
+use crate::fmt;
+
+// Scores of a single query token depending on how it matches a word of a task subject
+const SCORE_EXACT: f64 = 3.0;
+const SCORE_PREFIX: f64 = 2.0;
+const SCORE_WILDCARD: f64 = 2.0;
+const SCORE_SUBSTR: f64 = 1.0;
+const SCORE_FUZZY: f64 = 0.5;
+// Maximum bonuses for recently changed tasks and for tasks with high priority
+const BONUS_RECENT: f64 = 0.5;
+const BONUS_PRIORITY: f64 = 0.5;
+// The number of days after which the recency bonus decreases twice
+const RECENT_DAYS: f64 = 30.0;
+// Fuzzy matching is used only for tokens of this length or longer
+const FUZZY_MIN_LEN: usize = 4;
+const ARCHIVED_ID: &str = "arc";
+
+// A task found by a query
+pub struct Hit {
+    pub idx: usize,        // index of the task in the list of all searched tasks
+    pub id: Option<usize>, // task ID in the active list. None for archived tasks
+    pub score: f64,
+}
+
+// Splits a query into lowercase tokens. Tokens are separated with whitespaces
+pub fn parse_query(query: &str) -> Vec<String> {
+    query.split_whitespace().map(|s| s.to_lowercase()).collect()
+}
+
+// Strips punctuation and a leading project/context/hashtag marker from a subject word
+fn normalize_word(word: &str) -> String {
+    let w = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '*');
+    w.to_lowercase()
+}
+
+// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+// Matches a word against a pattern where `*` means any number of any characters
+fn wildcard_match(pattern: &str, word: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let mut rest = word;
+    for (idx, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if idx == 0 {
+            if !rest.starts_with(part) {
+                return false;
+            }
+            rest = &rest[part.len()..];
+        } else if idx == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                None => return false,
+                Some(pos) => rest = &rest[pos + part.len()..],
+            }
+        }
+    }
+    true
+}
+
+// Returns how well a token matches a word. Zero means no match.
+fn token_score(token: &str, word: &str) -> f64 {
+    if word.is_empty() {
+        return 0.0;
+    }
+    if token.contains('*') {
+        return if wildcard_match(token, word) { SCORE_WILDCARD } else { 0.0 };
+    }
+    if token == word {
+        SCORE_EXACT
+    } else if word.starts_with(token) {
+        SCORE_PREFIX
+    } else if word.contains(token) {
+        SCORE_SUBSTR
+    } else if token.chars().count() >= FUZZY_MIN_LEN && edit_distance(token, word) <= token.chars().count() / 3 {
+        SCORE_FUZZY
+    } else {
+        0.0
+    }
+}
+
+// Returns true if a subject word matches any of tokens
+fn is_hit(word: &str, tokens: &[String]) -> bool {
+    let word = normalize_word(word);
+    tokens.iter().any(|t| token_score(t, &word) > 0.0)
+}
+
+// Calculates the relevance of a task. Zero means that the task does not match the query.
+pub fn score_task(task: &todotxt::Task, tokens: &[String], today: NaiveDate) -> f64 {
+    let words: Vec<String> = task.subject.split_whitespace().map(normalize_word).collect();
+    let mut score = 0.0;
+    for token in tokens {
+        let best = words.iter().map(|w| token_score(token, w)).fold(0.0, f64::max);
+        score += best;
+    }
+    if score == 0.0 {
+        return 0.0;
+    }
+    if let Some(dt) = task.finish_date.or(task.create_date).or(task.due_date) {
+        let days = (today - dt).num_days().abs() as f64;
+        score += BONUS_RECENT * RECENT_DAYS / (RECENT_DAYS + days);
+    }
+    if task.priority < todotxt::NO_PRIORITY {
+        score += BONUS_PRIORITY * f64::from(todotxt::NO_PRIORITY - task.priority) / f64::from(todotxt::NO_PRIORITY);
+    }
+    score
+}
+
+// Searches the tasks and returns the matched ones, the most relevant first.
+// `active` is the number of active tasks at the beginning of the list: their IDs are their indices.
+pub fn search(
+    tasks: &todo::TaskSlice,
+    selected: &todo::IDSlice,
+    active: usize,
+    tokens: &[String],
+    today: NaiveDate,
+) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = Vec::new();
+    for idx in selected {
+        let score = score_task(&tasks[*idx], tokens, today);
+        if score > 0.0 {
+            let id = if *idx < active { Some(*idx) } else { None };
+            hits.push(Hit { idx: *idx, id, score });
+        }
+    }
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal).then(a.idx.cmp(&b.idx)));
+    hits
+}
+
+pub fn print_hits(
+    stdout: &mut StandardStream,
+    tasks: &todo::TaskSlice,
+    hits: &[Hit],
+    tokens: &[String],
+    conf: &conf::Conf,
+) -> io::Result<()> {
+    if hits.is_empty() {
+        return writeln!(stdout, "No todo found");
+    }
+    let mut hl = conf.fmt.colors.default_fg.clone();
+    hl.set_fg(Some(Color::Yellow));
+    hl.set_bold(true);
+    // Hits must be highlighted with the same color regardless of syntax highlighting
+    let mut plain = conf.fmt.clone();
+    plain.syntax = false;
+    let id_width = hits.iter().filter_map(|h| h.id).map(|id| fmt::number_of_digits(id + 1)).max().unwrap_or(0);
+    let id_width = id_width.max(ARCHIVED_ID.len());
+    let default_fg = &conf.fmt.colors.default_fg;
+    for hit in hits {
+        let task = &tasks[hit.idx];
+        let id = match hit.id {
+            Some(id) => format!("{}", id + 1),
+            None => ARCHIVED_ID.to_string(),
+        };
+        let color = if task.finished { &conf.fmt.colors.done } else { default_fg };
+        fmt::print_with_highlight(stdout, &format!("{id:>id_width$} "), color, &plain)?;
+        if !conf.is_single_file_mode()
+            && let Some(src) = &task.source
+        {
+            fmt::print_with_highlight(stdout, &format!("[{}] ", src.name), color, &plain)?;
+        }
+        let prefix = format!("{}{}", fmt::done_str(task), fmt::priority_str(task));
+        fmt::print_with_highlight(stdout, &prefix, color, &plain)?;
+        for word in task.subject.split_inclusive(' ') {
+            let trimmed = word.trim_end();
+            if is_hit(trimmed, tokens) {
+                fmt::print_with_highlight(stdout, trimmed, &hl, &plain)?;
+                fmt::print_with_highlight(stdout, &word[trimmed.len()..], color, &plain)?;
+            } else {
+                fmt::print_with_highlight(stdout, word, color, &conf.fmt)?;
+            }
+        }
+        stdout.set_color(default_fg)?;
+        writeln!(stdout)?;
+    }
+    writeln!(stdout, "{} todos found", hits.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_test() {
+        struct Test {
+            a: &'static str,
+            b: &'static str,
+            d: usize,
+        }
+        let tests: Vec<Test> = vec![
+            Test { a: "", b: "", d: 0 },
+            Test { a: "abc", b: "", d: 3 },
+            Test { a: "kitten", b: "sitting", d: 3 },
+            Test { a: "report", b: "reprot", d: 2 },
+            Test { a: "milk", b: "milk", d: 0 },
+        ];
+        for test in tests.iter() {
+            let d = edit_distance(test.a, test.b);
+            assert_eq!(test.d, d, "{} - {}: {} != {}", test.a, test.b, test.d, d);
+        }
+    }
+
+    #[test]
+    fn token_score_test() {
+        struct Test {
+            token: &'static str,
+            word: &'static str,
+            score: f64,
+        }
+        let tests: Vec<Test> = vec![
+            Test { token: "milk", word: "milk", score: SCORE_EXACT },
+            Test { token: "mil", word: "milk", score: SCORE_PREFIX },
+            Test { token: "ilk", word: "milk", score: SCORE_SUBSTR },
+            Test { token: "m*k", word: "milk", score: SCORE_WILDCARD },
+            Test { token: "m*x", word: "milk", score: 0.0 },
+            Test { token: "reprot", word: "report", score: SCORE_FUZZY },
+            Test { token: "rprt", word: "report", score: 0.0 },
+            Test { token: "reportt", word: "report", score: SCORE_FUZZY },
+            Test { token: "cat", word: "car", score: 0.0 },
+        ];
+        for test in tests.iter() {
+            let score = token_score(test.token, test.word);
+            assert_eq!(test.score, score, "{} - {}: {} != {}", test.token, test.word, test.score, score);
+        }
+    }
+
+    #[test]
+    fn search_test() {
+        let today = NaiveDate::from_ymd_opt(2020, 3, 10).unwrap();
+        let tasks: todo::TaskVec = vec![
+            todotxt::Task::parse("buy milk and bread", today),
+            todotxt::Task::parse("call mom", today),
+            todotxt::Task::parse("(A) buy milk", today),
+            todotxt::Task::parse("x 2020-03-09 2020-01-01 milkshake recipe", today),
+            todotxt::Task::parse("x 2019-01-09 2019-01-01 bread", today),
+        ];
+        let selected: todo::IDVec = (0..tasks.len()).collect();
+        let tokens = parse_query("Milk bread");
+        let hits = search(&tasks, &selected, 3, &tokens, today);
+        let res: Vec<(usize, Option<usize>)> = hits.iter().map(|h| (h.idx, h.id)).collect();
+        assert_eq!(res, vec![(0, Some(0)), (2, Some(2)), (4, None), (3, None)]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Duration;
+
+use todo_lib::todotxt;
+
+// Requests with a larger body are refused
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Request {
+    pub method: String,
+    // Non-empty parts of the path, e.g. `["todos", "3", "done"]`
+    pub path: Vec<String>,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+    // Query parameters as command-line options: `due=soon` turns into `--due=soon`, and `all` into `--all`
+    pub fn options(&self) -> Vec<String> {
+        self.query
+            .iter()
+            .map(|(k, v)| {
+                let dashes = if k.chars().count() == 1 { "-" } else { "--" };
+                if v.is_empty() { format!("{dashes}{k}") } else { format!("{dashes}{k}={v}") }
+            })
+            .collect()
+    }
+    // Returns a string field of the JSON body. An empty body has no fields
+    pub fn body_field(&self, name: &str) -> Result<Option<String>, String> {
+        if self.body.trim().is_empty() {
+            return Ok(None);
+        }
+        let body = json::parse(&self.body).map_err(|e| format!("Invalid JSON: {e}"))?;
+        if !body.is_object() {
+            return Err("JSON object expected".to_string());
+        }
+        match &body[name] {
+            json::JsonValue::Null => Ok(None),
+            v => match v.as_str() {
+                Some(s) => Ok(Some(s.to_string())),
+                None => Err(format!("Field '{name}' must be a string")),
+            },
+        }
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: json::JsonValue,
+    pub etag: Option<String>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: json::JsonValue) -> Self {
+        Response { status, body, etag: None }
+    }
+    pub fn error(status: u16, msg: &str) -> Self {
+        Response::new(status, json::object! { "error" => msg })
+    }
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+// Decodes `%XX` sequences and pluses of a URL query
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => res.push(b' '),
+            b'%' if idx + 2 < bytes.len() => {
+                let hi = (bytes[idx + 1] as char).to_digit(16);
+                let lo = (bytes[idx + 2] as char).to_digit(16);
+                match (hi, lo) {
+                    (Some(hi), Some(lo)) => {
+                        res.push((hi * 16 + lo) as u8);
+                        idx += 2;
+                    }
+                    _ => res.push(b'%'),
+                }
+            }
+            b => res.push(b),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&res).to_string()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split_once('=') {
+            Some((k, v)) => (percent_decode(k), percent_decode(v)),
+            None => (percent_decode(p), String::new()),
+        })
+        .collect()
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let bad_request = |msg: &str| Response::error(400, msg);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| bad_request(&e.to_string()))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(bad_request("Invalid request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut req = Request {
+        method: method.to_uppercase(),
+        path: path.split('/').filter(|p| !p.is_empty()).map(percent_decode).collect(),
+        query: parse_query(query),
+        headers: Vec::new(),
+        body: String::new(),
+    };
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| bad_request(&e.to_string()))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            req.headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let size: usize = match req.header("content-length") {
+        None => 0,
+        Some(v) => v.parse().map_err(|_| bad_request("Invalid Content-Length"))?,
+    };
+    if size > MAX_BODY_SIZE {
+        return Err(Response::error(413, "Request body is too large"));
+    }
+    let mut body = vec![0u8; size];
+    reader.read_exact(&mut body).map_err(|e| bad_request(&e.to_string()))?;
+    req.body = String::from_utf8(body).map_err(|_| bad_request("Request body is not UTF-8"))?;
+    Ok(req)
+}
+
+fn write_response(out: &mut impl Write, resp: &Response) -> io::Result<()> {
+    let body = resp.body.dump();
+    write!(out, "HTTP/1.1 {0} {1}\r\n", resp.status, status_text(resp.status))?;
+    write!(out, "Content-Type: application/json\r\nContent-Length: {0}\r\nConnection: close\r\n", body.len())?;
+    if let Some(etag) = &resp.etag {
+        write!(out, "ETag: \"{etag}\"\r\n")?;
+    }
+    write!(out, "\r\n{body}")?;
+    out.flush()
+}
+
+fn date_json(date: Option<chrono::NaiveDate>) -> json::JsonValue {
+    match date {
+        Some(d) => d.format("%Y-%m-%d").to_string().into(),
+        None => json::JsonValue::Null,
+    }
+}
+
+// Converts a todo to JSON. `id` is the ID shown by `list`
+pub fn task_json(task: &todotxt::Task, id: usize) -> json::JsonValue {
+    let mut tags = json::JsonValue::new_object();
+    for (k, v) in &task.tags {
+        tags[k.as_str()] = v.as_str().into();
+    }
+    let priority = if task.priority < todotxt::NO_PRIORITY {
+        ((b'A' + task.priority) as char).to_string().into()
+    } else {
+        json::JsonValue::Null
+    };
+    json::object! {
+        "id" => id,
+        "list" => task.source.as_ref().filter(|s| !s.name.is_empty()).map(|s| s.name.clone()),
+        "text" => task.to_string(),
+        "subject" => task.subject.clone(),
+        "priority" => priority,
+        "done" => task.finished,
+        "created" => date_json(task.create_date),
+        "finished" => date_json(task.finish_date),
+        "due" => date_json(task.due_date),
+        "threshold" => date_json(task.threshold_date),
+        "projects" => task.projects.clone(),
+        "contexts" => task.contexts.clone(),
+        "hashtags" => task.hashtags.clone(),
+        "tags" => tags,
+    }
+}
+
+// Returns a value that changes every time any of the files is modified
+pub fn files_stamp(files: &[&Path]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        file.hash(&mut hasher);
+        if let Ok(meta) = fs::metadata(file) {
+            meta.len().hash(&mut hasher);
+            if let Ok(modified) = meta.modified() {
+                modified.hash(&mut hasher);
+            }
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+// Serves requests one by one, so changes of task lists never overlap
+pub fn run<F: FnMut(&Request) -> Response>(addr: &str, mut handler: F) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!("Listening on http://{0}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to accept a connection: {e}");
+                continue;
+            }
+        };
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let resp = match read_request(&mut BufReader::new(&stream)) {
+            Ok(req) => handler(&req),
+            Err(resp) => resp,
+        };
+        if let Err(e) = write_response(&mut stream, &resp) {
+            eprintln!("Failed to send a response: {e}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_test() {
+        assert_eq!(percent_decode("due%3Dsoon"), "due=soon");
+        assert_eq!(percent_decode("buy+milk"), "buy milk");
+        assert_eq!(percent_decode("%2Bproj%40ctx"), "+proj@ctx");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn read_request_test() {
+        let raw =
+            "PATCH /todos/3?set-pri=a&all HTTP/1.1\r\nHost: x\r\nContent-Length: 19\r\n\r\n{\"subject\": \"milk\"}";
+        let req = read_request(&mut raw.as_bytes()).ok().unwrap();
+        assert_eq!(req.method, "PATCH");
+        assert_eq!(req.path, vec!["todos", "3"]);
+        assert_eq!(req.options(), vec!["--set-pri=a", "--all"]);
+        assert_eq!(req.header("content-length"), Some("19"));
+        assert_eq!(req.body_field("subject"), Ok(Some("milk".to_string())));
+        assert_eq!(req.body_field("list"), Ok(None));
+
+        let raw = "GET /todos HTTP/1.1\r\nContent-Length: 2000000\r\n\r\n";
+        assert_eq!(read_request(&mut raw.as_bytes()).err().unwrap().status, 413);
+    }
+}
@@ -11,7 +11,7 @@ const PRJ_WIDTH: usize = 15;
 const CTX_WIDTH: usize = 10;
 const NUM_WIDTH: usize = 10;
 
-fn is_task_overdue(task: &todotxt::Task, today: chrono::NaiveDate) -> bool {
+pub(crate) fn is_task_overdue(task: &todotxt::Task, today: chrono::NaiveDate) -> bool {
     match task.due_date {
         None => false,
         Some(d) => {
@@ -0,0 +1,153 @@
+use chrono::NaiveDate;
+use todo_lib::{timer, todo};
+
+use crate::fmt;
+use crate::stats;
+
+pub const DEFAULT_FORMAT: &str = "{active} todos, {overdue} overdue, {today} due today";
+
+// Counters of the selected todos
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub total: usize,
+    pub active: usize,
+    pub done: usize,
+    pub overdue: usize,
+    pub today: usize,
+    pub soon: usize,
+    pub recurrent: usize,
+    // Subject and time spent of the todo with running timer
+    pub timer: Option<(String, chrono::Duration)>,
+}
+
+pub fn summary(tasks: &todo::TaskSlice, ids: &todo::IDSlice, today: NaiveDate, soon_days: u8) -> Summary {
+    let mut sum = Summary { total: ids.len(), ..Default::default() };
+    for &id in ids {
+        let task = &tasks[id];
+        if task.finished {
+            sum.done += 1;
+            continue;
+        }
+        sum.active += 1;
+        if stats::is_task_overdue(task, today) {
+            sum.overdue += 1;
+        }
+        if task.recurrence.is_some() {
+            sum.recurrent += 1;
+        }
+        if let Some(due) = task.due_date {
+            let days = (due - today).num_days();
+            if days == 0 {
+                sum.today += 1;
+            } else if days > 0 && days <= i64::from(soon_days) {
+                sum.soon += 1;
+            }
+        }
+        if sum.timer.is_none() && timer::is_timer_on(task) {
+            sum.timer = Some((task.subject.clone(), timer::spent_time(task)));
+        }
+    }
+    sum
+}
+
+fn value(name: &str, sum: &Summary) -> Option<String> {
+    let v = match name {
+        "total" => sum.total.to_string(),
+        "active" => sum.active.to_string(),
+        "done" => sum.done.to_string(),
+        "overdue" => sum.overdue.to_string(),
+        "today" => sum.today.to_string(),
+        "soon" => sum.soon.to_string(),
+        "recurrent" => sum.recurrent.to_string(),
+        "timer" => sum.timer.as_ref().map(|(s, _)| s.clone()).unwrap_or_default(),
+        "spent" => sum.timer.as_ref().map(|(_, d)| fmt::duration_str(*d)).unwrap_or_default(),
+        _ => return None,
+    };
+    Some(v)
+}
+
+// Replaces `{name}` in the format with the values of the summary. `{{` and `}}` are literal braces
+pub fn format_summary(format: &str, sum: &Summary) -> Result<String, String> {
+    let mut res = String::new();
+    let mut rest = format;
+    while let Some(pos) = rest.find(['{', '}']) {
+        res += &rest[..pos];
+        let tail = &rest[pos..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            res.push(tail.as_bytes()[0] as char);
+            rest = &tail[2..];
+            continue;
+        }
+        let Some(end) = tail.find('}').filter(|_| tail.starts_with('{')) else {
+            return Err(format!("Invalid format '{format}': unmatched brace"));
+        };
+        let name = &tail[1..end];
+        match value(name, sum) {
+            Some(v) => res += &v,
+            None => return Err(format!("Invalid format '{format}': unknown field '{name}'")),
+        }
+        rest = &tail[end + 1..];
+    }
+    res += rest;
+    Ok(res)
+}
+
+// Returns the summary in the format of custom modules of waybar: the formatted text, a tooltip
+// with all counters, and CSS classes to highlight the module
+pub fn summary_json(format: &str, sum: &Summary) -> Result<json::JsonValue, String> {
+    let text = format_summary(format, sum)?;
+    let mut tooltip = format!(
+        "Active: {0}\nOverdue: {1}\nDue today: {2}\nDue soon: {3}\nDone: {4}",
+        sum.active, sum.overdue, sum.today, sum.soon, sum.done
+    );
+    if let Some((subj, spent)) = &sum.timer {
+        tooltip += &format!("\nTimer: {subj} ({0})", fmt::duration_str(*spent));
+    }
+    let mut class = json::JsonValue::new_array();
+    if sum.overdue > 0 {
+        let _ = class.push("overdue");
+    }
+    if sum.today > 0 {
+        let _ = class.push("today");
+    }
+    if sum.timer.is_some() {
+        let _ = class.push("timer");
+    }
+    Ok(json::object! { "text" => text, "tooltip" => tooltip, "class" => class })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use todo_lib::todotxt;
+
+    #[test]
+    fn summary_test() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let lines = [
+            "overdue due:2024-05-01",
+            "today due:2024-05-10 rec:1w",
+            "soon due:2024-05-12",
+            "later due:2024-06-12",
+            "x done",
+            "x 2024-05-02 done before due date due:2024-05-08",
+            "timer tmr:1",
+        ];
+        let tasks: todo::TaskVec = lines.iter().map(|l| todotxt::Task::parse(l, today)).collect();
+        let ids: todo::IDVec = (0..tasks.len()).collect();
+        let sum = summary(&tasks, &ids, today, 3);
+        assert_eq!((sum.total, sum.active, sum.done), (7, 5, 2));
+        assert_eq!((sum.overdue, sum.today, sum.soon, sum.recurrent), (1, 1, 1, 1));
+        assert_eq!(sum.timer.map(|(s, _)| s), Some("timer tmr:1".to_string()));
+    }
+
+    #[test]
+    fn format_summary_test() {
+        let sum = Summary { active: 5, overdue: 2, today: 1, ..Default::default() };
+        assert_eq!(format_summary("{overdue}!{today}", &sum), Ok("2!1".to_string()));
+        assert_eq!(format_summary("{{{active}}} {timer}", &sum), Ok("{5} ".to_string()));
+        assert!(format_summary("{nope}", &sum).is_err());
+        assert!(format_summary("{active", &sum).is_err());
+        assert!(format_summary("active}", &sum).is_err());
+    }
+}
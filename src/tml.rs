@@ -35,8 +35,16 @@ pub struct Global {
     pub auto_show_columns: Option<bool>,
     pub always_hide_columns: Option<String>,
     pub priority_on_done: Option<String>,
+    pub archive_rotation: Option<String>,
+    pub archive_on_done: Option<bool>,
+    pub auto_clean_days: Option<u32>,
+    pub git_commit: Option<bool>,
+    pub git_remote: Option<String>,
+    pub confirm: Option<bool>,
+    pub confirm_count: Option<usize>,
     pub add_completion_date_always: Option<bool>,
     pub editor: Option<String>,
+    pub pomodoro_notify: Option<String>,
     pub hide_fields: Option<String>,
     pub date_format: Option<String>,
 }
@@ -82,12 +90,29 @@ pub struct Agenda {
     pub marks: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct Calendar {
+    pub fields: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Board {
+    pub field: Option<String>,
+    pub columns: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct Source {
     pub name: Option<String>,
     pub path: String,
     pub archive_path: Option<String>,
     pub default: Option<bool>,
+    pub creation_date_auto: Option<bool>,
+    pub priority_on_done: Option<String>,
+    pub projects: Option<String>,
+    pub contexts: Option<String>,
+    pub color: Option<String>,
+    pub readonly: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -100,5 +125,7 @@ pub struct Conf {
     pub markdown: Option<Markdown>,
     pub fields: Option<Vec<CustomField>>,
     pub agenda: Option<Agenda>,
+    pub calendar: Option<Calendar>,
+    pub board: Option<Board>,
     pub sources: Option<Vec<Source>>,
 }
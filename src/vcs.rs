@@ -0,0 +1,262 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::archive;
+use crate::conf;
+use crate::merge;
+
+const DEFAULT_REMOTE: &str = "origin";
+
+// Runs git in the directory and returns its output
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let out = match Command::new("git").arg("-C").arg(dir).args(args).output() {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Failed to run git: {e}")),
+    };
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("'git {0}' failed: {1}", args.join(" "), err.trim()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+// Returns the root of the git repository that contains the file
+fn repo_root(file: &Path) -> Option<PathBuf> {
+    let dir = file.parent()?;
+    let root = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+    fs::canonicalize(root.trim()).ok()
+}
+
+// Returns existing files of all task lists grouped by git repositories.
+// Files that are not in a repository are skipped.
+fn task_files(conf: &conf::Conf) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    let mut repos: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+    for tlist in &conf.task_lists {
+        let mut files = vec![tlist.todo_file.clone()];
+        files.extend(archive::archive_paths(&tlist.done_file, conf.archive_rotation));
+        for file in files {
+            let Ok(file) = fs::canonicalize(&file) else {
+                continue;
+            };
+            let Some(root) = repo_root(&file) else {
+                continue;
+            };
+            match repos.iter_mut().find(|(r, _)| *r == root) {
+                Some((_, l)) => {
+                    if !l.contains(&file) {
+                        l.push(file)
+                    }
+                }
+                None => repos.push((root, vec![file])),
+            }
+        }
+    }
+    repos
+}
+
+// Returns the number of changed lines in the most changed file of `git diff --numstat` output
+fn changed_count(numstat: &str) -> usize {
+    numstat
+        .lines()
+        .filter_map(|l| {
+            let mut vals = l.split('\t');
+            let added: usize = vals.next()?.parse().ok()?;
+            let deleted: usize = vals.next()?.parse().ok()?;
+            Some(added.max(deleted))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// Commits the files if they have changed. Returns true if a commit was made
+fn commit_files(root: &Path, files: &[PathBuf], action: &str) -> Result<bool, String> {
+    let paths: Vec<&str> = files.iter().filter_map(|f| f.to_str()).collect();
+    let mut args = vec!["add", "--"];
+    args.extend(&paths);
+    git(root, &args)?;
+    let mut args = vec!["diff", "--cached", "--numstat", "--"];
+    args.extend(&paths);
+    let numstat = git(root, &args)?;
+    if numstat.trim().is_empty() {
+        return Ok(false);
+    }
+    let msg = match changed_count(&numstat) {
+        0 => action.to_string(),
+        1 => format!("{action} 1 todo"),
+        n => format!("{action} {n} todos"),
+    };
+    let mut args = vec!["commit", "-q", "-m", &msg, "--"];
+    args.extend(&paths);
+    git(root, &args)?;
+    Ok(true)
+}
+
+// Commits changed task lists and archives in all repositories they belong to.
+// The commit message describes the command, e.g. "done 3 todos".
+pub fn commit_changes(conf: &conf::Conf, action: &str) -> Result<(), String> {
+    for (root, files) in task_files(conf) {
+        if commit_files(&root, &files, action)? && conf.verbose {
+            println!("Committed changes to '{0}'", root.display());
+        }
+    }
+    Ok(())
+}
+
+// Merges conflicting task files todo by todo and marks them resolved. Fails if any other file has
+// a conflict, or if both sides changed the same field of a todo: such conflicts must be resolved manually.
+fn resolve_conflicts(root: &Path, files: &[PathBuf]) -> Result<(), String> {
+    let conflicts = git(root, &["diff", "--name-only", "--diff-filter=U"])?;
+    let conflicts: Vec<&str> = conflicts.lines().filter(|l| !l.is_empty()).collect();
+    if conflicts.is_empty() {
+        return Err("Nothing to merge".to_string());
+    }
+    for path in conflicts {
+        if !files.contains(&root.join(path)) {
+            return Err(format!("Cannot merge '{path}': it is not a task list"));
+        }
+        // During a rebase, stage 2 is the remote branch, and stage 3 is the local commit being replayed
+        let base = git(root, &["show", &format!(":1:{path}")]).unwrap_or_default();
+        let ours = git(root, &["show", &format!(":3:{path}")]).unwrap_or_default();
+        let theirs = git(root, &["show", &format!(":2:{path}")]).unwrap_or_default();
+        let today = chrono::Local::now().date_naive();
+        let (merged, conflicts) = merge::merge_text(&base, &ours, &theirs, today);
+        if !conflicts.is_empty() {
+            return Err(format!(
+                "Local and remote changes of '{path}' conflict:\n{0}",
+                merge::conflict_report(&conflicts).trim_end()
+            ));
+        }
+        if let Err(e) = fs::write(root.join(path), merged) {
+            return Err(format!("Failed to save '{path}': {e}"));
+        }
+        git(root, &["add", "--", path])?;
+    }
+    Ok(())
+}
+
+// Commits local changes, rebases them on top of the remote branch, and pushes the result back.
+// Conflicts in task lists are resolved todo by todo.
+fn sync_repo(root: &Path, files: &[PathBuf], remote: &str) -> Result<(), String> {
+    commit_files(root, files, "sync")?;
+    let branch = git(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let branch = branch.trim();
+    let exists = !git(root, &["ls-remote", "--heads", remote, branch])?.trim().is_empty();
+    if exists {
+        git(root, &["fetch", "-q", remote, branch])?;
+        let mut res = git(root, &["rebase", "--autostash", "FETCH_HEAD"]);
+        while let Err(e) = res {
+            if let Err(merge_err) = resolve_conflicts(root, files) {
+                let _ = git(root, &["rebase", "--abort"]);
+                return Err(format!("{e}\n{merge_err}"));
+            }
+            res = git(root, &["-c", "core.editor=true", "rebase", "--continue"]);
+        }
+    }
+    git(root, &["push", "-q", remote, branch])?;
+    Ok(())
+}
+
+pub fn sync(conf: &conf::Conf) -> Result<(), String> {
+    let repos = task_files(conf);
+    if repos.is_empty() {
+        return Err("None of task lists is in a git repository".to_string());
+    }
+    let remote = conf.git_remote.as_deref().unwrap_or(DEFAULT_REMOTE);
+    for (root, files) in repos {
+        sync_repo(&root, &files, remote)?;
+        println!("Synchronized '{0}' with '{remote}'", root.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Creates a repository with a task list, and pushes it to a new bare repository
+    fn init_repo(dir: &Path, text: &str) -> PathBuf {
+        let remote = dir.join("remote.git");
+        let local = dir.join("local");
+        git(dir, &["init", "-q", "--bare", "-b", "main", remote.to_str().unwrap()]).unwrap();
+        git(dir, &["clone", "-q", remote.to_str().unwrap(), local.to_str().unwrap()]).unwrap();
+        setup_clone(&local);
+        fs::write(local.join("todo.txt"), text).unwrap();
+        git(&local, &["add", "todo.txt"]).unwrap();
+        git(&local, &["commit", "-q", "-m", "init"]).unwrap();
+        git(&local, &["push", "-q", "origin", "HEAD:main"]).unwrap();
+        remote
+    }
+
+    fn setup_clone(dir: &Path) {
+        git(dir, &["checkout", "-q", "-B", "main"]).unwrap();
+        git(dir, &["config", "user.name", "test"]).unwrap();
+        git(dir, &["config", "user.email", "test@example.com"]).unwrap();
+        git(dir, &["config", "commit.gpgsign", "false"]).unwrap();
+    }
+
+    // Pushes a change from another clone of the remote repository
+    fn push_remote_change(dir: &Path, remote: &Path, text: &str) {
+        let other = dir.join("other");
+        git(dir, &["clone", "-q", remote.to_str().unwrap(), other.to_str().unwrap()]).unwrap();
+        setup_clone(&other);
+        fs::write(other.join("todo.txt"), text).unwrap();
+        git(&other, &["commit", "-q", "-a", "-m", "remote"]).unwrap();
+        git(&other, &["push", "-q", "origin", "main"]).unwrap();
+    }
+
+    #[test]
+    fn sync_repo_test() {
+        struct Test {
+            remote: &'static str,
+            local: &'static str,
+            // None if the sync must fail because of a conflict
+            res: Option<&'static str>,
+        }
+        let base = "call mom\nbuy milk\n";
+        let tests: Vec<Test> = vec![
+            Test {
+                remote: "call mom +family\nbuy milk\n",
+                local: "call mom\n(A) buy milk\n",
+                res: Some("call mom +family\n(A) buy milk\n"),
+            },
+            Test { remote: "call mom\n(B) buy milk\n", local: "call mom\n(C) buy milk\n", res: None },
+        ];
+        for (idx, test) in tests.iter().enumerate() {
+            let dir = tempfile::tempdir().unwrap();
+            let dir = fs::canonicalize(dir.path()).unwrap();
+            let remote = init_repo(&dir, base);
+            push_remote_change(&dir, &remote, test.remote);
+            let local = dir.join("local");
+            let file = local.join("todo.txt");
+            fs::write(&file, test.local).unwrap();
+            let res = sync_repo(&local, std::slice::from_ref(&file), "origin");
+            match test.res {
+                Some(text) => {
+                    assert!(res.is_ok(), "{idx}: {res:?}");
+                    assert_eq!(fs::read_to_string(&file).unwrap(), text, "{idx}");
+                    let pushed = git(&dir, &["--git-dir", remote.to_str().unwrap(), "show", "main:todo.txt"]);
+                    assert_eq!(pushed.unwrap(), text, "{idx}");
+                }
+                None => {
+                    // The local commit is `ours` even though git calls it `theirs` during a rebase
+                    let err = res.unwrap_err();
+                    assert!(err.contains("priority is '(C)' in ours and '(B)' in theirs"), "{idx}: {err}");
+                    // The rebase is canceled, and the local change is kept
+                    assert!(!local.join(".git").join("rebase-merge").exists(), "{idx}");
+                    assert_eq!(fs::read_to_string(&file).unwrap(), test.local, "{idx}");
+                    let pushed = git(&dir, &["--git-dir", remote.to_str().unwrap(), "show", "main:todo.txt"]);
+                    assert_eq!(pushed.unwrap(), test.remote, "{idx}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn changed_count_test() {
+        assert_eq!(changed_count(""), 0);
+        assert_eq!(changed_count("3\t3\ttodo.txt\n0\t1\tdone.txt\n"), 3);
+        assert_eq!(changed_count("1\t0\ttodo.txt\n3\t0\tdone.txt\n"), 3);
+        assert_eq!(changed_count("-\t-\timage.png\n"), 0);
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+fn has_wildcard(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+// Returns true if any part of the path contains `*` or `?`
+pub fn is_wildcard(path: &Path) -> bool {
+    path.components().any(|c| has_wildcard(&c.as_os_str().to_string_lossy()))
+}
+
+// Matches a file name against a pattern: `*` - any number of any characters, `?` - any single character
+fn name_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| name_matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && name_matches(&pattern[1..], &name[1..]),
+        Some(c) => name.first() == Some(c) && name_matches(&pattern[1..], &name[1..]),
+    }
+}
+
+// Returns all entries of the directory `dir` which names match the pattern.
+// Hidden entries are skipped unless the pattern starts with a dot.
+fn matching_entries(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let rd_dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let show_hidden = pattern.first() == Some(&'.');
+    match fs::read_dir(rd_dir) {
+        Err(_) => Vec::new(),
+        Ok(rd) => rd
+            .map_while(Result::ok)
+            .filter(|e| {
+                let name: Vec<char> = e.file_name().to_string_lossy().chars().collect();
+                (show_hidden || name.first() != Some(&'.')) && name_matches(&pattern, &name)
+            })
+            .map(|e| dir.join(e.file_name()))
+            .collect(),
+    }
+}
+
+// Returns all existing files which paths match the pattern, sorted by name.
+// A wildcard may be used in any part of the path, e.g. `~/projects/*/todo.txt`.
+pub fn expand(path: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for comp in path.components() {
+        let part = comp.as_os_str().to_string_lossy().to_string();
+        let is_pattern = matches!(comp, Component::Normal(_)) && has_wildcard(&part);
+        paths = if is_pattern {
+            paths.iter().flat_map(|p| matching_entries(p, &part)).collect()
+        } else {
+            paths.iter().map(|p| p.join(&part)).collect()
+        };
+    }
+    let mut paths: Vec<PathBuf> = paths.into_iter().filter(|p| p.is_file()).collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_matches_test() {
+        struct Test {
+            pattern: &'static str,
+            name: &'static str,
+            res: bool,
+        }
+        let tests: Vec<Test> = vec![
+            Test { pattern: "done-*.txt", name: "done-2026-10.txt", res: true },
+            Test { pattern: "done-*.txt", name: "done.txt", res: false },
+            Test { pattern: "done*.txt", name: "done.txt", res: true },
+            Test { pattern: "done-????.txt", name: "done-2026.txt", res: true },
+            Test { pattern: "done-????.txt", name: "done-2026-10.txt", res: false },
+            Test { pattern: "*", name: "", res: true },
+        ];
+        for test in tests.iter() {
+            let p: Vec<char> = test.pattern.chars().collect();
+            let n: Vec<char> = test.name.chars().collect();
+            assert_eq!(test.res, name_matches(&p, &n), "{} - {}", test.pattern, test.name);
+        }
+    }
+
+    #[test]
+    fn expand_test() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["a", "b", ".hidden", "empty"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        for file in ["a/todo.txt", "b/todo.txt", ".hidden/todo.txt", "b/done.txt"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        let res = expand(&dir.path().join("*").join("todo.txt"));
+        assert_eq!(res, vec![dir.path().join("a/todo.txt"), dir.path().join("b/todo.txt")]);
+        let res = expand(&dir.path().join("b").join("*.txt"));
+        assert_eq!(res, vec![dir.path().join("b/done.txt"), dir.path().join("b/todo.txt")]);
+        let res = expand(&dir.path().join("*").join("none.txt"));
+        assert!(res.is_empty());
+    }
+}